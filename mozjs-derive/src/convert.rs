@@ -0,0 +1,366 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ffi::CString;
+
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{parse_quote, Attribute, Data, DeriveInput, Fields, Ident, LitStr, Path, Type};
+
+/// The `#[js(...)]` options of a field or variant.
+#[derive(Default)]
+struct Options {
+    rename: Option<String>,
+    default: Option<TokenStream>,
+    behavior: Option<TokenStream>,
+}
+
+impl Options {
+    fn parse(attrs: &[Attribute], field: bool) -> syn::Result<Options> {
+        let mut options = Options::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("js")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let name: LitStr = meta.value()?.parse()?;
+                    options.rename = Some(name.value());
+                } else if field && meta.path.is_ident("default") {
+                    options.default = Some(if meta.input.peek(syn::Token![=]) {
+                        let path: Path = meta.value()?.parse::<LitStr>()?.parse()?;
+                        quote!(#path())
+                    } else {
+                        quote!(::std::default::Default::default())
+                    });
+                } else if field && meta.path.is_ident("enforce_range") {
+                    options.behavior = Some(quote!(
+                        ::mozjs::conversions::ConversionBehavior::EnforceRange
+                    ));
+                } else if field && meta.path.is_ident("clamp") {
+                    options.behavior =
+                        Some(quote!(::mozjs::conversions::ConversionBehavior::Clamp));
+                } else {
+                    return Err(meta.error("unsupported js attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+struct Field<'a> {
+    ident: &'a Ident,
+    ty: &'a Type,
+    property: String,
+    /// The property name, as a C string literal.
+    name: Literal,
+    options: Options,
+}
+
+struct Variant<'a> {
+    ident: &'a Ident,
+    name: String,
+}
+
+pub(crate) fn c_string(name: &str, span: Span) -> syn::Result<Literal> {
+    let name = CString::new(name).map_err(|_| syn::Error::new(span, "names cannot contain NUL"))?;
+    Ok(Literal::c_string(&name))
+}
+
+fn fields(input: &DeriveInput) -> syn::Result<Vec<Field<'_>>> {
+    let Data::Struct(data) = &input.data else {
+        unreachable!();
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "only structs with named fields can be converted",
+        ));
+    };
+    fields
+        .named
+        .iter()
+        .map(|field| {
+            let ident = field.ident.as_ref().unwrap();
+            let options = Options::parse(&field.attrs, true)?;
+            let property = match &options.rename {
+                Some(name) => name.clone(),
+                None => ident.unraw().to_string(),
+            };
+            Ok(Field {
+                ident,
+                ty: &field.ty,
+                name: c_string(&property, field.span())?,
+                property,
+                options,
+            })
+        })
+        .collect()
+}
+
+fn variants(input: &DeriveInput) -> syn::Result<Vec<Variant<'_>>> {
+    let Data::Enum(data) = &input.data else {
+        unreachable!();
+    };
+    data.variants
+        .iter()
+        .map(|variant| {
+            if !matches!(variant.fields, Fields::Unit) {
+                return Err(syn::Error::new(
+                    variant.span(),
+                    "only fieldless enums can be converted",
+                ));
+            }
+            let options = Options::parse(&variant.attrs, false)?;
+            Ok(Variant {
+                ident: &variant.ident,
+                name: options
+                    .rename
+                    .unwrap_or_else(|| variant.ident.unraw().to_string()),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+pub fn derive_to_jsval(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let mut generics = input.generics.clone();
+    let body = match &input.data {
+        Data::Struct(_) => {
+            let fields = fields(input)?;
+            if !input.generics.params.is_empty() {
+                let where_clause = generics.make_where_clause();
+                for Field { ty, .. } in &fields {
+                    where_clause
+                        .predicates
+                        .push(parse_quote!(#ty: ::mozjs::conversions::ToJSValConvertible));
+                }
+            }
+            let properties = fields.iter().map(|Field { ident, name, .. }| {
+                quote! {
+                    ::mozjs::conversions::ToJSValConvertible::to_jsval(
+                        &self.#ident,
+                        cx,
+                        value.handle_mut(),
+                    );
+                    assert!(::mozjs::rust::wrappers::JS_DefineProperty(
+                        cx,
+                        obj.handle(),
+                        #name.as_ptr(),
+                        value.handle(),
+                        ::mozjs::jsapi::JSPROP_ENUMERATE as u32,
+                    ));
+                }
+            });
+            quote! {
+                ::mozjs::rooted!(in(cx) let obj = ::mozjs::jsapi::JS_NewPlainObject(cx));
+                assert!(!obj.get().is_null());
+                ::mozjs::rooted!(in(cx) let mut value = ::mozjs::jsval::UndefinedValue());
+                #(#properties)*
+                rval.set(::mozjs::jsval::ObjectValue(obj.get()));
+            }
+        }
+        Data::Enum(_) => {
+            let arms = variants(input)?.into_iter().map(
+                |Variant {
+                     ident: variant,
+                     name,
+                 }| quote!(#ident::#variant => #name,),
+            );
+            quote! {
+                let name: &str = match self {
+                    #(#arms)*
+                };
+                <str as ::mozjs::conversions::ToJSValConvertible>::to_jsval(name, cx, rval);
+            }
+        }
+        Data::Union(_) => return Err(syn::Error::new(ident.span(), "unions cannot be converted")),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mozjs::conversions::ToJSValConvertible for #ident #ty_generics
+        #where_clause
+        {
+            #[allow(unused_mut)]
+            unsafe fn to_jsval(
+                &self,
+                cx: *mut ::mozjs::jsapi::JSContext,
+                mut rval: ::mozjs::rust::MutableHandleValue,
+            ) {
+                unsafe { #body }
+            }
+        }
+    })
+}
+
+pub fn derive_from_jsval(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let mut generics = input.generics.clone();
+    let body = match &input.data {
+        Data::Struct(_) => {
+            let fields = fields(input)?;
+            // Bind members to fresh names, so that fields cannot shadow the
+            // variables of the generated code.
+            let bindings: Vec<_> = (0..fields.len())
+                .map(|i| Ident::new(&format!("__member{}", i), Span::call_site()))
+                .collect();
+            if !input.generics.params.is_empty() {
+                let where_clause = generics.make_where_clause();
+                for Field { ty, options, .. } in &fields {
+                    where_clause.predicates.push(match options.behavior {
+                        Some(_) => parse_quote! {
+                            #ty: ::mozjs::conversions::FromJSValConvertible<
+                                Config = ::mozjs::conversions::ConversionBehavior,
+                            >
+                        },
+                        None => parse_quote! {
+                            #ty: ::mozjs::conversions::FromJSValConvertible<
+                                Config: ::std::default::Default,
+                            >
+                        },
+                    });
+                }
+            }
+            let members = fields.iter().zip(&bindings).map(
+                |(
+                    Field {
+                        ty,
+                        property,
+                        name,
+                        options,
+                        ..
+                    },
+                    binding,
+                )| {
+                    let missing = match &options.default {
+                        Some(default) => default.clone(),
+                        None if is_option(ty) => quote!(::std::option::Option::None),
+                        None => {
+                            let message = c_string(
+                                &format!("Missing required member {} of {}", property, ident),
+                                Span::call_site(),
+                            )
+                            .unwrap();
+                            quote! {
+                                return Ok(::mozjs::conversions::ConversionResult::Failure(
+                                    #message.into(),
+                                ));
+                            }
+                        }
+                    };
+                    let config = match &options.behavior {
+                        Some(behavior) => behavior.clone(),
+                        None => quote!(::std::default::Default::default()),
+                    };
+                    quote! {
+                        member.set(::mozjs::jsval::UndefinedValue());
+                        if !obj.get().is_null()
+                            && !::mozjs::rust::wrappers::JS_GetProperty(
+                                cx,
+                                obj.handle(),
+                                #name.as_ptr(),
+                                member.handle_mut(),
+                            )
+                        {
+                            return Err(());
+                        }
+                        let #binding: #ty = if member.get().is_undefined() {
+                            #missing
+                        } else {
+                            match ::mozjs::conversions::FromJSValConvertible::from_jsval(
+                                cx,
+                                member.handle(),
+                                #config,
+                            )? {
+                                ::mozjs::conversions::ConversionResult::Success(value) => value,
+                                ::mozjs::conversions::ConversionResult::Failure(error) => {
+                                    return Ok(::mozjs::conversions::ConversionResult::Failure(
+                                        error,
+                                    ));
+                                },
+                            }
+                        };
+                    }
+                },
+            );
+            let idents = fields.iter().map(|field| field.ident);
+            quote! {
+                let obj = if value.get().is_object() {
+                    value.get().to_object()
+                } else if value.get().is_null_or_undefined() {
+                    ::std::ptr::null_mut()
+                } else {
+                    return Ok(::mozjs::conversions::ConversionResult::Failure(
+                        c"Value is not an object".into(),
+                    ));
+                };
+                ::mozjs::rooted!(in(cx) let obj = obj);
+                ::mozjs::rooted!(in(cx) let mut member = ::mozjs::jsval::UndefinedValue());
+                #(#members)*
+                Ok(::mozjs::conversions::ConversionResult::Success(Self {
+                    #(#idents: #bindings),*
+                }))
+            }
+        }
+        Data::Enum(_) => {
+            let arms = variants(input)?.into_iter().map(
+                |Variant {
+                     ident: variant,
+                     name,
+                 }| quote!(#name => #ident::#variant,),
+            );
+            let message = c_string(
+                &format!("Value is not a valid value for enumeration {}", ident),
+                ident.span(),
+            )?;
+            quote! {
+                let name = match <::std::string::String as ::mozjs::conversions::FromJSValConvertible>::from_jsval(cx, value, ())? {
+                    ::mozjs::conversions::ConversionResult::Success(name) => name,
+                    ::mozjs::conversions::ConversionResult::Failure(error) => {
+                        return Ok(::mozjs::conversions::ConversionResult::Failure(error));
+                    },
+                };
+                Ok(::mozjs::conversions::ConversionResult::Success(match &*name {
+                    #(#arms)*
+                    _ => {
+                        return Ok(::mozjs::conversions::ConversionResult::Failure(
+                            #message.into(),
+                        ));
+                    },
+                }))
+            }
+        }
+        Data::Union(_) => return Err(syn::Error::new(ident.span(), "unions cannot be converted")),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mozjs::conversions::FromJSValConvertible for #ident #ty_generics
+        #where_clause
+        {
+            type Config = ();
+
+            unsafe fn from_jsval(
+                cx: *mut ::mozjs::jsapi::JSContext,
+                value: ::mozjs::rust::HandleValue,
+                _option: (),
+            ) -> Result<::mozjs::conversions::ConversionResult<Self>, ()> {
+                unsafe { #body }
+            }
+        }
+    })
+}
@@ -0,0 +1,108 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Derive and attribute macros for the `mozjs` crate. Use them through their re-exports
+//! in `mozjs`, since the generated code refers to `::mozjs` paths.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, ItemFn};
+
+mod convert;
+mod native;
+mod trace;
+
+/// Derives `mozjs::conversions::ToJSValConvertible`.
+///
+/// Structs with named fields convert to plain objects with a property per
+/// field, like WebIDL dictionaries. Fieldless enums convert to the name of
+/// their variant, like WebIDL enums.
+///
+/// `#[js(rename = "name")]` changes the name of a property or enum value.
+#[proc_macro_derive(ToJSValConvertible, attributes(js))]
+pub fn derive_to_jsval_convertible(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    convert::derive_to_jsval(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `mozjs::conversions::FromJSValConvertible`, with `()` as its
+/// `Config`.
+///
+/// Structs with named fields convert from objects, or from `null` and
+/// `undefined` as if all their properties were `undefined`, like WebIDL
+/// dictionaries. Properties are read in field order. An `undefined`
+/// property is an error unless the field is an `Option` or has a default.
+/// Fieldless enums convert from strings equal to the name of a variant.
+/// Values of the wrong shape convert to `ConversionResult::Failure`.
+///
+/// Attributes:
+///
+/// * `#[js(rename = "name")]` changes the name of a property or enum value.
+/// * `#[js(default)]` uses `Default::default()` for `undefined` properties,
+///   and `#[js(default = "path")]` calls `path()` instead.
+/// * `#[js(enforce_range)]` and `#[js(clamp)]` convert an integer field
+///   with `ConversionBehavior::EnforceRange` or `ConversionBehavior::Clamp`.
+#[proc_macro_derive(FromJSValConvertible, attributes(js))]
+pub fn derive_from_jsval_convertible(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    convert::derive_from_jsval(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `mozjs::gc::Traceable`, tracing every field of a struct or of the
+/// active enum variant.
+///
+/// Fields that hold no GC things can opt out with `#[no_trace]`. Every other
+/// field must implement `Traceable`: a concrete field type that does not is a
+/// compile error, and the types of fields that mention a type parameter
+/// become bounds of the impl.
+#[proc_macro_derive(Traceable, attributes(no_trace))]
+pub fn derive_traceable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    trace::derive_traceable(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generates a `JSNative` calling a module-level function, with its arguments
+/// converted by `mozjs::conversions::FromJSValConvertible` and its result by
+/// `mozjs::conversions::ToJSValConvertible`.
+///
+/// ```ignore
+/// #[js_native]
+/// fn encode(cx: &mut JSContext, text: String, level: Option<f64>) -> Result<Vec<u8>, Exception> {
+///     ...
+/// }
+///
+/// const METHODS: &[JSFunctionSpec] = &[encode::SPEC, JSFunctionSpec::ZERO];
+/// ```
+///
+/// The function is kept, and a module of the same name holds the generated
+/// `native` and `SPEC`, a `JSFunctionSpec` for `mozjs::rust::define_methods`.
+///
+/// * A leading `&mut` parameter receives the context of the call.
+/// * Every other parameter is converted from an argument, with the default
+///   configuration of its type. `#[js(enforce_range)]` and `#[js(clamp)]`
+///   convert an integer with `ConversionBehavior::EnforceRange` or
+///   `ConversionBehavior::Clamp` instead.
+/// * Trailing `Option` parameters are optional. Calls with fewer arguments
+///   than the other parameters throw a `TypeError`, as do arguments that
+///   cannot be converted, naming the argument.
+/// * A returned `Err(exception)` is thrown to the caller, and panics are
+///   caught with `mozjs::panic::wrap_panic`.
+///
+/// `#[js_native(name = "name")]` changes the name of the function in
+/// JavaScript, which defaults to the name of the Rust function.
+#[proc_macro_attribute]
+pub fn js_native(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = native::NativeOptions::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta));
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    native::js_native(options, function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,244 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
+use syn::spanned::Spanned;
+use syn::{FnArg, ItemFn, LitStr, ReturnType, Type};
+
+use crate::convert::{c_string, is_option};
+
+/// The options of `#[js_native(...)]`.
+#[derive(Default)]
+pub struct NativeOptions {
+    name: Option<String>,
+}
+
+impl NativeOptions {
+    pub fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("name") {
+            let name: LitStr = meta.value()?.parse()?;
+            self.name = Some(name.value());
+            Ok(())
+        } else {
+            Err(meta.error("unsupported js_native option"))
+        }
+    }
+}
+
+/// A parameter converted from an argument of the call.
+struct Argument {
+    ty: Type,
+    /// The `ConversionBehavior` of `#[js(enforce_range)]` or `#[js(clamp)]`.
+    behavior: Option<TokenStream>,
+}
+
+fn is_result(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Result"),
+        _ => false,
+    }
+}
+
+/// Take the `#[js(...)]` attributes off `arg` and parse them.
+fn argument(arg: &mut FnArg) -> syn::Result<Argument> {
+    let FnArg::Typed(arg) = arg else {
+        return Err(syn::Error::new(
+            arg.span(),
+            "js_native functions cannot take self",
+        ));
+    };
+    let mut behavior = None;
+    for attr in arg.attrs.iter().filter(|attr| attr.path().is_ident("js")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("enforce_range") {
+                behavior = Some(quote!(
+                    ::mozjs::conversions::ConversionBehavior::EnforceRange
+                ));
+            } else if meta.path.is_ident("clamp") {
+                behavior = Some(quote!(::mozjs::conversions::ConversionBehavior::Clamp));
+            } else {
+                return Err(meta.error("unsupported js attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    arg.attrs.retain(|attr| !attr.path().is_ident("js"));
+    Ok(Argument {
+        ty: (*arg.ty).clone(),
+        behavior,
+    })
+}
+
+pub fn js_native(options: NativeOptions, mut function: ItemFn) -> syn::Result<TokenStream> {
+    let sig = &function.sig;
+    if sig.asyncness.is_some() || !sig.generics.params.is_empty() || sig.variadic.is_some() {
+        return Err(syn::Error::new(
+            sig.span(),
+            "js_native functions cannot be async, generic or variadic",
+        ));
+    }
+    let ident = sig.ident.clone();
+    let name = options.name.unwrap_or_else(|| ident.unraw().to_string());
+    let c_name = c_string(&name, ident.span())?;
+
+    // A leading `&mut` parameter receives the context.
+    let mut inputs = function.sig.inputs.iter_mut().peekable();
+    let takes_cx = matches!(
+        inputs.peek(),
+        Some(FnArg::Typed(arg)) if matches!(&*arg.ty, Type::Reference(reference) if reference.mutability.is_some())
+    );
+    if takes_cx {
+        inputs.next();
+    }
+    let arguments = inputs.map(argument).collect::<syn::Result<Vec<_>>>()?;
+
+    // Like WebIDL, trailing `Option` arguments are optional and do not count
+    // towards the `length` of the function.
+    let required = arguments.len()
+        - arguments
+            .iter()
+            .rev()
+            .take_while(|argument| is_option(&argument.ty))
+            .count();
+    let required = required as u32;
+
+    let bindings: Vec<_> = (0..arguments.len())
+        .map(|i| format_ident!("__arg{}", i))
+        .collect();
+    let conversions = arguments.iter().zip(&bindings).enumerate().map(
+        |(index, (Argument { ty, behavior }, binding))| {
+            let config = match behavior {
+                Some(behavior) => behavior.clone(),
+                None => quote!(::std::default::Default::default()),
+            };
+            let index = index as u32;
+            let position = index + 1;
+            quote_spanned! {ty.span()=>
+                let #binding: #ty = match ::mozjs::conversions::FromJSValConvertible::from_jsval(
+                    cx,
+                    ::mozjs::rust::Handle::from_raw(args.get(#index)),
+                    #config,
+                ) {
+                    Ok(::mozjs::conversions::ConversionResult::Success(value)) => value,
+                    Ok(::mozjs::conversions::ConversionResult::Failure(reason)) => {
+                        ::mozjs::error::throw_error(
+                            cx,
+                            ::mozjs::jsapi::JSExnType::JSEXN_TYPEERR,
+                            format_args!(
+                                "{}: argument {}: {}",
+                                #name,
+                                #position,
+                                reason.to_string_lossy(),
+                            ),
+                        );
+                        return;
+                    }
+                    Err(()) => return,
+                };
+            }
+        },
+    );
+
+    let context = takes_cx.then(|| {
+        quote! {
+            let mut context =
+                ::mozjs::context::JSContext::from_ptr(::std::ptr::NonNull::new(cx).unwrap());
+        }
+    });
+    let context_arg = takes_cx.then(|| quote!(&mut context,));
+    let call = quote!(super::#ident(#context_arg #(#bindings),*));
+    let set_rval = quote! {
+        ::mozjs::rooted!(in(cx) let mut rval = ::mozjs::jsval::UndefinedValue());
+        ::mozjs::conversions::ToJSValConvertible::to_jsval(&value, cx, rval.handle_mut());
+        args.rval().set(rval.get());
+        result = true;
+    };
+    let body = match &function.sig.output {
+        ReturnType::Type(_, ty) if is_result(ty) => quote! {
+            match #call {
+                Ok(value) => { #set_rval }
+                Err(exception) => ::mozjs::error::Exception::throw(&exception, cx),
+            }
+        },
+        ReturnType::Type(..) => quote! {
+            let value = #call;
+            #set_rval
+        },
+        ReturnType::Default => quote! {
+            #call;
+            args.rval().set(::mozjs::jsval::UndefinedValue());
+            result = true;
+        },
+    };
+
+    let check_argc = (required > 0).then(|| {
+        let message = format!(
+            "{{}}: At least {} argument{} required, but only {{}} passed",
+            required,
+            if required == 1 { "" } else { "s" },
+        );
+        quote! {
+            if argc < #required {
+                ::mozjs::error::throw_error(
+                    cx,
+                    ::mozjs::jsapi::JSExnType::JSEXN_TYPEERR,
+                    format_args!(#message, #name, argc),
+                );
+                return;
+            }
+        }
+    });
+
+    let vis = &function.vis;
+    let module_doc = format!("The `JSNative` generated for [`{}`](fn@{}).", ident, ident);
+    Ok(quote! {
+        #function
+
+        #[doc = #module_doc]
+        #[allow(non_snake_case)]
+        #vis mod #ident {
+            #[allow(unused_imports)]
+            use super::*;
+
+            /// Calls the function with the converted arguments of the call.
+            pub unsafe extern "C" fn native(
+                cx: *mut ::mozjs::jsapi::JSContext,
+                argc: u32,
+                vp: *mut ::mozjs::jsapi::Value,
+            ) -> bool {
+                let mut result = false;
+                ::mozjs::panic::wrap_panic(&mut || unsafe {
+                    let args = ::mozjs::jsapi::CallArgs::from_vp(vp, argc);
+                    #check_argc
+                    #(#conversions)*
+                    #context
+                    #body
+                });
+                result
+            }
+
+            /// The entry of the function in a `JSFunctionSpec` array, for
+            /// `mozjs::rust::define_methods`.
+            pub const SPEC: ::mozjs::jsapi::JSFunctionSpec = ::mozjs::jsapi::JSFunctionSpec {
+                name: ::mozjs::jsapi::JSPropertySpec_Name {
+                    string_: #c_name.as_ptr(),
+                },
+                call: ::mozjs::jsapi::JSNativeWrapper {
+                    op: Some(native),
+                    info: ::std::ptr::null(),
+                },
+                nargs: #required as u16,
+                flags: ::mozjs::jsapi::JSPROP_ENUMERATE as u16,
+                selfHostedName: ::std::ptr::null(),
+            };
+        }
+    })
+}
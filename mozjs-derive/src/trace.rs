@@ -0,0 +1,133 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+use syn::{parse_quote, Data, DeriveInput, Fields, Ident, Index, Type};
+
+/// Whether `tokens` mention one of `params`.
+fn mentions(tokens: TokenStream, params: &[Ident]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => params.contains(&ident),
+        TokenTree::Group(group) => mentions(group.stream(), params),
+        _ => false,
+    })
+}
+
+/// The fields to trace, with the expressions that reach them.
+fn traced_fields(
+    fields: &Fields,
+    access: impl Fn(usize, TokenStream) -> TokenStream,
+) -> Vec<(TokenStream, &Type)> {
+    fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| {
+            !field
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("no_trace"))
+        })
+        .map(|(i, field)| {
+            let member = match &field.ident {
+                Some(ident) => ident.to_token_stream(),
+                None => Index::from(i).to_token_stream(),
+            };
+            (access(i, member), &field.ty)
+        })
+        .collect()
+}
+
+/// A pattern binding the fields of a variant to `__field{i}`.
+fn variant_pattern(fields: &Fields) -> TokenStream {
+    let bindings = fields.iter().enumerate().map(|(i, field)| {
+        let binding = Ident::new(&format!("__field{}", i), Span::call_site());
+        match &field.ident {
+            Some(ident) => quote!(#ident: #binding),
+            None => quote!(#binding),
+        }
+    });
+    match fields {
+        Fields::Named(_) => quote!({ #(#bindings),* }),
+        Fields::Unnamed(_) => quote!(( #(#bindings),* )),
+        Fields::Unit => quote!(),
+    }
+}
+
+fn trace_calls(fields: &[(TokenStream, &Type)]) -> TokenStream {
+    let calls = fields.iter().map(|(expr, ty)| {
+        quote_spanned! {ty.span()=>
+            ::mozjs::gc::Traceable::trace(#expr, trc);
+        }
+    });
+    quote!(#(#calls)*)
+}
+
+pub fn derive_traceable(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let params: Vec<Ident> = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let mut traced_types = vec![];
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = traced_fields(&data.fields, |_, member| quote!(&self.#member));
+            traced_types.extend(fields.iter().map(|(_, ty)| *ty));
+            trace_calls(&fields)
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let name = &variant.ident;
+                let pattern = variant_pattern(&variant.fields);
+                let fields = traced_fields(&variant.fields, |i, _| {
+                    let binding = Ident::new(&format!("__field{}", i), Span::call_site());
+                    quote!(#binding)
+                });
+                traced_types.extend(fields.iter().map(|(_, ty)| *ty));
+                let calls = trace_calls(&fields);
+                quote! {
+                    #[allow(unused_variables)]
+                    #ident::#name #pattern => { #calls },
+                }
+            });
+            let arms: Vec<_> = arms.collect();
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => return Err(syn::Error::new(ident.span(), "unions cannot be traced")),
+    };
+
+    // Require the types of traced fields that depend on type parameters to
+    // be traceable; the others are checked by the calls to `trace`.
+    let mut generics = input.generics.clone();
+    if !params.is_empty() {
+        let where_clause = generics.make_where_clause();
+        for ty in traced_types {
+            if mentions(ty.to_token_stream(), &params) {
+                where_clause
+                    .predicates
+                    .push(parse_quote!(#ty: ::mozjs::gc::Traceable));
+            }
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        unsafe impl #impl_generics ::mozjs::gc::Traceable for #ident #ty_generics
+        #where_clause
+        {
+            #[inline]
+            unsafe fn trace(&self, trc: *mut ::mozjs::jsapi::JSTracer) {
+                unsafe { #body }
+            }
+        }
+    })
+}
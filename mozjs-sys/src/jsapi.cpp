@@ -16,6 +16,8 @@
 #include "js/Conversions.h"
 #include "js/Date.h"
 #include "js/EnvironmentChain.h"
+#include "js/ErrorReport.h"
+#include "js/Exception.h"
 #include "js/Equality.h"
 #include "js/ForOfIterator.h"
 #include "js/Id.h"
@@ -36,10 +38,12 @@
 #include "js/String.h"
 #include "js/StructuredClone.h"
 #include "js/Symbol.h"
+#include "js/Transcoding.h"
 #include "js/UniquePtr.h"
 #include "js/Utility.h"
 #include "js/Warnings.h"
 #include "js/WasmModule.h"
+#include "js/experimental/CompileScript.h"
 #include "js/experimental/JSStencil.h"
 #include "js/experimental/JitInfo.h"
 #include "js/experimental/TypedData.h"
@@ -319,6 +323,23 @@ bool CreateError(JSContext* cx, JSExnType type, JS::HandleObject stack,
       rval);
 }
 
+bool CreateErrorWithOptionalCause(JSContext* cx, JSExnType type,
+                                  JS::HandleObject stack,
+                                  JS::HandleString fileName,
+                                  uint32_t lineNumber, uint32_t columnNumber,
+                                  JSErrorReport* report,
+                                  JS::HandleString message,
+                                  JS::HandleValue cause, bool hasCause,
+                                  JS::MutableHandleValue rval) {
+  JS::Rooted<mozilla::Maybe<JS::Value>> maybeCause(cx);
+  if (hasCause) {
+    maybeCause = mozilla::Some(cause.get());
+  }
+  return JS::CreateError(cx, type, stack, fileName, lineNumber,
+                         JS::ColumnNumberOneOrigin(columnNumber), report,
+                         message, maybeCause, rval);
+}
+
 JSExnType GetErrorType(const JS::Value& val) {
   auto type = JS_GetErrorType(val);
   if (type.isNothing()) {
@@ -336,6 +357,34 @@ void GetExceptionCause(JSObject* exc, JS::MutableHandleValue dest) {
   }
 }
 
+bool StealPendingExceptionWithStack(JSContext* cx,
+                                    JS::MutableHandleValue exception,
+                                    JS::MutableHandleObject stack) {
+  JS::ExceptionStack exnStack(cx);
+  if (!JS::StealPendingExceptionStack(cx, &exnStack)) {
+    return false;
+  }
+  exception.set(exnStack.exception());
+  stack.set(exnStack.stack());
+  return true;
+}
+
+size_t GetErrorNotesLength(JSErrorReport* report) {
+  return report->notes ? report->notes->length() : 0;
+}
+
+const JSErrorBase* GetErrorNote(JSErrorReport* report, size_t index) {
+  if (!report->notes) {
+    return nullptr;
+  }
+  for (auto&& note : *report->notes) {
+    if (index-- == 0) {
+      return note.get();
+    }
+  }
+  return nullptr;
+}
+
 JS::EnvironmentChain* NewEnvironmentChain(
     JSContext* cx, JS::SupportUnscopables supportUnscopables) {
   return new JS::EnvironmentChain(cx, supportUnscopables);
@@ -347,6 +396,97 @@ bool AppendToEnvironmentChain(JS::EnvironmentChain* chain, JSObject* obj) {
   return chain->append(obj);
 }
 
+// Compile options for a JS::FrontendContext, which has no JSContext to take
+// the prefable options from. Free with DeleteCompileOptions.
+JS::ReadOnlyCompileOptions* NewFrontendCompileOptions(JS::FrontendContext* fc,
+                                                      const char* filename,
+                                                      uint32_t lineno) {
+  JS::CompileOptions options((JS::PrefableCompileOptions()));
+  options.setFileAndLine(filename, lineno);
+
+  JS::OwningCompileOptions* owned = new JS::OwningCompileOptions(
+      JS::OwningCompileOptions::ForFrontendContext());
+  if (!owned->copy(fc, options)) {
+    delete owned;
+    return nullptr;
+  }
+  return owned;
+}
+
+already_AddRefed<JS::Stencil> CompileGlobalScriptToStencilWithFrontendContext(
+    JS::FrontendContext* fc, const JS::ReadOnlyCompileOptions& options,
+    const char* chars, size_t length) {
+  JS::SourceText<mozilla::Utf8Unit> source;
+  if (!source.init(fc, chars, length, JS::SourceOwnership::Borrowed)) {
+    return nullptr;
+  }
+  return JS::CompileGlobalScriptToStencil(fc, options, source);
+}
+
+JSScript* InstantiateGlobalStencilWithDefaultOptions(JSContext* cx,
+                                                     JS::Stencil* stencil) {
+  JS::InstantiateOptions options;
+  return JS::InstantiateGlobalStencil(cx, options, stencil);
+}
+
+static const char* gTranscodingBuildId = nullptr;
+
+static bool GetTranscodingBuildId(JS::BuildIdCharVector* buildId) {
+  return buildId->append(gTranscodingBuildId, strlen(gTranscodingBuildId));
+}
+
+// Install a BuildIdOp reporting `buildId`, which must outlive the process.
+void SetTranscodingBuildId(const char* buildId) {
+  gTranscodingBuildId = buildId;
+  JS::SetProcessBuildIdOp(GetTranscodingBuildId);
+}
+
+// Copy up to `capacity` bytes of the transcoding build ID into `buffer` and
+// return its full length, or 0 on failure.
+size_t GetScriptTranscodingBuildId(char* buffer, size_t capacity) {
+  JS::BuildIdCharVector buildId;
+  if (!JS::GetScriptTranscodingBuildId(&buildId)) {
+    return 0;
+  }
+  size_t copied = capacity < buildId.length() ? capacity : buildId.length();
+  memcpy(buffer, buildId.begin(), copied);
+  return buildId.length();
+}
+
+// Encode `stencil` and pass the bytes to `callback` on success.
+JS::TranscodeResult EncodeStencil(JSContext* cx, JS::Stencil* stencil,
+                                  void* data,
+                                  void (*callback)(void* data,
+                                                   const uint8_t* bytes,
+                                                   size_t length)) {
+  JS::TranscodeBuffer buffer;
+  JS::TranscodeResult result = JS::EncodeStencil(cx, stencil, buffer);
+  if (result == JS::TranscodeResult::Ok) {
+    callback(data, buffer.begin(), buffer.length());
+  }
+  return result;
+}
+
+already_AddRefed<JS::Stencil> DecodeStencilOffThread(
+    JS::FrontendContext* fc, const uint8_t* bytes, size_t length,
+    JS::TranscodeResult* result) {
+  // The decoder reads the buffer in place, so it has to be aligned.
+  JS::TranscodeBuffer aligned;
+  if (!JS::IsTranscodingBytecodeAligned(bytes)) {
+    if (!aligned.append(bytes, length)) {
+      *result = JS::TranscodeResult::Throw;
+      return nullptr;
+    }
+    bytes = aligned.begin();
+  }
+
+  JS::DecodeOptions options;
+  JS::TranscodeRange range(bytes, length);
+  JS::Stencil* stencil = nullptr;
+  *result = JS::DecodeStencil(fc, options, range, &stencil);
+  return already_AddRefed<JS::Stencil>(stencil);
+}
+
 }  // namespace glue
 
 // There's a couple of classes from pre-57 releases of SM that bindgen can't
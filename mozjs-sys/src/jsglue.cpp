@@ -134,7 +134,12 @@ class RustJobQueue : public JS::JobQueue {
       // (SavedQueue from C to B). If the SavedQueue from B to A is restored
       // before the SavedQueue from C to B, the embedder will destroy both C and
       // B, but in the end, the queue will be set to B, a freed queue.
-      MOZ_ASSERT(mTraps.popInterruptQueue(mInterruptQueues) == mNewQueue);
+      //
+      // The pop has to happen outside the assertion, otherwise release builds
+      // would never destroy the queue.
+      const void* poppedQueue = mTraps.popInterruptQueue(mInterruptQueues);
+      MOZ_ASSERT(poppedQueue == mNewQueue);
+      (void)poppedQueue;
 
       *mCurrentQueue = mSavedQueue;
     }
@@ -605,6 +610,33 @@ class ServoDOMVisitor : public JS::ObjectPrivateVisitor {
       : ObjectPrivateVisitor(getISupports), get_size(gs) {}
 };
 
+// The sizes reported by CollectMemoryReport, for the whole runtime and for
+// the kinds of things a report breaks down.
+struct MemoryReportSizes {
+  JS::ServoSizes total;
+  JS::ServoSizes objects;
+  JS::ServoSizes strings;
+  JS::ServoSizes shapes;
+  JS::ServoSizes jitCode;
+};
+
+typedef void (*RealmSizesCallback)(void* data, JS::Realm* realm,
+                                   const JS::ServoSizes* sizes);
+
+class ReportRuntimeStats : public JS::RuntimeStats {
+ public:
+  explicit ReportRuntimeStats(mozilla::MallocSizeOf mallocSizeOf)
+      : JS::RuntimeStats(mallocSizeOf) {}
+
+  void initExtraZoneStats(JS::Zone* zone, JS::ZoneStats* zStats,
+                          const JS::AutoRequireNoGC& nogc) override {}
+
+  void initExtraRealmStats(JS::Realm* realm, JS::RealmStats* realmStats,
+                           const JS::AutoRequireNoGC& nogc) override {
+    realmStats->extra = realm;
+  }
+};
+
 struct JSPrincipalsCallbacks {
   bool (*write)(JSPrincipals*, JSContext* cx, JSStructuredCloneWriter* writer);
   bool (*isSystemOrAddonPrincipal)(JSPrincipals*);
@@ -722,6 +754,32 @@ const void* CreateProxyHandler(const ProxyTraps* aTraps, const void* aExtra) {
   return new ForwardingProxyHandler(*aTraps, aExtra);
 }
 
+void DeleteProxyHandler(const void* handler) {
+  delete static_cast<const ForwardingProxyHandler*>(handler);
+}
+
+// The BaseProxyHandler implementations of the optional traps, for handlers
+// that only override some of them.
+bool CallBaseProxyHas(JSContext* cx, JS::HandleObject proxy, JS::HandleId id,
+                      bool* bp) {
+  return js::GetProxyHandler(proxy)->js::BaseProxyHandler::has(cx, proxy, id,
+                                                               bp);
+}
+
+bool CallBaseProxyGet(JSContext* cx, JS::HandleObject proxy,
+                      JS::HandleValue receiver, JS::HandleId id,
+                      JS::MutableHandleValue vp) {
+  return js::GetProxyHandler(proxy)->js::BaseProxyHandler::get(
+      cx, proxy, receiver, id, vp);
+}
+
+bool CallBaseProxySet(JSContext* cx, JS::HandleObject proxy, JS::HandleId id,
+                      JS::HandleValue v, JS::HandleValue receiver,
+                      JS::ObjectOpResult& result) {
+  return js::GetProxyHandler(proxy)->js::BaseProxyHandler::set(
+      cx, proxy, id, v, receiver, result);
+}
+
 const void* CreateWrapperProxyHandler(const ProxyTraps* aTraps) {
   return new WrapperProxyHandler(*aTraps);
 }
@@ -971,6 +1029,52 @@ bool CollectServoSizes(JSContext* cx, JS::ServoSizes* sizes, GetSize gs) {
 
 void InitializeMemoryReporter(WantToMeasure wtm) { gWantToMeasure = wtm; }
 
+bool CollectMemoryReport(JSContext* cx, MemoryReportSizes* sizes, void* data,
+                         RealmSizesCallback realmCallback) {
+  mozilla::PodZero(sizes);
+
+  ReportRuntimeStats rtStats(MallocSizeOf);
+  if (!JS::CollectRuntimeStats(cx, &rtStats, nullptr, false)) {
+    return false;
+  }
+
+  rtStats.addToServoSizes(&sizes->total);
+  rtStats.zTotals.addToServoSizes(&sizes->total);
+  rtStats.realmTotals.addToServoSizes(&sizes->total);
+  rtStats.realmTotals.classInfo.addToServoSizes(&sizes->objects);
+  rtStats.zTotals.stringInfo.addToServoSizes(&sizes->strings);
+  rtStats.zTotals.shapeInfo.addToServoSizes(&sizes->shapes);
+  rtStats.zTotals.code.addToServoSizes(&sizes->jitCode);
+
+  for (const JS::RealmStats& realmStats : rtStats.realmStatsVector) {
+    JS::ServoSizes realmSizes;
+    realmStats.addToServoSizes(&realmSizes);
+    realmCallback(data, static_cast<JS::Realm*>(realmStats.extra),
+                  &realmSizes);
+  }
+  return true;
+}
+
+// Slice budgets cannot be created from Rust, so take them in microseconds.
+
+static JS::SliceBudget TimeSliceBudget(int64_t micros, bool idle) {
+  JS::SliceBudget budget(
+      JS::TimeBudget(mozilla::TimeDuration::FromMicroseconds(micros)));
+  budget.idle = idle;
+  return budget;
+}
+
+void StartIncrementalGCWithBudget(JSContext* cx, JS::GCOptions options,
+                                  JS::GCReason reason, int64_t micros,
+                                  bool idle) {
+  JS::StartIncrementalGC(cx, options, reason, TimeSliceBudget(micros, idle));
+}
+
+void IncrementalGCSliceWithBudget(JSContext* cx, JS::GCReason reason,
+                                  int64_t micros, bool idle) {
+  JS::IncrementalGCSlice(cx, reason, TimeSliceBudget(micros, idle));
+}
+
 // Expose templated functions for tracing
 
 void CallValueTracer(JSTracer* trc, JS::Heap<JS::Value>* valuep,
@@ -50,10 +50,14 @@ fn run(mut rt: Runtime) {
     let options = CompileOptionsWrapper::new(rt.cx_no_gc(), filename, lineno);
     let res = evaluate_script(rt.cx(), global.handle(), source, rval.handle_mut(), options);
 
-    if res.is_ok() {
-        /* Should get a number back from the example source. */
-        assert!(rval.get().is_int32());
-        assert_eq!(rval.get().to_int32(), 42);
+    match res {
+        Ok(()) => {
+            /* Should get a number back from the example source. */
+            assert!(rval.get().is_int32());
+            assert_eq!(rval.get().to_int32(), 42);
+        }
+        /* The exception describes what went wrong, and where. */
+        Err(exception) => eprintln!("{}", exception),
     }
 }
 
@@ -0,0 +1,312 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Rust structs as JavaScript classes.
+//!
+//! A [`JsClass<T>`] describes a class whose instances own a boxed `T`. The
+//! `trace` hook of the class traces the value with [`Traceable`], and its
+//! `finalize` hook drops it. [`JsClass::init`] defines the constructor and
+//! the prototype with `JS_InitClass`, and [`unwrap`] returns the value of an
+//! instance after checking its class.
+//!
+//! ```ignore
+//! #[derive(Traceable)]
+//! struct Point {
+//!     x: f64,
+//!     y: f64,
+//! }
+//!
+//! unsafe extern "C" fn construct(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+//!     let args = CallArgs::from_vp(vp, argc);
+//!     let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+//!     let point = Point { x: 0.0, y: 0.0 };
+//!     let obj = JsClass::new_object_for_constructor(&mut cx, &args, point);
+//!     args.rval().set(ObjectValue(obj));
+//!     !obj.is_null()
+//! }
+//!
+//! JsClass::<Point>::new(c"Point")
+//!     .constructor(Some(construct), 0)
+//!     .methods(METHODS)
+//!     .init(cx, global, HandleObject::null());
+//! ```
+//!
+//! Each `T` has a single `JSClass`, which is created by the first
+//! [`JsClass::new`] for `T` and lives for the rest of the program, so that it
+//! can be used in any realm.
+
+use std::any::TypeId;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::Mutex;
+
+use crate::context::JSContext;
+use crate::error::throw_type_error;
+use crate::gc::Traceable;
+use crate::glue::JS_GetReservedSlot;
+use crate::jsapi;
+use crate::jsapi::{CallArgs, GCContext, JSClass, JSClassOps, JSFunctionSpec, JSNative};
+use crate::jsapi::{JSObject, JSPropertySpec, JSTracer, JS_SetReservedSlot, Value};
+use crate::jsapi::{JSCLASS_FOREGROUND_FINALIZE, JSCLASS_RESERVED_SLOTS_SHIFT};
+use crate::jsval::{PrivateValue, UndefinedValue};
+use crate::rust::get_object_class;
+use crate::rust::wrappers2::{
+    JS_InitClass, JS_NewObjectForConstructor, JS_NewObjectWithGivenProto,
+};
+use crate::rust::HandleObject;
+
+/// The reserved slot of an instance that holds its boxed value.
+const VALUE_SLOT: u32 = 0;
+
+/// The class of every type with a [`JsClass`].
+static CLASSES: Mutex<Vec<(TypeId, &'static JSClass)>> = Mutex::new(Vec::new());
+
+fn class_of<T: 'static>() -> Option<&'static JSClass> {
+    let classes = CLASSES.lock().unwrap();
+    classes
+        .iter()
+        .find(|(id, _)| *id == TypeId::of::<T>())
+        .map(|&(_, class)| class)
+}
+
+/// The boxed value of `obj`, which must be an instance of a [`JsClass`].
+unsafe fn value_of<T>(obj: *mut JSObject) -> *mut T {
+    let mut value = UndefinedValue();
+    JS_GetReservedSlot(obj, VALUE_SLOT, &mut value);
+    if value.is_undefined() {
+        ptr::null_mut()
+    } else {
+        value.to_private() as *mut T
+    }
+}
+
+unsafe extern "C" fn finalize<T>(_gcx: *mut GCContext, obj: *mut JSObject) {
+    let value = value_of::<T>(obj);
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
+
+unsafe extern "C" fn trace<T: Traceable>(trc: *mut JSTracer, obj: *mut JSObject) {
+    let value = value_of::<T>(obj);
+    if !value.is_null() {
+        (*value).trace(trc);
+    }
+}
+
+/// The constructor of classes without one, which cannot be constructed from
+/// JavaScript.
+unsafe extern "C" fn illegal_constructor(
+    cx: *mut jsapi::JSContext,
+    _argc: u32,
+    _vp: *mut Value,
+) -> bool {
+    throw_type_error(cx, c"Illegal constructor");
+    false
+}
+
+/// A JavaScript class whose instances own a `T`.
+pub struct JsClass<T> {
+    class: &'static JSClass,
+    constructor: JSNative,
+    nargs: u32,
+    properties: Option<&'static [JSPropertySpec]>,
+    methods: Option<&'static [JSFunctionSpec]>,
+    static_properties: Option<&'static [JSPropertySpec]>,
+    static_methods: Option<&'static [JSFunctionSpec]>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Traceable + 'static> JsClass<T> {
+    /// Describe the class named `name` of `T`, without a constructor,
+    /// properties or methods.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the class of `T` was created with another name.
+    pub fn new(name: &CStr) -> JsClass<T> {
+        let mut classes = CLASSES.lock().unwrap();
+        let class = match classes.iter().find(|(id, _)| *id == TypeId::of::<T>()) {
+            Some(&(_, class)) => {
+                assert_eq!(
+                    unsafe { CStr::from_ptr(class.name) },
+                    name,
+                    "The class of a type cannot be renamed"
+                );
+                class
+            }
+            None => {
+                let ops: &'static JSClassOps = Box::leak(Box::new(JSClassOps {
+                    addProperty: None,
+                    delProperty: None,
+                    enumerate: None,
+                    newEnumerate: None,
+                    resolve: None,
+                    mayResolve: None,
+                    finalize: Some(finalize::<T>),
+                    call: None,
+                    construct: None,
+                    trace: Some(trace::<T>),
+                }));
+                let class: &'static JSClass = Box::leak(Box::new(JSClass {
+                    name: Box::leak(Box::<CStr>::from(name)).as_ptr(),
+                    flags: JSCLASS_FOREGROUND_FINALIZE | (1 << JSCLASS_RESERVED_SLOTS_SHIFT),
+                    cOps: ops,
+                    spec: ptr::null(),
+                    ext: ptr::null(),
+                    oOps: ptr::null(),
+                }));
+                classes.push((TypeId::of::<T>(), class));
+                class
+            }
+        };
+        JsClass {
+            class,
+            constructor: Some(illegal_constructor),
+            nargs: 0,
+            properties: None,
+            methods: None,
+            static_properties: None,
+            static_methods: None,
+            marker: PhantomData,
+        }
+    }
+
+    /// Use `constructor`, with a `length` of `nargs`, as the constructor of
+    /// the class. It can create its instance with
+    /// [`JsClass::new_object_for_constructor`].
+    pub fn constructor(mut self, constructor: JSNative, nargs: u32) -> JsClass<T> {
+        self.constructor = constructor;
+        self.nargs = nargs;
+        self
+    }
+
+    /// Define `properties` on the prototype. The last entry must contain
+    /// zeroed memory.
+    pub fn properties(mut self, properties: &'static [JSPropertySpec]) -> JsClass<T> {
+        assert!(properties.last().is_some_and(JSPropertySpec::is_zeroed));
+        self.properties = Some(properties);
+        self
+    }
+
+    /// Define `methods` on the prototype. The last entry must contain zeroed
+    /// memory.
+    pub fn methods(mut self, methods: &'static [JSFunctionSpec]) -> JsClass<T> {
+        assert!(methods.last().is_some_and(JSFunctionSpec::is_zeroed));
+        self.methods = Some(methods);
+        self
+    }
+
+    /// Define `properties` on the constructor. The last entry must contain
+    /// zeroed memory.
+    pub fn static_properties(mut self, properties: &'static [JSPropertySpec]) -> JsClass<T> {
+        assert!(properties.last().is_some_and(JSPropertySpec::is_zeroed));
+        self.static_properties = Some(properties);
+        self
+    }
+
+    /// Define `methods` on the constructor. The last entry must contain
+    /// zeroed memory.
+    pub fn static_methods(mut self, methods: &'static [JSFunctionSpec]) -> JsClass<T> {
+        assert!(methods.last().is_some_and(JSFunctionSpec::is_zeroed));
+        self.static_methods = Some(methods);
+        self
+    }
+
+    /// The `JSClass` of the instances.
+    pub fn class(&self) -> &'static JSClass {
+        self.class
+    }
+
+    /// Define the constructor of the class on `obj`, and return its
+    /// prototype, which inherits from `parent_proto`, or from
+    /// `Object.prototype` if it is null. Returns null with an exception
+    /// pending on failure.
+    pub fn init(
+        &self,
+        cx: &mut JSContext,
+        obj: HandleObject,
+        parent_proto: HandleObject,
+    ) -> *mut JSObject {
+        fn or_null<S>(specs: Option<&'static [S]>) -> *const S {
+            specs.map_or(ptr::null(), <[S]>::as_ptr)
+        }
+
+        unsafe {
+            JS_InitClass(
+                cx,
+                obj,
+                ptr::null(),
+                parent_proto,
+                self.class.name,
+                self.constructor,
+                self.nargs,
+                or_null(self.properties),
+                or_null(self.methods),
+                or_null(self.static_properties),
+                or_null(self.static_methods),
+            )
+        }
+    }
+
+    /// Create an instance owning `value`, with `proto` as its prototype.
+    /// Returns null with an exception pending on failure.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the class of `T` has not been created by [`JsClass::new`].
+    pub fn new_object(cx: &mut JSContext, proto: HandleObject, value: T) -> *mut JSObject {
+        let class = Self::expect_class();
+        let obj = unsafe { JS_NewObjectWithGivenProto(cx, class, proto) };
+        unsafe { Self::attach(obj, value) }
+    }
+
+    /// Create the instance owning `value` for a call of the constructor of
+    /// the class, with the prototype of `new.target`. Returns null with an
+    /// exception pending on failure.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the class of `T` has not been created by [`JsClass::new`].
+    ///
+    /// # Safety
+    ///
+    /// `args` must be the arguments of a call in the realm of `cx`.
+    pub unsafe fn new_object_for_constructor(
+        cx: &mut JSContext,
+        args: &CallArgs,
+        value: T,
+    ) -> *mut JSObject {
+        let class = Self::expect_class();
+        let obj = JS_NewObjectForConstructor(cx, class, args);
+        Self::attach(obj, value)
+    }
+
+    fn expect_class() -> &'static JSClass {
+        class_of::<T>().expect("JsClass::new was not called for this type")
+    }
+
+    unsafe fn attach(obj: *mut JSObject, value: T) -> *mut JSObject {
+        if !obj.is_null() {
+            let value = Box::into_raw(Box::new(value));
+            JS_SetReservedSlot(obj, VALUE_SLOT, &PrivateValue(value as *const _));
+        }
+        obj
+    }
+}
+
+/// The value of `obj` if it is an instance of the class of `T`, as opposed to
+/// another object or a wrapper of an instance.
+///
+/// # Safety
+///
+/// `obj` must be a live object, and the reference must not outlive it.
+pub unsafe fn unwrap<'a, T: 'static>(obj: *mut JSObject) -> Option<&'a T> {
+    let class = class_of::<T>()?;
+    if get_object_class(obj) != class as *const JSClass {
+        return None;
+    }
+    value_of::<T>(obj).as_ref()
+}
@@ -23,32 +23,59 @@
 //! | USVString               | `String`                         |
 //! | object                  | `*mut JSObject`                  |
 //! | symbol                  | `*mut Symbol`                    |
+//! | bigint                  | `JsBigInt<T>`                    |
 //! | nullable types          | `Option<T>`                      |
-//! | sequences               | `Vec<T>`                         |
+//! | sequences               | `Vec<T>`, `HashSet<T>`           |
+//! | records                 | `HashMap<String, T>`             |
+//! | dictionaries            | structs deriving the traits      |
+//! | enumerations            | fieldless enums deriving them    |
+//!
+//! Both traits can be derived for structs with named fields and for
+//! fieldless enums; see [`macro@ToJSValConvertible`] and
+//! [`macro@FromJSValConvertible`] for the supported attributes.
+//!
+//! `BTreeMap<String, T>` and `BTreeSet<T>` convert like their hash-based
+//! counterparts, and so does `IndexMap<String, T>` with the `indexmap`
+//! feature, keeping the order of the properties. Wrap a map in
+//! [`MapObject`] or a set in [`SetObject`] to convert to and from a JS `Map`
+//! or `Set` instead.
 
 #![deny(missing_docs)]
 
-use crate::error::throw_type_error;
+pub use mozjs_derive::{FromJSValConvertible, ToJSValConvertible};
+
+use crate::error::{throw_range_error, throw_type_error};
 use crate::jsapi::AssertSameCompartment;
 use crate::jsapi::JS;
+use crate::jsapi::JS::detail::{BigIntIsInt64, BigIntIsUint64};
+use crate::jsapi::{jsid, PropertyDescriptor, JSITER_HIDDEN, JSITER_OWNONLY, JSITER_SYMBOLS};
+use crate::jsapi::{BigIntFromInt64, BigIntFromUint64, NewMapObject, NewSetObject};
 use crate::jsapi::{ForOfIterator, ForOfIterator_NonIterableBehavior};
 use crate::jsapi::{Heap, JS_DefineElement, JS_GetLatin1StringCharsAndLength};
 use crate::jsapi::{JSContext, JSObject, JSString, RootedObject, RootedValue};
 use crate::jsapi::{JS_DeprecatedStringHasLatin1Chars, JS_NewStringCopyUTF8N, JSPROP_ENUMERATE};
-use crate::jsapi::{JS_GetTwoByteStringCharsAndLength, NewArrayObject1};
+use crate::jsapi::{JS_GetTwoByteStringCharsAndLength, JS_NewPlainObject, NewArrayObject1};
+use crate::jsval::{BigIntValue, JSVal, ObjectOrNullValue, ObjectValue, StringValue, SymbolValue};
 use crate::jsval::{BooleanValue, DoubleValue, Int32Value, NullValue, UInt32Value, UndefinedValue};
-use crate::jsval::{JSVal, ObjectOrNullValue, ObjectValue, StringValue, SymbolValue};
 use crate::rooted;
 use crate::rust::maybe_wrap_value;
+use crate::rust::wrappers::{BigIntToString, JS_IdToValue, JS_ValueToId, ToBigInt};
+use crate::rust::wrappers::{GetPropertyKeys, JS_GetOwnPropertyDescriptorById};
+use crate::rust::wrappers::{IsMapObject, IsSetObject, MapEntries, MapSet, SetAdd, SetValues};
+use crate::rust::wrappers::{JS_DefinePropertyById2, JS_GetElement, JS_GetPropertyById};
 use crate::rust::{maybe_wrap_object_or_null_value, maybe_wrap_object_value, ToString};
-use crate::rust::{HandleValue, MutableHandleValue};
+use crate::rust::{HandleValue, IdVector, MutableHandleValue};
 use crate::rust::{ToBoolean, ToInt32, ToInt64, ToNumber, ToUint16, ToUint32, ToUint64};
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
 use libc;
 use log::debug;
 use mozjs_sys::jsgc::Rooted;
 use num_traits::PrimInt;
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ffi::CStr;
+use std::hash::{BuildHasher, Hash};
 use std::mem;
 use std::ptr::NonNull;
 use std::rc::Rc;
@@ -157,6 +184,14 @@ impl<T> ConversionResult<T> {
             _ => None,
         }
     }
+
+    /// Maps a successful value with `f`, keeping failures as they are.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ConversionResult<U> {
+        match self {
+            ConversionResult::Success(v) => ConversionResult::Success(f(v)),
+            ConversionResult::Failure(e) => ConversionResult::Failure(e),
+        }
+    }
 }
 
 /// A trait to convert `JSVal`s to Rust types.
@@ -230,9 +265,10 @@ impl<T: FromJSValConvertibleRc> FromJSValConvertible for Rc<T> {
 }
 
 /// Behavior for converting out-of-range integers.
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq, Clone, Default)]
 pub enum ConversionBehavior {
     /// Wrap into the integer's range.
+    #[default]
     Default,
     /// Throw an exception.
     EnforceRange,
@@ -782,6 +818,76 @@ impl<'a> Drop for ForOfIteratorGuard<'a> {
     }
 }
 
+/// Call `f` with each value produced by iterating `value`, as `for-of` does.
+/// Returns `Failure` if `value` is not iterable.
+unsafe fn for_of(
+    cx: *mut JSContext,
+    value: HandleValue,
+    mut f: impl FnMut(HandleValue) -> Result<(), ()>,
+) -> Result<ConversionResult<()>, ()> {
+    if !value.is_object() {
+        return Ok(ConversionResult::Failure(c"Value is not an object".into()));
+    }
+
+    // Depending on the version of LLVM in use, bindgen can end up including
+    // a padding field in the ForOfIterator. To support multiple versions of
+    // LLVM that may not have the same fields as a result, we create an empty
+    // iterator instance and initialize a non-empty instance using the empty
+    // instance as a base value.
+    let zero = mem::zeroed();
+    let mut iterator = ForOfIterator {
+        cx_: cx,
+        iterator: RootedObject::new_unrooted(ptr::null_mut()),
+        nextMethod: RootedValue::new_unrooted(JSVal { asBits_: 0 }),
+        index: ::std::u32::MAX, // NOT_ARRAY
+        ..zero
+    };
+    let iterator = ForOfIteratorGuard::new(cx, &mut iterator);
+    let iterator: &mut ForOfIterator = &mut *iterator.root;
+
+    if !iterator.init(
+        value.into(),
+        ForOfIterator_NonIterableBehavior::AllowNonIterable,
+    ) {
+        return Err(());
+    }
+
+    if iterator.iterator.data.is_null() {
+        return Ok(ConversionResult::Failure(c"Value is not iterable".into()));
+    }
+
+    loop {
+        let mut done = false;
+        rooted!(in(cx) let mut val = UndefinedValue());
+        if !iterator.next(val.handle_mut().into(), &mut done) {
+            return Err(());
+        }
+
+        if done {
+            break;
+        }
+
+        f(val.handle())?;
+    }
+
+    Ok(ConversionResult::Success(()))
+}
+
+/// Convert `value` to a `T`, throwing a `TypeError` on `Failure`.
+unsafe fn convert_or_throw<T: FromJSValConvertible>(
+    cx: *mut JSContext,
+    value: HandleValue,
+    option: T::Config,
+) -> Result<T, ()> {
+    match T::from_jsval(cx, value, option)? {
+        ConversionResult::Success(v) => Ok(v),
+        ConversionResult::Failure(e) => {
+            throw_type_error(cx, e.as_ref());
+            Err(())
+        }
+    }
+}
+
 impl<C: Clone, T: FromJSValConvertible<Config = C>> FromJSValConvertible for Vec<T> {
     type Config = C;
 
@@ -790,60 +896,472 @@ impl<C: Clone, T: FromJSValConvertible<Config = C>> FromJSValConvertible for Vec
         value: HandleValue,
         option: C,
     ) -> Result<ConversionResult<Vec<T>>, ()> {
-        if !value.is_object() {
-            return Ok(ConversionResult::Failure(c"Value is not an object".into()));
-        }
+        let mut ret: Vec<T> = vec![];
+        let result = for_of(cx, value, |val| {
+            ret.push(convert_or_throw(cx, val, option.clone())?);
+            Ok(())
+        })?;
+        Ok(result.map(|()| ret))
+    }
+}
 
-        // Depending on the version of LLVM in use, bindgen can end up including
-        // a padding field in the ForOfIterator. To support multiple versions of
-        // LLVM that may not have the same fields as a result, we create an empty
-        // iterator instance and initialize a non-empty instance using the empty
-        // instance as a base value.
-        let zero = mem::zeroed();
-        let mut iterator = ForOfIterator {
-            cx_: cx,
-            iterator: RootedObject::new_unrooted(ptr::null_mut()),
-            nextMethod: RootedValue::new_unrooted(JSVal { asBits_: 0 }),
-            index: ::std::u32::MAX, // NOT_ARRAY
-            ..zero
-        };
-        let iterator = ForOfIteratorGuard::new(cx, &mut iterator);
-        let iterator: &mut ForOfIterator = &mut *iterator.root;
+// Sets convert like sequences.
+impl<T: ToJSValConvertible, S> ToJSValConvertible for HashSet<T, S> {
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        self.iter().collect::<Vec<_>>().to_jsval(cx, rval)
+    }
+}
+
+impl<C, T, S> FromJSValConvertible for HashSet<T, S>
+where
+    C: Clone,
+    T: FromJSValConvertible<Config = C> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    type Config = C;
+
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: C,
+    ) -> Result<ConversionResult<Self>, ()> {
+        Ok(Vec::<T>::from_jsval(cx, value, option)?.map(HashSet::from_iter))
+    }
+}
+
+impl<T: ToJSValConvertible> ToJSValConvertible for BTreeSet<T> {
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        self.iter().collect::<Vec<_>>().to_jsval(cx, rval)
+    }
+}
+
+impl<C: Clone, T: FromJSValConvertible<Config = C> + Ord> FromJSValConvertible for BTreeSet<T> {
+    type Config = C;
 
-        if !iterator.init(
-            value.into(),
-            ForOfIterator_NonIterableBehavior::AllowNonIterable,
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: C,
+    ) -> Result<ConversionResult<Self>, ()> {
+        Ok(Vec::<T>::from_jsval(cx, value, option)?.map(BTreeSet::from_iter))
+    }
+}
+
+/// Create a plain object with a property for each entry.
+unsafe fn record_to_jsval<'a, T: ToJSValConvertible + 'a>(
+    cx: *mut JSContext,
+    entries: impl Iterator<Item = (&'a String, &'a T)>,
+    mut rval: MutableHandleValue,
+) {
+    rooted!(in(cx) let obj = JS_NewPlainObject(cx));
+    assert!(!obj.get().is_null());
+    rooted!(in(cx) let mut key = UndefinedValue());
+    rooted!(in(cx) let mut id: jsid);
+    rooted!(in(cx) let mut val = UndefinedValue());
+    for (name, value) in entries {
+        name.to_jsval(cx, key.handle_mut());
+        assert!(JS_ValueToId(cx, key.handle(), id.handle_mut()));
+        value.to_jsval(cx, val.handle_mut());
+        assert!(JS_DefinePropertyById2(
+            cx,
+            obj.handle(),
+            id.handle(),
+            val.handle(),
+            JSPROP_ENUMERATE as u32,
+        ));
+    }
+    rval.set(ObjectValue(obj.get()));
+}
+
+/// Call `insert` with each own enumerable property of `value`, in property
+/// order, invoking getters.
+// https://webidl.spec.whatwg.org/#es-record
+unsafe fn record_from_jsval<C: Clone, T: FromJSValConvertible<Config = C>>(
+    cx: *mut JSContext,
+    value: HandleValue,
+    option: C,
+    mut insert: impl FnMut(String, T),
+) -> Result<ConversionResult<()>, ()> {
+    if !value.is_object() {
+        return Ok(ConversionResult::Failure(c"Value is not an object".into()));
+    }
+    rooted!(in(cx) let obj = value.to_object());
+    let mut ids = IdVector::new(cx);
+    if !GetPropertyKeys(
+        cx,
+        obj.handle(),
+        JSITER_OWNONLY | JSITER_HIDDEN | JSITER_SYMBOLS,
+        ids.handle_mut(),
+    ) {
+        return Err(());
+    }
+    rooted!(in(cx) let mut id: jsid);
+    rooted!(in(cx) let mut desc: PropertyDescriptor);
+    rooted!(in(cx) let mut key = UndefinedValue());
+    rooted!(in(cx) let mut val = UndefinedValue());
+    for &raw_id in ids.iter() {
+        id.set(raw_id);
+        let mut is_none = true;
+        if !JS_GetOwnPropertyDescriptorById(
+            cx,
+            obj.handle(),
+            id.handle(),
+            desc.handle_mut(),
+            &mut is_none,
         ) {
             return Err(());
         }
-
-        if iterator.iterator.data.is_null() {
-            return Ok(ConversionResult::Failure(c"Value is not iterable".into()));
+        if is_none || !desc.get().enumerable_() {
+            continue;
+        }
+        // Symbol keys fail to convert to strings, with a TypeError.
+        if !JS_IdToValue(cx, id.get(), key.handle_mut()) {
+            return Err(());
+        }
+        let name = convert_or_throw(cx, key.handle(), ())?;
+        if !JS_GetPropertyById(cx, obj.handle(), id.handle(), val.handle_mut()) {
+            return Err(());
         }
+        insert(name, convert_or_throw(cx, val.handle(), option.clone())?);
+    }
+    Ok(ConversionResult::Success(()))
+}
 
-        let mut ret = vec![];
+// Maps with string keys convert like records.
+impl<T: ToJSValConvertible, S> ToJSValConvertible for HashMap<String, T, S> {
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        record_to_jsval(cx, self.iter(), rval)
+    }
+}
 
-        loop {
-            let mut done = false;
-            rooted!(in(cx) let mut val = UndefinedValue());
-            if !iterator.next(val.handle_mut().into(), &mut done) {
-                return Err(());
-            }
+impl<C, T, S> FromJSValConvertible for HashMap<String, T, S>
+where
+    C: Clone,
+    T: FromJSValConvertible<Config = C>,
+    S: BuildHasher + Default,
+{
+    type Config = C;
 
-            if done {
-                break;
-            }
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: C,
+    ) -> Result<ConversionResult<Self>, ()> {
+        let mut ret: HashMap<String, T, S> = HashMap::default();
+        let result = record_from_jsval(cx, value, option, |name, value| {
+            ret.insert(name, value);
+        })?;
+        Ok(result.map(|()| ret))
+    }
+}
 
-            ret.push(match T::from_jsval(cx, val.handle(), option.clone())? {
-                ConversionResult::Success(v) => v,
-                ConversionResult::Failure(e) => {
-                    throw_type_error(cx, e.as_ref());
-                    return Err(());
-                }
-            });
+impl<T: ToJSValConvertible> ToJSValConvertible for BTreeMap<String, T> {
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        record_to_jsval(cx, self.iter(), rval)
+    }
+}
+
+impl<C: Clone, T: FromJSValConvertible<Config = C>> FromJSValConvertible for BTreeMap<String, T> {
+    type Config = C;
+
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: C,
+    ) -> Result<ConversionResult<Self>, ()> {
+        let mut ret: BTreeMap<String, T> = BTreeMap::new();
+        let result = record_from_jsval(cx, value, option, |name, value| {
+            ret.insert(name, value);
+        })?;
+        Ok(result.map(|()| ret))
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<T: ToJSValConvertible, S> ToJSValConvertible for IndexMap<String, T, S> {
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        record_to_jsval(cx, self.iter(), rval)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<C, T, S> FromJSValConvertible for IndexMap<String, T, S>
+where
+    C: Clone,
+    T: FromJSValConvertible<Config = C>,
+    S: BuildHasher + Default,
+{
+    type Config = C;
+
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: C,
+    ) -> Result<ConversionResult<Self>, ()> {
+        let mut ret: IndexMap<String, T, S> = IndexMap::default();
+        let result = record_from_jsval(cx, value, option, |name, value| {
+            ret.insert(name, value);
+        })?;
+        Ok(result.map(|()| ret))
+    }
+}
+
+/// A map that converts to and from a JS `Map`, rather than a record.
+///
+/// Keys may be of any convertible type. `Config` is the pair of the key and
+/// value configurations.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MapObject<M>(pub M);
+
+/// A set that converts to and from a JS `Set`, rather than a sequence.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SetObject<S>(pub S);
+
+unsafe fn map_to_jsval<'a, K: ToJSValConvertible + 'a, V: ToJSValConvertible + 'a>(
+    cx: *mut JSContext,
+    entries: impl Iterator<Item = (&'a K, &'a V)>,
+    mut rval: MutableHandleValue,
+) {
+    rooted!(in(cx) let map = NewMapObject(cx));
+    assert!(!map.get().is_null());
+    rooted!(in(cx) let mut key = UndefinedValue());
+    rooted!(in(cx) let mut val = UndefinedValue());
+    for (k, v) in entries {
+        k.to_jsval(cx, key.handle_mut());
+        v.to_jsval(cx, val.handle_mut());
+        assert!(MapSet(cx, map.handle(), key.handle(), val.handle()));
+    }
+    rval.set(ObjectValue(map.get()));
+}
+
+unsafe fn map_from_jsval<K: FromJSValConvertible, V: FromJSValConvertible>(
+    cx: *mut JSContext,
+    value: HandleValue,
+    option: (K::Config, V::Config),
+    mut insert: impl FnMut(K, V),
+) -> Result<ConversionResult<()>, ()>
+where
+    K::Config: Clone,
+    V::Config: Clone,
+{
+    if !value.is_object() {
+        return Ok(ConversionResult::Failure(c"Value is not an object".into()));
+    }
+    rooted!(in(cx) let map = value.to_object());
+    let mut is_map = false;
+    if !IsMapObject(cx, map.handle(), &mut is_map) {
+        return Err(());
+    }
+    if !is_map {
+        return Ok(ConversionResult::Failure(c"Value is not a Map".into()));
+    }
+    rooted!(in(cx) let mut entries = UndefinedValue());
+    if !MapEntries(cx, map.handle(), entries.handle_mut()) {
+        return Err(());
+    }
+    rooted!(in(cx) let mut key = UndefinedValue());
+    rooted!(in(cx) let mut val = UndefinedValue());
+    for_of(cx, entries.handle(), |entry| {
+        rooted!(in(cx) let entry = entry.to_object());
+        if !JS_GetElement(cx, entry.handle(), 0, key.handle_mut())
+            || !JS_GetElement(cx, entry.handle(), 1, val.handle_mut())
+        {
+            return Err(());
         }
+        let k = convert_or_throw(cx, key.handle(), option.0.clone())?;
+        insert(k, convert_or_throw(cx, val.handle(), option.1.clone())?);
+        Ok(())
+    })
+}
+
+unsafe fn set_to_jsval<'a, T: ToJSValConvertible + 'a>(
+    cx: *mut JSContext,
+    values: impl Iterator<Item = &'a T>,
+    mut rval: MutableHandleValue,
+) {
+    rooted!(in(cx) let set = NewSetObject(cx));
+    assert!(!set.get().is_null());
+    rooted!(in(cx) let mut val = UndefinedValue());
+    for v in values {
+        v.to_jsval(cx, val.handle_mut());
+        assert!(SetAdd(cx, set.handle(), val.handle()));
+    }
+    rval.set(ObjectValue(set.get()));
+}
+
+unsafe fn set_from_jsval<C: Clone, T: FromJSValConvertible<Config = C>>(
+    cx: *mut JSContext,
+    value: HandleValue,
+    option: C,
+    mut insert: impl FnMut(T),
+) -> Result<ConversionResult<()>, ()> {
+    if !value.is_object() {
+        return Ok(ConversionResult::Failure(c"Value is not an object".into()));
+    }
+    rooted!(in(cx) let set = value.to_object());
+    let mut is_set = false;
+    if !IsSetObject(cx, set.handle(), &mut is_set) {
+        return Err(());
+    }
+    if !is_set {
+        return Ok(ConversionResult::Failure(c"Value is not a Set".into()));
+    }
+    rooted!(in(cx) let mut values = UndefinedValue());
+    if !SetValues(cx, set.handle(), values.handle_mut()) {
+        return Err(());
+    }
+    for_of(cx, values.handle(), |val| {
+        insert(convert_or_throw(cx, val, option.clone())?);
+        Ok(())
+    })
+}
+
+impl<K: ToJSValConvertible, V: ToJSValConvertible, S> ToJSValConvertible
+    for MapObject<HashMap<K, V, S>>
+{
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        map_to_jsval(cx, self.0.iter(), rval)
+    }
+}
+
+impl<K, V, S> FromJSValConvertible for MapObject<HashMap<K, V, S>>
+where
+    K: FromJSValConvertible<Config: Clone> + Eq + Hash,
+    V: FromJSValConvertible<Config: Clone>,
+    S: BuildHasher + Default,
+{
+    type Config = (K::Config, V::Config);
+
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: Self::Config,
+    ) -> Result<ConversionResult<Self>, ()> {
+        let mut ret: HashMap<K, V, S> = HashMap::default();
+        let result = map_from_jsval(cx, value, option, |k, v| {
+            ret.insert(k, v);
+        })?;
+        Ok(result.map(|()| MapObject(ret)))
+    }
+}
+
+impl<K: ToJSValConvertible, V: ToJSValConvertible> ToJSValConvertible
+    for MapObject<BTreeMap<K, V>>
+{
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        map_to_jsval(cx, self.0.iter(), rval)
+    }
+}
+
+impl<K, V> FromJSValConvertible for MapObject<BTreeMap<K, V>>
+where
+    K: FromJSValConvertible<Config: Clone> + Ord,
+    V: FromJSValConvertible<Config: Clone>,
+{
+    type Config = (K::Config, V::Config);
+
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: Self::Config,
+    ) -> Result<ConversionResult<Self>, ()> {
+        let mut ret: BTreeMap<K, V> = BTreeMap::new();
+        let result = map_from_jsval(cx, value, option, |k, v| {
+            ret.insert(k, v);
+        })?;
+        Ok(result.map(|()| MapObject(ret)))
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: ToJSValConvertible, V: ToJSValConvertible, S> ToJSValConvertible
+    for MapObject<IndexMap<K, V, S>>
+{
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        map_to_jsval(cx, self.0.iter(), rval)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> FromJSValConvertible for MapObject<IndexMap<K, V, S>>
+where
+    K: FromJSValConvertible<Config: Clone> + Eq + Hash,
+    V: FromJSValConvertible<Config: Clone>,
+    S: BuildHasher + Default,
+{
+    type Config = (K::Config, V::Config);
+
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: Self::Config,
+    ) -> Result<ConversionResult<Self>, ()> {
+        let mut ret: IndexMap<K, V, S> = IndexMap::default();
+        let result = map_from_jsval(cx, value, option, |k, v| {
+            ret.insert(k, v);
+        })?;
+        Ok(result.map(|()| MapObject(ret)))
+    }
+}
+
+impl<T: ToJSValConvertible, S> ToJSValConvertible for SetObject<HashSet<T, S>> {
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        set_to_jsval(cx, self.0.iter(), rval)
+    }
+}
+
+impl<C, T, S> FromJSValConvertible for SetObject<HashSet<T, S>>
+where
+    C: Clone,
+    T: FromJSValConvertible<Config = C> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    type Config = C;
 
-        Ok(ret).map(ConversionResult::Success)
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: C,
+    ) -> Result<ConversionResult<Self>, ()> {
+        let mut ret: HashSet<T, S> = HashSet::default();
+        let result = set_from_jsval(cx, value, option, |v| {
+            ret.insert(v);
+        })?;
+        Ok(result.map(|()| SetObject(ret)))
+    }
+}
+
+impl<T: ToJSValConvertible> ToJSValConvertible for SetObject<BTreeSet<T>> {
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        set_to_jsval(cx, self.0.iter(), rval)
+    }
+}
+
+impl<C: Clone, T: FromJSValConvertible<Config = C> + Ord> FromJSValConvertible
+    for SetObject<BTreeSet<T>>
+{
+    type Config = C;
+
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        option: C,
+    ) -> Result<ConversionResult<Self>, ()> {
+        let mut ret: BTreeSet<T> = BTreeSet::new();
+        let result = set_from_jsval(cx, value, option, |v| {
+            ret.insert(v);
+        })?;
+        Ok(result.map(|()| SetObject(ret)))
     }
 }
 
@@ -918,6 +1436,126 @@ impl FromJSValConvertible for *mut JS::Symbol {
     }
 }
 
+/// An integer that converts losslessly to and from a JS BigInt, rather than
+/// a Number.
+///
+/// Conversion from JS goes through `ToBigInt`, so it also accepts booleans
+/// and numeric strings, and throws a `RangeError` for values that do not
+/// fit in `T`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JsBigInt<T>(pub T);
+
+unsafe fn bigint_to_jsval(bigint: *mut JS::BigInt, mut rval: MutableHandleValue) {
+    assert!(!bigint.is_null());
+    rval.set(BigIntValue(&*bigint));
+}
+
+/// Create a BigInt from a string of decimal digits.
+unsafe fn bigint_from_decimal(cx: *mut JSContext, digits: &str, rval: MutableHandleValue) {
+    rooted!(in(cx) let mut string = UndefinedValue());
+    digits.to_jsval(cx, string.handle_mut());
+    bigint_to_jsval(ToBigInt(cx, string.handle()), rval);
+}
+
+/// The decimal digits of `value`, converted to a BigInt.
+unsafe fn bigint_to_decimal(cx: *mut JSContext, value: HandleValue) -> Result<String, ()> {
+    rooted!(in(cx) let bigint = ToBigInt(cx, value));
+    if bigint.get().is_null() {
+        return Err(());
+    }
+    let string = NonNull::new(BigIntToString(cx, bigint.handle(), 10)).ok_or(())?;
+    Ok(jsstr_to_string(cx, string))
+}
+
+impl ToJSValConvertible for JsBigInt<i64> {
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        bigint_to_jsval(BigIntFromInt64(cx, self.0), rval);
+    }
+}
+
+impl FromJSValConvertible for JsBigInt<i64> {
+    type Config = ();
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        _option: (),
+    ) -> Result<ConversionResult<Self>, ()> {
+        let bigint = ToBigInt(cx, value);
+        if bigint.is_null() {
+            return Err(());
+        }
+        let mut result = 0;
+        if !BigIntIsInt64(bigint, &mut result) {
+            throw_range_error(cx, c"BigInt value is out of range");
+            return Err(());
+        }
+        Ok(ConversionResult::Success(JsBigInt(result)))
+    }
+}
+
+impl ToJSValConvertible for JsBigInt<u64> {
+    #[inline]
+    unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+        bigint_to_jsval(BigIntFromUint64(cx, self.0), rval);
+    }
+}
+
+impl FromJSValConvertible for JsBigInt<u64> {
+    type Config = ();
+    unsafe fn from_jsval(
+        cx: *mut JSContext,
+        value: HandleValue,
+        _option: (),
+    ) -> Result<ConversionResult<Self>, ()> {
+        let bigint = ToBigInt(cx, value);
+        if bigint.is_null() {
+            return Err(());
+        }
+        let mut result = 0;
+        if !BigIntIsUint64(bigint, &mut result) {
+            throw_range_error(cx, c"BigInt value is out of range");
+            return Err(());
+        }
+        Ok(ConversionResult::Success(JsBigInt(result)))
+    }
+}
+
+/// Conversions through decimal strings, for integers wider than JSAPI's.
+macro_rules! impl_bigint_decimal {
+    ($($ty:ty),*) => {
+        $(
+            impl ToJSValConvertible for JsBigInt<$ty> {
+                #[inline]
+                unsafe fn to_jsval(&self, cx: *mut JSContext, rval: MutableHandleValue) {
+                    bigint_from_decimal(cx, &self.0.to_string(), rval);
+                }
+            }
+
+            impl FromJSValConvertible for JsBigInt<$ty> {
+                type Config = ();
+                unsafe fn from_jsval(
+                    cx: *mut JSContext,
+                    value: HandleValue,
+                    _option: (),
+                ) -> Result<ConversionResult<Self>, ()> {
+                    match bigint_to_decimal(cx, value)?.parse() {
+                        Ok(result) => Ok(ConversionResult::Success(JsBigInt(result))),
+                        Err(_) => {
+                            throw_range_error(cx, c"BigInt value is out of range");
+                            Err(())
+                        }
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_bigint_decimal!(i128, u128);
+#[cfg(feature = "num-bigint")]
+impl_bigint_decimal!(num_bigint::BigInt, num_bigint::BigUint);
+
 /// A wrapper type over [`crate::jsapi::UTF8Chars`]. This is created to help transferring
 /// a rust string to mozjs. The inner [`crate::jsapi::UTF8Chars`] can be accessed via the
 /// [`std::ops::Deref`] trait.
@@ -2,33 +2,74 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-//! Functions to throw JavaScript exceptions from Rust.
+//! Functions to throw JavaScript exceptions from Rust, and the [`Exception`]
+//! type that carries them back.
 
 #![deny(missing_docs)]
 
-use crate::jsapi::{JSContext, JSErrorFormatString, JSExnType, JS_ReportErrorNumberUTF8};
+use crate::conversions::{jsstr_to_string, ConversionResult, FromJSValConvertible};
+use crate::conversions::{ToJSValConvertible, Utf8Chars};
+use crate::gc::{HandleObject, HandleValue, MutableHandleValue, RootedTraceableBox};
+use crate::jsapi::glue::{GetErrorNote, GetErrorNotesLength};
+use crate::jsapi::{CaptureCurrentStack, ExceptionStackBehavior, JS_StackCapture_AllFrames};
+use crate::jsapi::{Heap, JSContext, JSErrorFormatString, JSExnType, JSObject, JSString};
+use crate::jsapi::{JSErrorBase, StackFormat};
+use crate::jsapi::{JS_ClearPendingException, JS_IsExceptionPending};
+use crate::jsapi::{JS_IsThrowingOutOfMemory, JS_ReportOutOfMemory};
+use crate::jsapi::{JS_NewStringCopyUTF8N, JS_ReportErrorNumberUTF8, NewArrayObject1};
+use crate::jsval::{JSVal, ObjectValue, UndefinedValue};
+use crate::memory::{take_out_of_memory, OutOfMemory};
+use crate::rooted;
+use crate::rust::wrappers::GetSymbolDescription;
+use crate::rust::wrappers::JS_DefineProperty;
+use crate::rust::wrappers::StealPendingExceptionWithStack;
+use crate::rust::wrappers::{BuildStackString, ExceptionStackOrNull, JS_ErrorFromException};
+use crate::rust::wrappers::{CreateErrorWithOptionalCause, JS_SetPendingException};
+use crate::rust::wrappers::{JS_GetPendingException, JS_GetProperty, JS_WrapValue};
+use crate::rust::{describe_scripted_caller, get_object_class};
 use libc;
-use std::ffi::CStr;
-use std::{mem, os, ptr};
+use std::ffi::{CStr, CString};
+use std::mem::MaybeUninit;
+use std::ptr::NonNull;
+use std::{fmt, mem, os, ptr};
 
 /// Format string used to throw javascript errors.
 static ERROR_FORMAT_STRING_STRING: &CStr = c"{0}";
 
-/// Format string struct used to throw `TypeError`s.
-static mut TYPE_ERROR_FORMAT_STRING: JSErrorFormatString = JSErrorFormatString {
-    name: c"RUSTMSG_TYPE_ERROR".as_ptr(),
-    format: ERROR_FORMAT_STRING_STRING.as_ptr(),
-    argCount: 1,
-    exnType: JSExnType::JSEXN_TYPEERR as i16,
-};
-
-/// Format string struct used to throw `RangeError`s.
-static mut RANGE_ERROR_FORMAT_STRING: JSErrorFormatString = JSErrorFormatString {
-    name: c"RUSTMSG_RANGE_ERROR".as_ptr(),
-    format: ERROR_FORMAT_STRING_STRING.as_ptr(),
-    argCount: 1,
-    exnType: JSExnType::JSEXN_RANGEERR as i16,
-};
+/// The format string used to throw errors of type `kind`, if it is one of
+/// the types that `throw_error` supports.
+fn error_format_string(kind: JSExnType) -> Option<*const JSErrorFormatString> {
+    macro_rules! format_string {
+        ($name:literal, $kind:ident) => {{
+            static mut FORMAT_STRING: JSErrorFormatString = JSErrorFormatString {
+                name: $name.as_ptr(),
+                format: ERROR_FORMAT_STRING_STRING.as_ptr(),
+                argCount: 1,
+                exnType: JSExnType::$kind as i16,
+            };
+            Some(&raw const FORMAT_STRING)
+        }};
+    }
+
+    match kind {
+        JSExnType::JSEXN_ERR => format_string!(c"RUSTMSG_ERROR", JSEXN_ERR),
+        JSExnType::JSEXN_INTERNALERR => {
+            format_string!(c"RUSTMSG_INTERNAL_ERROR", JSEXN_INTERNALERR)
+        }
+        JSExnType::JSEXN_AGGREGATEERR => {
+            format_string!(c"RUSTMSG_AGGREGATE_ERROR", JSEXN_AGGREGATEERR)
+        }
+        JSExnType::JSEXN_EVALERR => format_string!(c"RUSTMSG_EVAL_ERROR", JSEXN_EVALERR),
+        JSExnType::JSEXN_RANGEERR => format_string!(c"RUSTMSG_RANGE_ERROR", JSEXN_RANGEERR),
+        JSExnType::JSEXN_REFERENCEERR => {
+            format_string!(c"RUSTMSG_REFERENCE_ERROR", JSEXN_REFERENCEERR)
+        }
+        JSExnType::JSEXN_SYNTAXERR => format_string!(c"RUSTMSG_SYNTAX_ERROR", JSEXN_SYNTAXERR),
+        JSExnType::JSEXN_TYPEERR => format_string!(c"RUSTMSG_TYPE_ERROR", JSEXN_TYPEERR),
+        JSExnType::JSEXN_URIERR => format_string!(c"RUSTMSG_URI_ERROR", JSEXN_URIERR),
+        _ => None,
+    }
+}
 
 /// Callback used to throw javascript errors.
 /// See throw_js_error for info about error_number.
@@ -37,14 +78,9 @@ unsafe extern "C" fn get_error_message(
     error_number: libc::c_uint,
 ) -> *const JSErrorFormatString {
     let num: JSExnType = mem::transmute(error_number);
-    match num {
-        JSExnType::JSEXN_TYPEERR => &raw const TYPE_ERROR_FORMAT_STRING,
-        JSExnType::JSEXN_RANGEERR => &raw const RANGE_ERROR_FORMAT_STRING,
-        _ => panic!(
-            "Bad js error number given to get_error_message: {}",
-            error_number
-        ),
-    }
+    // throw_js_error only passes supported types, and panicking here would
+    // abort anyway.
+    error_format_string(num).unwrap_or(ptr::null())
 }
 
 /// Helper fn to throw a javascript error with the given message and number.
@@ -61,6 +97,16 @@ unsafe fn throw_js_error(cx: *mut JSContext, error: &CStr, error_number: u32) {
     );
 }
 
+/// Convert `message` to a C string, cutting it at the first NUL.
+fn message_to_cstring(message: impl fmt::Display) -> CString {
+    CString::new(message.to_string()).unwrap_or_else(|error| {
+        let position = error.nul_position();
+        let mut bytes = error.into_vec();
+        bytes.truncate(position);
+        CString::new(bytes).unwrap()
+    })
+}
+
 /// Throw a `TypeError` with the given message.
 pub unsafe fn throw_type_error(cx: *mut JSContext, error: &CStr) {
     throw_js_error(cx, error, JSExnType::JSEXN_TYPEERR as u32);
@@ -75,3 +121,480 @@ pub unsafe fn throw_range_error(cx: *mut JSContext, error: &CStr) {
 pub unsafe fn throw_internal_error(cx: *mut JSContext, error: &CStr) {
     throw_js_error(cx, error, JSExnType::JSEXN_INTERNALERR as u32);
 }
+
+/// Throw an error of type `kind` with the given message, which can be
+/// anything that implements `Display`, such as a `&str` or the result of
+/// `format_args!`.
+///
+/// # Panics
+///
+/// Panics if `kind` is not `JSEXN_ERR`, `JSEXN_INTERNALERR`,
+/// `JSEXN_AGGREGATEERR`, `JSEXN_EVALERR`, `JSEXN_RANGEERR`,
+/// `JSEXN_REFERENCEERR`, `JSEXN_SYNTAXERR`, `JSEXN_TYPEERR` or
+/// `JSEXN_URIERR`.
+pub unsafe fn throw_error(cx: *mut JSContext, kind: JSExnType, message: impl fmt::Display) {
+    assert!(
+        error_format_string(kind).is_some(),
+        "Cannot throw errors of type {:?}",
+        kind
+    );
+    throw_js_error(cx, &message_to_cstring(message), kind as u32);
+    if kind == JSExnType::JSEXN_AGGREGATEERR {
+        define_empty_errors(cx);
+    }
+}
+
+/// Give the pending `AggregateError` the empty `errors` array that `new
+/// AggregateError([], message)` would have, since reporting an error number
+/// does not define one.
+unsafe fn define_empty_errors(cx: *mut JSContext) {
+    rooted!(in(cx) let mut exception = UndefinedValue());
+    if !JS_GetPendingException(cx, exception.handle_mut()) || !exception.is_object() {
+        return;
+    }
+    JS_ClearPendingException(cx);
+    rooted!(in(cx) let error = exception.to_object());
+    rooted!(in(cx) let errors = NewArrayObject1(cx, 0));
+    if errors.is_null() {
+        // Leave the out of memory exception pending.
+        return;
+    }
+    rooted!(in(cx) let errors = ObjectValue(errors.get()));
+    // Non-enumerable, like the property the constructor defines.
+    if JS_DefineProperty(cx, error.handle(), c"errors".as_ptr(), errors.handle(), 0) {
+        JS_SetPendingException(cx, exception.handle(), ExceptionStackBehavior::Capture);
+    }
+}
+
+/// Throw `value`, which does not need to be an `Error`, capturing the current
+/// stack for it.
+pub unsafe fn throw_value<T: ToJSValConvertible + ?Sized>(cx: *mut JSContext, value: &T) {
+    rooted!(in(cx) let mut exception = UndefinedValue());
+    value.to_jsval(cx, exception.handle_mut());
+    JS_SetPendingException(cx, exception.handle(), ExceptionStackBehavior::Capture);
+}
+
+/// Create an error object of type `kind`, like `new TypeError(message, {
+/// cause })` would in the current realm, with the current stack and the
+/// location of the innermost script.
+///
+/// Returns the exception that was thrown on failure.
+pub unsafe fn create_error(
+    cx: *mut JSContext,
+    kind: JSExnType,
+    message: impl fmt::Display,
+    cause: Option<HandleValue>,
+    rval: MutableHandleValue,
+) -> Result<(), Exception> {
+    let new_string = |string: &str| {
+        let string = JS_NewStringCopyUTF8N(cx, &*Utf8Chars::from(string));
+        if string.is_null() {
+            Err(Exception::steal(cx))
+        } else {
+            Ok(string)
+        }
+    };
+
+    rooted!(in(cx) let message = new_string(&message.to_string())?);
+    // There is no scripted caller when called from Rust directly.
+    let caller = describe_scripted_caller(cx).unwrap_or_default();
+    rooted!(in(cx) let filename: *mut JSString = new_string(&caller.filename)?);
+
+    rooted!(in(cx) let mut stack = ptr::null_mut::<JSObject>());
+    let mut capture = MaybeUninit::uninit();
+    JS_StackCapture_AllFrames(capture.as_mut_ptr());
+    if !CaptureCurrentStack(
+        cx,
+        stack.handle_mut().raw(),
+        capture.as_mut_ptr(),
+        HandleObject::null().into(),
+    ) {
+        return Err(Exception::steal(cx));
+    }
+
+    let has_cause = cause.is_some();
+    let cause = cause.unwrap_or(HandleValue::undefined());
+    if CreateErrorWithOptionalCause(
+        cx,
+        kind,
+        stack.handle(),
+        filename.handle(),
+        caller.line,
+        caller.col,
+        ptr::null_mut(),
+        message.handle(),
+        cause,
+        has_cause,
+        rval,
+    ) {
+        Ok(())
+    } else {
+        Err(Exception::steal(cx))
+    }
+}
+
+/// A note attached to an error report, pointing at a related location, such
+/// as the previous declaration of a redeclared variable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorNote {
+    /// The text of the note.
+    pub message: String,
+    /// The file the note points at.
+    pub filename: String,
+    /// The line the note points at, starting at 1.
+    pub lineno: u32,
+    /// The column the note points at, starting at 1.
+    pub column: u32,
+}
+
+impl ErrorNote {
+    unsafe fn from_base(base: &JSErrorBase) -> ErrorNote {
+        ErrorNote {
+            message: lossy_string(base.message_.data_),
+            filename: lossy_string(base.filename.data_),
+            lineno: base.lineno,
+            column: base.column._base,
+        }
+    }
+}
+
+/// Copy a possibly null UTF-8 C string.
+unsafe fn lossy_string(chars: *const os::raw::c_char) -> String {
+    if chars.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(chars).to_string_lossy().into_owned()
+    }
+}
+
+/// A JavaScript exception, rooted so that it can be carried around in Rust,
+/// for example as the error of a failed [`evaluate_script`] or of a rejected
+/// promise.
+///
+/// Exceptions taken with [`Exception::steal`] also describe where the error
+/// happened, which is what their `Display` implementation prints.
+///
+/// [`evaluate_script`]: crate::rust::evaluate_script
+pub struct Exception {
+    value: RootedTraceableBox<Heap<JSVal>>,
+    stack_object: RootedTraceableBox<Heap<*mut JSObject>>,
+    uncatchable: bool,
+    out_of_memory: Option<OutOfMemory>,
+    message: String,
+    filename: String,
+    lineno: u32,
+    column: u32,
+    name: Option<String>,
+    stack: Option<String>,
+    notes: Vec<ErrorNote>,
+}
+
+impl Exception {
+    /// Root `value` as an exception, without describing it.
+    pub fn from_value(value: JSVal) -> Exception {
+        Exception {
+            value: RootedTraceableBox::from_box(Heap::boxed(value)),
+            stack_object: RootedTraceableBox::from_box(Heap::boxed(ptr::null_mut())),
+            uncatchable: false,
+            out_of_memory: None,
+            message: String::new(),
+            filename: String::new(),
+            lineno: 0,
+            column: 0,
+            name: None,
+            stack: None,
+            notes: vec![],
+        }
+    }
+
+    /// Take the pending exception and the stack it was thrown with, leaving
+    /// no exception pending.
+    ///
+    /// When nothing is pending, as when a script was terminated by the
+    /// interrupt callback, the exception is [uncatchable] and its value is
+    /// `undefined`.
+    ///
+    /// [uncatchable]: Exception::is_uncatchable
+    pub unsafe fn steal(cx: *mut JSContext) -> Exception {
+        if !JS_IsExceptionPending(cx) {
+            let mut exception = Exception::from_value(UndefinedValue());
+            exception.uncatchable = true;
+            return exception;
+        }
+
+        // Take what the out-of-memory callback recorded even if the exception
+        // is never converted to an `ExecutionError`, so it cannot be
+        // attributed to a later exception.
+        let out_of_memory = JS_IsThrowingOutOfMemory(cx).then(take_out_of_memory);
+        rooted!(in(cx) let mut value = UndefinedValue());
+        rooted!(in(cx) let mut stack = ptr::null_mut::<JSObject>());
+        if !StealPendingExceptionWithStack(cx, value.handle_mut(), stack.handle_mut()) {
+            // Keep the exception even if its stack could not be wrapped.
+            JS_GetPendingException(cx, value.handle_mut());
+            JS_ClearPendingException(cx);
+        }
+        let mut exception = Exception::describe(cx, value.handle(), stack.handle());
+        exception.out_of_memory = out_of_memory;
+        exception
+    }
+
+    /// Create an error of type `kind` with `message`, as [`create_error`]
+    /// does, to be returned as an exception. If creating the error fails, the
+    /// exception is the reason it failed.
+    pub unsafe fn new_error(
+        cx: *mut JSContext,
+        kind: JSExnType,
+        message: impl fmt::Display,
+    ) -> Exception {
+        rooted!(in(cx) let mut error = UndefinedValue());
+        if let Err(exception) = create_error(cx, kind, message, None, error.handle_mut()) {
+            return exception;
+        }
+        Exception::describe(cx, error.handle(), HandleObject::null())
+    }
+
+    /// Make the exception pending on `cx` again, so that a `JSNative`
+    /// returning `false` throws it to its caller. Nothing is pending for
+    /// [uncatchable] exceptions, which keeps them uncatchable, and
+    /// [out-of-memory] exceptions are reported again.
+    ///
+    /// [uncatchable]: Exception::is_uncatchable
+    /// [out-of-memory]: Exception::is_out_of_memory
+    pub unsafe fn throw(&self, cx: *mut JSContext) {
+        if self.uncatchable {
+            return;
+        }
+        if self.out_of_memory.is_some() {
+            JS_ReportOutOfMemory(cx);
+            return;
+        }
+        rooted!(in(cx) let mut value = self.value.get());
+        if JS_WrapValue(cx, value.handle_mut()) {
+            JS_SetPendingException(cx, value.handle(), ExceptionStackBehavior::Capture);
+        }
+    }
+
+    /// Root `value` and read the error report of error objects.
+    pub(crate) unsafe fn describe(
+        cx: *mut JSContext,
+        value: HandleValue,
+        stack: HandleObject,
+    ) -> Exception {
+        let mut exception = Exception::from_value(value.get());
+        exception.stack_object.set(stack.get());
+
+        let report = if value.is_object() {
+            rooted!(in(cx) let object = value.to_object());
+            let report = JS_ErrorFromException(cx, object.handle());
+            if !report.is_null() {
+                exception.name = error_name(cx, object.handle());
+                if stack.is_null() {
+                    exception
+                        .stack_object
+                        .set(ExceptionStackOrNull(object.handle()));
+                }
+            }
+            report
+        } else {
+            ptr::null_mut()
+        };
+
+        if report.is_null() {
+            exception.message = describe_value(cx, value);
+        } else {
+            let base = &(*report)._base;
+            exception.message = lossy_string(base.message_.data_);
+            exception.filename = lossy_string(base.filename.data_);
+            exception.lineno = base.lineno;
+            exception.column = base.column._base;
+            exception.notes = (0..GetErrorNotesLength(report))
+                .filter_map(|index| GetErrorNote(report, index).as_ref())
+                .map(|note| ErrorNote::from_base(note))
+                .collect();
+        }
+
+        if !exception.stack_object.get().is_null() {
+            rooted!(in(cx) let mut string = ptr::null_mut::<JSString>());
+            if BuildStackString(
+                cx,
+                ptr::null_mut(),
+                exception.stack_object.handle(),
+                string.handle_mut(),
+                0,
+                StackFormat::Default,
+            ) {
+                exception.stack =
+                    NonNull::new(string.get()).map(|string| jsstr_to_string(cx, string));
+            } else {
+                JS_ClearPendingException(cx);
+            }
+        }
+        exception
+    }
+
+    /// The thrown value.
+    pub fn value(&self) -> HandleValue<'_> {
+        self.value.handle()
+    }
+
+    /// The `SavedFrame` stack the exception was thrown with, if any.
+    pub fn stack_object(&self) -> Option<HandleObject<'_>> {
+        if self.stack_object.get().is_null() {
+            None
+        } else {
+            Some(self.stack_object.handle())
+        }
+    }
+
+    /// Whether the exception could not be caught by scripts, because
+    /// execution was terminated without an exception value.
+    pub fn is_uncatchable(&self) -> bool {
+        self.uncatchable
+    }
+
+    /// Whether the exception reports that the engine ran out of memory, for
+    /// example because the heap reached the limit of the runtime.
+    pub fn is_out_of_memory(&self) -> bool {
+        self.out_of_memory.is_some()
+    }
+
+    /// The error message, or a description of thrown values that are not
+    /// errors: the string conversion of primitives, and the class of objects.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The file the error was created in, empty for values that are not
+    /// errors.
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// The line the error was created on, starting at 1, or 0 if unknown.
+    pub fn lineno(&self) -> u32 {
+        self.lineno
+    }
+
+    /// The column the error was created at, starting at 1, or 0 if unknown.
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+
+    /// The `name` of the error, like `TypeError`, or `None` for thrown values
+    /// that are not errors.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The formatted stack the exception was thrown with, one frame per line.
+    pub fn stack(&self) -> Option<&str> {
+        self.stack.as_deref()
+    }
+
+    /// The notes of the error report.
+    pub fn notes(&self) -> &[ErrorNote] {
+        &self.notes
+    }
+}
+
+/// Describe a thrown value that is not an error without running scripts, so
+/// no `toString` method is called: primitives by their string conversion, as
+/// `throw 42` reports "uncaught exception: 42", and objects by their class.
+unsafe fn describe_value(cx: *mut JSContext, value: HandleValue) -> String {
+    if value.is_object() {
+        let class = get_object_class(value.to_object());
+        return format!("[object {}]", lossy_string((*class).name));
+    }
+    if value.is_symbol() {
+        // Converting a symbol to a string throws.
+        rooted!(in(cx) let symbol = value.to_symbol());
+        let description = NonNull::new(GetSymbolDescription(symbol.handle()))
+            .map(|description| jsstr_to_string(cx, description))
+            .unwrap_or_default();
+        return format!("Symbol({})", description);
+    }
+    match String::from_jsval(cx, value, ()) {
+        Ok(ConversionResult::Success(message)) => message,
+        _ => {
+            JS_ClearPendingException(cx);
+            String::new()
+        }
+    }
+}
+
+/// The `name` property of an error object, if it is a string.
+unsafe fn error_name(cx: *mut JSContext, error: HandleObject) -> Option<String> {
+    rooted!(in(cx) let mut name = UndefinedValue());
+    if !JS_GetProperty(cx, error, c"name".as_ptr(), name.handle_mut()) {
+        JS_ClearPendingException(cx);
+        return None;
+    }
+    if !name.get().is_string() {
+        return None;
+    }
+    NonNull::new(name.get().to_string()).map(|name| jsstr_to_string(cx, name))
+}
+
+impl fmt::Debug for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Exception")
+            .field("value", &self.value.get())
+            .field("name", &self.name)
+            .field("message", &self.message)
+            .field("filename", &self.filename)
+            .field("lineno", &self.lineno)
+            .field("column", &self.column)
+            .finish()
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.filename.is_empty() {
+            write!(f, "{}:{}:{}: ", self.filename, self.lineno, self.column)?;
+        }
+        match &self.name {
+            Some(name) if self.message.is_empty() => f.write_str(name),
+            Some(name) => write!(f, "{}: {}", name, self.message),
+            None if self.uncatchable => f.write_str("uncatchable exception"),
+            None if self.message.is_empty() => f.write_str("uncaught exception"),
+            None => write!(f, "uncaught exception: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Exception {}
+
+/// Why a script run with a limit did not complete.
+#[derive(Debug)]
+pub enum ExecutionError {
+    /// The script threw `Exception`, or was terminated for another reason.
+    Exception(Exception),
+    /// The script was terminated because it ran out of time.
+    Timeout,
+    /// The engine ran out of memory while the script ran.
+    OutOfMemory(OutOfMemory),
+}
+
+impl From<Exception> for ExecutionError {
+    /// Out-of-memory exceptions become [`ExecutionError::OutOfMemory`], with
+    /// the realm recorded by the out-of-memory callback.
+    fn from(mut exception: Exception) -> ExecutionError {
+        match exception.out_of_memory.take() {
+            Some(out_of_memory) => ExecutionError::OutOfMemory(out_of_memory),
+            None => ExecutionError::Exception(exception),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionError::Exception(exception) => exception.fmt(f),
+            ExecutionError::Timeout => f.write_str("the script ran out of time"),
+            ExecutionError::OutOfMemory(_) => f.write_str("the script ran out of memory"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
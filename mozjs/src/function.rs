@@ -0,0 +1,229 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Rust closures as JavaScript functions.
+//!
+//! [`JsFunction::new`] wraps a closure in a function object. The arguments
+//! of a call are converted to the closure's argument tuple with
+//! [`FromJSValConvertible`], using the default configuration of each type,
+//! with missing arguments being `undefined`. The result is converted back
+//! with [`ToJSValConvertible`], and an [`Exception`] is thrown to the caller.
+//!
+//! ```ignore
+//! let add = JsFunction::new(cx, c"add", |_cx, (a, b): (i32, i32)| Ok(a + b))?;
+//! ```
+//!
+//! The closure is dropped when the function object is finalized. Values it
+//! captures are not traced, and rooting them from the closure would keep
+//! the function alive forever, so closures should not capture GC things.
+//!
+//! Functions that capture nothing can use the [`js_native`] attribute
+//! instead, which generates a `JSNative` and a `JSFunctionSpec` for
+//! [`define_methods`](crate::rust::define_methods) at compile time.
+
+use std::ffi::CStr;
+use std::ptr::{self, NonNull};
+
+use crate::context::JSContext;
+use crate::conversions::{ConversionResult, FromJSValConvertible, ToJSValConvertible};
+use crate::error::{throw_type_error, Exception};
+use crate::glue::JS_GetReservedSlot;
+use crate::jsapi;
+use crate::jsapi::{CallArgs, GCContext, Heap, JSClass, JSClassOps, JSObject, Value};
+use crate::jsapi::{GetFunctionNativeReserved, SetFunctionNativeReserved};
+use crate::jsapi::{JS_GetFunctionObject, JS_SetReservedSlot};
+use crate::jsapi::{JSCLASS_FOREGROUND_FINALIZE, JSCLASS_RESERVED_SLOTS_SHIFT};
+use crate::jsval::{ObjectValue, PrivateValue, UndefinedValue};
+use crate::panic::wrap_panic;
+use crate::rooted;
+use crate::rust::wrappers2::{JS_NewObject, NewFunctionWithReserved};
+use crate::rust::{Handle, HandleObject, RootedTraceableBox};
+
+pub use mozjs_derive::js_native;
+
+/// The arguments of a [`JsFunction`], converted from the arguments of a call.
+///
+/// This is implemented for tuples of up to eight types whose conversions
+/// have a default configuration.
+pub trait FromJSArgs: Sized {
+    /// The number of arguments, which becomes the `length` of the function.
+    const COUNT: u32;
+
+    /// Convert the arguments of `args`.
+    ///
+    /// Returns `Ok(Failure(reason))` without an exception pending, like
+    /// [`FromJSValConvertible::from_jsval`], if an argument cannot be
+    /// converted.
+    unsafe fn from_args(
+        cx: *mut jsapi::JSContext,
+        args: &CallArgs,
+    ) -> Result<ConversionResult<Self>, ()>;
+}
+
+macro_rules! impl_from_js_args {
+    ($count:literal; $($name:ident: $index:literal),*) => {
+        impl<$($name: FromJSValConvertible),*> FromJSArgs for ($($name,)*)
+        where
+            $($name::Config: Default,)*
+        {
+            const COUNT: u32 = $count;
+
+            #[allow(unused_variables)]
+            unsafe fn from_args(
+                cx: *mut jsapi::JSContext,
+                args: &CallArgs,
+            ) -> Result<ConversionResult<Self>, ()> {
+                Ok(ConversionResult::Success(($(
+                    match $name::from_jsval(
+                        cx,
+                        Handle::from_raw(args.get($index)),
+                        Default::default(),
+                    )? {
+                        ConversionResult::Success(value) => value,
+                        ConversionResult::Failure(reason) => {
+                            return Ok(ConversionResult::Failure(reason));
+                        }
+                    },
+                )*)))
+            }
+        }
+    };
+}
+
+impl_from_js_args!(0;);
+impl_from_js_args!(1; A: 0);
+impl_from_js_args!(2; A: 0, B: 1);
+impl_from_js_args!(3; A: 0, B: 1, C: 2);
+impl_from_js_args!(4; A: 0, B: 1, C: 2, D: 3);
+impl_from_js_args!(5; A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_from_js_args!(6; A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+impl_from_js_args!(7; A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+impl_from_js_args!(8; A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7);
+
+/// A closure behind a [`JsFunction`], with its arguments and result
+/// conversions. Returns `false` with an exception pending on failure.
+type Closure = dyn Fn(&mut JSContext, &CallArgs) -> bool;
+
+/// The reserved slot of the function that holds the closure's holder.
+const FUNCTION_HOLDER_SLOT: usize = 0;
+/// The reserved slot of the holder that holds the boxed closure.
+const HOLDER_CLOSURE_SLOT: u32 = 0;
+
+static HOLDER_CLASS_OPS: JSClassOps = JSClassOps {
+    addProperty: None,
+    delProperty: None,
+    enumerate: None,
+    newEnumerate: None,
+    resolve: None,
+    mayResolve: None,
+    finalize: Some(finalize_holder),
+    call: None,
+    construct: None,
+    trace: None,
+};
+
+/// The class of the objects that own the closures, so that they are dropped
+/// when their function, the only thing referring to the holder, is collected.
+static HOLDER_CLASS: JSClass = JSClass {
+    name: c"RustClosure".as_ptr(),
+    flags: JSCLASS_FOREGROUND_FINALIZE | (1 << JSCLASS_RESERVED_SLOTS_SHIFT),
+    cOps: &HOLDER_CLASS_OPS as *const JSClassOps,
+    spec: ptr::null(),
+    ext: ptr::null(),
+    oOps: ptr::null(),
+};
+
+unsafe extern "C" fn finalize_holder(_gcx: *mut GCContext, holder: *mut JSObject) {
+    let mut closure = UndefinedValue();
+    JS_GetReservedSlot(holder, HOLDER_CLOSURE_SLOT, &mut closure);
+    if !closure.is_undefined() {
+        drop(Box::from_raw(closure.to_private() as *mut Box<Closure>));
+    }
+}
+
+unsafe extern "C" fn call_closure(cx: *mut jsapi::JSContext, argc: u32, vp: *mut Value) -> bool {
+    let mut result = false;
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        let args = CallArgs::from_vp(vp, argc);
+        // The callee roots the holder until the return value replaces it.
+        let holder = (*GetFunctionNativeReserved(args.callee(), FUNCTION_HOLDER_SLOT)).to_object();
+        let mut closure = UndefinedValue();
+        JS_GetReservedSlot(holder, HOLDER_CLOSURE_SLOT, &mut closure);
+        let closure = &*(closure.to_private() as *const Box<Closure>);
+        result = closure(&mut cx, &args);
+    });
+    result
+}
+
+/// A JavaScript function calling a Rust closure, rooted for as long as this
+/// value lives.
+pub struct JsFunction {
+    function: RootedTraceableBox<Heap<*mut JSObject>>,
+}
+
+impl JsFunction {
+    /// Create a function named `name` in the current realm that calls `f`
+    /// with the converted arguments. Arguments that cannot be converted
+    /// throw a `TypeError`. Returns `None` with an exception pending on
+    /// failure.
+    pub fn new<Args, R, F>(cx: &mut JSContext, name: &CStr, f: F) -> Option<JsFunction>
+    where
+        Args: FromJSArgs,
+        R: ToJSValConvertible,
+        F: Fn(&mut JSContext, Args) -> Result<R, Exception> + 'static,
+    {
+        let closure: Box<Closure> = Box::new(move |cx, args| unsafe {
+            let args_tuple = match Args::from_args(cx.raw_cx(), args) {
+                Ok(ConversionResult::Success(args_tuple)) => args_tuple,
+                Ok(ConversionResult::Failure(reason)) => {
+                    throw_type_error(cx.raw_cx(), &reason);
+                    return false;
+                }
+                Err(()) => return false,
+            };
+            match f(cx, args_tuple) {
+                Ok(result) => {
+                    rooted!(&in(cx) let mut rval = UndefinedValue());
+                    result.to_jsval(cx.raw_cx(), rval.handle_mut());
+                    args.rval().set(rval.get());
+                    true
+                }
+                Err(exception) => {
+                    exception.throw(cx.raw_cx());
+                    false
+                }
+            }
+        });
+
+        unsafe {
+            rooted!(&in(cx) let holder = JS_NewObject(cx, &HOLDER_CLASS));
+            if holder.get().is_null() {
+                return None;
+            }
+            let closure = Box::into_raw(Box::new(closure));
+            JS_SetReservedSlot(
+                holder.get(),
+                HOLDER_CLOSURE_SLOT,
+                &PrivateValue(closure as *const _),
+            );
+
+            let function =
+                NewFunctionWithReserved(cx, Some(call_closure), Args::COUNT, 0, name.as_ptr());
+            if function.is_null() {
+                return None;
+            }
+            let function = JS_GetFunctionObject(function);
+            SetFunctionNativeReserved(function, FUNCTION_HOLDER_SLOT, &ObjectValue(holder.get()));
+            Some(JsFunction {
+                function: RootedTraceableBox::from_box(Heap::boxed(function)),
+            })
+        }
+    }
+
+    /// The function object.
+    pub fn handle(&self) -> HandleObject<'_> {
+        self.function.handle()
+    }
+}
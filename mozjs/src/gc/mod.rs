@@ -1,12 +1,17 @@
 pub use crate::gc::collections::*;
 pub use crate::gc::custom::*;
+pub use crate::gc::observer::{GcObserver, GcStats};
 pub use crate::gc::root::*;
+pub use crate::gc::scheduler::GcScheduler;
 pub use crate::gc::trace::*;
+pub use mozjs_derive::Traceable;
 pub use mozjs_sys::jsgc::{GCMethods, Initialize, RootKind, Rootable, StackGCVector, ValueArray};
 pub use mozjs_sys::trace::Traceable;
 
 mod collections;
 mod custom;
 mod macros;
+pub(crate) mod observer;
 mod root;
+mod scheduler;
 mod trace;
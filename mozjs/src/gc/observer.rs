@@ -0,0 +1,217 @@
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::ptr;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::jsapi;
+use crate::jsapi::{GCContext, GCDescription, GCNurseryProgress, GCProgress, GCReason};
+use crate::jsapi::{JSContext, JSFinalizeStatus, JSGCParamKey, JSGCStatus};
+use crate::panic::wrap_panic;
+
+/// Receives the collections of a runtime, registered with
+/// [`Runtime::set_gc_observer`](crate::rust::Runtime::set_gc_observer).
+///
+/// The methods are called while the GC runs, so they must not use the
+/// JavaScript heap. Panics are resumed once control returns to Rust.
+pub trait GcObserver: 'static {
+    /// A major collection starts.
+    fn on_gc_begin(&self, reason: GCReason) {
+        let _ = reason;
+    }
+
+    /// A major collection ends. Incremental collections run in several
+    /// slices, so `duration` includes the time scripts ran between them.
+    fn on_gc_end(&self, stats: &GcStats) {
+        let _ = stats;
+    }
+
+    /// A slice of a major collection ends. `duration` is the length of the
+    /// pause.
+    fn on_slice(&self, stats: &GcStats) {
+        let _ = stats;
+    }
+
+    /// A collection of the nursery ends.
+    fn on_minor_gc(&self, stats: &GcStats) {
+        let _ = stats;
+    }
+
+    /// Finalization reaches `status`.
+    fn on_finalize(&self, status: JSFinalizeStatus) {
+        let _ = status;
+    }
+}
+
+/// A collection, or part of one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GcStats {
+    /// Why the collection happened.
+    pub reason: GCReason,
+    /// How long it took.
+    pub duration: Duration,
+    /// The size of the GC heap before it (`JSGC_BYTES`).
+    pub heap_bytes_before: usize,
+    /// The size of the GC heap after it.
+    pub heap_bytes_after: usize,
+}
+
+/// The observer of the runtime of this thread, and the starts of the
+/// collections it is told about.
+struct Observer {
+    observer: Box<dyn GcObserver>,
+    cycle: Cell<Option<(Instant, usize)>>,
+    slice: Cell<Option<(Instant, usize)>>,
+    nursery: Cell<Option<(Instant, usize)>>,
+}
+
+thread_local!(static GC_OBSERVER: RefCell<Option<Rc<Observer>>> = const { RefCell::new(None) });
+/// The slice callback that was installed before the observer, which is
+/// still called.
+thread_local!(static PREVIOUS_SLICE_CALLBACK: Cell<jsapi::GCSliceCallback> = const { Cell::new(None) });
+
+fn observer() -> Option<Rc<Observer>> {
+    GC_OBSERVER.with(|observer| observer.borrow().clone())
+}
+
+unsafe fn heap_bytes(cx: *mut JSContext) -> usize {
+    jsapi::JS_GetGCParameter(cx, JSGCParamKey::JSGC_BYTES) as usize
+}
+
+/// Record the start of a collection in `start`.
+unsafe fn begin(cx: *mut JSContext, start: &Cell<Option<(Instant, usize)>>) {
+    start.set(Some((Instant::now(), heap_bytes(cx))));
+}
+
+/// The stats of the collection that started at `start`, if it was recorded.
+unsafe fn end(
+    cx: *mut JSContext,
+    start: &Cell<Option<(Instant, usize)>>,
+    reason: GCReason,
+) -> Option<GcStats> {
+    let (start, heap_bytes_before) = start.take()?;
+    Some(GcStats {
+        reason,
+        duration: start.elapsed(),
+        heap_bytes_before,
+        heap_bytes_after: heap_bytes(cx),
+    })
+}
+
+unsafe extern "C" fn gc_callback(
+    cx: *mut JSContext,
+    status: JSGCStatus,
+    reason: GCReason,
+    _data: *mut c_void,
+) {
+    wrap_panic(&mut || {
+        let Some(observer) = observer() else {
+            return;
+        };
+        match status {
+            JSGCStatus::JSGC_BEGIN => {
+                begin(cx, &observer.cycle);
+                observer.observer.on_gc_begin(reason);
+            }
+            JSGCStatus::JSGC_END => {
+                if let Some(stats) = end(cx, &observer.cycle, reason) {
+                    observer.observer.on_gc_end(&stats);
+                }
+            }
+        }
+    });
+}
+
+unsafe extern "C" fn slice_callback(
+    cx: *mut JSContext,
+    progress: GCProgress,
+    desc: *const GCDescription,
+) {
+    wrap_panic(&mut || {
+        let Some(observer) = observer() else {
+            return;
+        };
+        match progress {
+            GCProgress::GC_SLICE_BEGIN => begin(cx, &observer.slice),
+            GCProgress::GC_SLICE_END => {
+                if let Some(stats) = end(cx, &observer.slice, (*desc).reason_) {
+                    observer.observer.on_slice(&stats);
+                }
+            }
+            GCProgress::GC_CYCLE_BEGIN | GCProgress::GC_CYCLE_END => {}
+        }
+    });
+    if let Some(previous) = PREVIOUS_SLICE_CALLBACK.with(Cell::get) {
+        previous(cx, progress, desc);
+    }
+}
+
+unsafe extern "C" fn nursery_callback(
+    cx: *mut JSContext,
+    progress: GCNurseryProgress,
+    reason: GCReason,
+    _data: *mut c_void,
+) {
+    wrap_panic(&mut || {
+        let Some(observer) = observer() else {
+            return;
+        };
+        match progress {
+            GCNurseryProgress::GC_NURSERY_COLLECTION_START => begin(cx, &observer.nursery),
+            GCNurseryProgress::GC_NURSERY_COLLECTION_END => {
+                if let Some(stats) = end(cx, &observer.nursery, reason) {
+                    observer.observer.on_minor_gc(&stats);
+                }
+            }
+        }
+    });
+}
+
+unsafe extern "C" fn finalize_callback(
+    _gcx: *mut GCContext,
+    status: JSFinalizeStatus,
+    _data: *mut c_void,
+) {
+    wrap_panic(&mut || {
+        if let Some(observer) = observer() {
+            observer.observer.on_finalize(status);
+        }
+    });
+}
+
+/// Install `observer` and the GC callbacks on the runtime of this thread,
+/// replacing the observer installed before.
+pub(crate) fn install<O: GcObserver>(cx: *mut JSContext, observer: O) {
+    let observer = Observer {
+        observer: Box::new(observer),
+        cycle: Cell::new(None),
+        slice: Cell::new(None),
+        nursery: Cell::new(None),
+    };
+    let previous = GC_OBSERVER.with(|slot| slot.borrow_mut().replace(Rc::new(observer)));
+    if previous.is_some() {
+        return;
+    }
+    unsafe {
+        jsapi::JS_SetGCCallback(cx, Some(gc_callback), ptr::null_mut());
+        let previous = jsapi::SetGCSliceCallback(cx, Some(slice_callback));
+        PREVIOUS_SLICE_CALLBACK.with(|slot| slot.set(previous));
+        jsapi::AddGCNurseryCollectionCallback(cx, Some(nursery_callback), ptr::null_mut());
+        jsapi::JS_AddFinalizeCallback(cx, Some(finalize_callback), ptr::null_mut());
+    }
+}
+
+/// Drop the observer of the runtime of this thread and remove its GC
+/// callbacks, putting back the slice callback installed before it.
+pub(crate) fn uninstall(cx: *mut JSContext) {
+    let observer = GC_OBSERVER.with(|slot| slot.borrow_mut().take());
+    if observer.is_none() {
+        return;
+    }
+    unsafe {
+        jsapi::JS_SetGCCallback(cx, None, ptr::null_mut());
+        jsapi::SetGCSliceCallback(cx, PREVIOUS_SLICE_CALLBACK.with(Cell::take));
+        jsapi::RemoveGCNurseryCollectionCallback(cx, Some(nursery_callback), ptr::null_mut());
+        jsapi::JS_RemoveFinalizeCallback(cx, Some(finalize_callback));
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+use crate::context::JSContext;
+use crate::jsapi;
+use crate::jsapi::{GCOptions, GCReason};
+use crate::rust::wrappers2::{
+    FinishIncrementalGC, IncrementalGCSliceWithBudget, IsIncrementalGCInProgress, JS_GetRuntime,
+    NonIncrementalGC, PrepareForFullGC, PrepareForIncrementalGC, StartIncrementalGCWithBudget,
+};
+
+/// Runs the collections of a runtime when the embedder chooses to, for
+/// example in the idle time between frames.
+///
+/// ```ignore
+/// let mut scheduler = GcScheduler::new(cx);
+/// // After rendering a frame, with `deadline` the start of the next one:
+/// scheduler.idle(deadline);
+/// ```
+///
+/// Incremental collections are split into slices whose length is bounded by
+/// a budget. They are only incremental when `JSGC_INCREMENTAL_GC_ENABLED` is
+/// set, with [`RuntimeBuilder::gc_parameter`]; otherwise each collection
+/// completes in its first slice. The engine may also start collections and
+/// run slices itself, when allocations reach its thresholds.
+///
+/// [`RuntimeBuilder::gc_parameter`]: crate::rust::RuntimeBuilder::gc_parameter
+pub struct GcScheduler<'cx> {
+    cx: &'cx mut JSContext,
+}
+
+/// A budget in microseconds, as taken by the glue.
+fn micros(budget: Duration) -> i64 {
+    i64::try_from(budget.as_micros()).unwrap_or(i64::MAX)
+}
+
+impl<'cx> GcScheduler<'cx> {
+    /// A scheduler for the runtime of `cx`.
+    pub fn new(cx: &'cx mut JSContext) -> GcScheduler<'cx> {
+        GcScheduler { cx }
+    }
+
+    /// Whether an incremental collection has started and not finished.
+    pub fn is_in_progress(&self) -> bool {
+        unsafe { IsIncrementalGCInProgress(self.cx) }
+    }
+
+    /// Collect every zone without interruption, after finishing the
+    /// incremental collection in progress, if any.
+    pub fn collect_full(&mut self, reason: GCReason) {
+        self.finish(reason);
+        unsafe {
+            PrepareForFullGC(self.cx);
+            NonIncrementalGC(self.cx, GCOptions::Normal, reason);
+        }
+    }
+
+    /// Start an incremental collection of every zone, and run its first
+    /// slice for at most `budget`. Runs a slice of the collection in
+    /// progress instead, if any. Returns whether the collection is still in
+    /// progress.
+    pub fn start_incremental(&mut self, reason: GCReason, budget: Duration) -> bool {
+        self.start(reason, budget, false)
+    }
+
+    /// Run a slice of the incremental collection in progress for at most
+    /// `budget`. Returns whether the collection is still in progress.
+    pub fn slice(&mut self, reason: GCReason, budget: Duration) -> bool {
+        self.run_slice(reason, budget, false)
+    }
+
+    /// Finish the incremental collection in progress, if any, without
+    /// interruption.
+    pub fn finish(&mut self, reason: GCReason) {
+        if self.is_in_progress() {
+            unsafe {
+                PrepareForIncrementalGC(self.cx);
+                FinishIncrementalGC(self.cx, reason);
+            }
+        }
+    }
+
+    /// Use the time until `deadline` for GC work: a slice of the collection
+    /// in progress, or the start of one if the engine wants a collection
+    /// soon. The slices are recorded as idle time in GC profiles. Returns
+    /// whether a collection is in progress afterwards.
+    pub fn idle(&mut self, deadline: Instant) -> bool {
+        let budget = deadline.saturating_duration_since(Instant::now());
+        if budget.is_zero() {
+            return self.is_in_progress();
+        }
+        if self.is_in_progress() {
+            return self.run_slice(GCReason::API, budget, true);
+        }
+        let reason = unsafe { jsapi::WantEagerMajorGC(JS_GetRuntime(self.cx)) };
+        if reason == GCReason::NO_REASON {
+            return false;
+        }
+        self.start(reason, budget, true)
+    }
+
+    fn start(&mut self, reason: GCReason, budget: Duration, idle: bool) -> bool {
+        if self.is_in_progress() {
+            return self.run_slice(reason, budget, idle);
+        }
+        unsafe {
+            PrepareForFullGC(self.cx);
+            StartIncrementalGCWithBudget(self.cx, GCOptions::Normal, reason, micros(budget), idle);
+        }
+        self.is_in_progress()
+    }
+
+    fn run_slice(&mut self, reason: GCReason, budget: Duration, idle: bool) -> bool {
+        if !self.is_in_progress() {
+            return false;
+        }
+        unsafe {
+            PrepareForIncrementalGC(self.cx);
+            IncrementalGCSliceWithBudget(self.cx, reason, micros(budget), idle);
+        }
+        self.is_in_progress()
+    }
+}
@@ -2,6 +2,9 @@ wrap!(glue: pub fn RegisterScriptEnvironmentPreparer(cx: &mut JSContext, hook: I
 wrap!(glue: pub fn RunScriptEnvironmentPreparerClosure(cx: &mut JSContext, closure: *mut ScriptEnvironmentPreparer_Closure) -> bool);
 wrap!(glue: pub fn InvokeGetOwnPropertyDescriptor(handler: *const ::std::os::raw::c_void, cx: &mut JSContext, proxy: HandleObject, id: HandleId, desc: MutableHandle<PropertyDescriptor>, isNone: *mut bool) -> bool);
 wrap!(glue: pub fn InvokeHasOwn(handler: *const ::std::os::raw::c_void, cx: &mut JSContext, proxy: HandleObject, id: HandleId, bp: *mut bool) -> bool);
+wrap!(glue: pub fn CallBaseProxyHas(cx: &mut JSContext, proxy: HandleObject, id: HandleId, bp: *mut bool) -> bool);
+wrap!(glue: pub fn CallBaseProxyGet(cx: &mut JSContext, proxy: HandleObject, receiver: HandleValue, id: HandleId, vp: MutableHandleValue) -> bool);
+wrap!(glue: pub fn CallBaseProxySet(cx: &mut JSContext, proxy: HandleObject, id: HandleId, v: HandleValue, receiver: HandleValue, result: *mut ObjectOpResult) -> bool);
 wrap!(glue: pub fn CallJitGetterOp(info: *const JSJitInfo, cx: &mut JSContext, thisObj: HandleObject, specializedThis: *mut ::std::os::raw::c_void, argc: ::std::os::raw::c_uint, vp: *mut Value) -> bool);
 wrap!(glue: pub fn CallJitSetterOp(info: *const JSJitInfo, cx: &mut JSContext, thisObj: HandleObject, specializedThis: *mut ::std::os::raw::c_void, argc: ::std::os::raw::c_uint, vp: *mut Value) -> bool);
 wrap!(glue: pub fn CallJitMethodOp(info: *const JSJitInfo, cx: &mut JSContext, thisObj: HandleObject, specializedThis: *mut ::std::os::raw::c_void, argc: u32, vp: *mut Value) -> bool);
@@ -24,6 +27,9 @@ wrap!(glue: pub fn CreateRootedIdVector(cx: &mut JSContext) -> *mut PersistentRo
 wrap!(glue: pub fn AppendToIdVector(v: MutableHandleIdVector, id: HandleId) -> bool);
 wrap!(glue: pub fn CreateRootedObjectVector(aCx: &mut JSContext) -> *mut PersistentRootedObjectVector);
 wrap!(glue: pub fn CollectServoSizes(cx: &mut JSContext, sizes: *mut ServoSizes, gs: GetSize) -> bool);
+wrap!(glue: pub fn CollectMemoryReport(cx: &mut JSContext, sizes: *mut MemoryReportSizes, data: *mut ::std::os::raw::c_void, realmCallback: RealmSizesCallback) -> bool);
+wrap!(glue: pub fn StartIncrementalGCWithBudget(cx: &mut JSContext, options: GCOptions, reason: GCReason, micros: i64, idle: bool));
+wrap!(glue: pub fn IncrementalGCSliceWithBudget(cx: &mut JSContext, reason: GCReason, micros: i64, idle: bool));
 wrap!(glue: pub fn JS_GetPromiseResult(promise: HandleObject, dest: MutableHandleValue));
 wrap!(glue: pub fn JS_GetScriptPrivate(script: *mut JSScript, dest: MutableHandleValue));
 wrap!(glue: pub fn JS_MaybeGetScriptPrivate(obj: *mut JSObject, dest: MutableHandleValue));
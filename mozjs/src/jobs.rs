@@ -0,0 +1,325 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Promise job queues.
+//!
+//! SpiderMonkey does not run Promise reactions on its own; the embedding
+//! provides a [`JS::JobQueue`](crate::jsapi::JobQueue) that receives each job
+//! and decides when to run it. This module exposes that hook as the
+//! [`JobQueue`] trait, backed by the `RustJobQueue` glue, and provides
+//! [`MicrotaskQueue`], a FIFO queue with the usual microtask semantics.
+//!
+//! ```ignore
+//! let mut runtime = Runtime::new(engine.handle());
+//! runtime.set_job_queue(MicrotaskQueue::default());
+//! // ... evaluate scripts that use promises ...
+//! run_jobs(runtime.cx());
+//! ```
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::ffi::c_void;
+use std::ptr::{self, NonNull};
+
+use log::warn;
+
+use crate::context::JSContext;
+use crate::glue::{CreateJobQueue, DeleteJobQueue, JobQueueTraps};
+use crate::jsapi;
+use crate::jsapi::{Heap, JSObject};
+use crate::jsval::{ObjectValue, UndefinedValue};
+use crate::panic::{maybe_resume_unwind, wrap_panic};
+use crate::realm::AutoRealm;
+use crate::rooted;
+use crate::rust::wrappers2::{Call, JS_ClearPendingException, RunJobs};
+use crate::rust::{Handle, HandleObject, HandleValue, MutableHandleObject, RootedTraceableBox};
+
+/// An embedder-side queue of Promise jobs.
+///
+/// Every method is called on the thread owning the runtime, possibly
+/// re-entrantly from inside [`JobQueue::run_jobs`].
+pub trait JobQueue: 'static {
+    /// Queue `job`, a function object to be called with no arguments, for the
+    /// reaction of `promise`. Returns `false` if the job could not be queued,
+    /// which SpiderMonkey reports as an out-of-memory condition.
+    fn enqueue_promise_job(
+        &self,
+        cx: &mut JSContext,
+        promise: HandleObject,
+        job: HandleObject,
+        allocation_site: HandleObject,
+        host_defined_data: HandleObject,
+    ) -> bool;
+
+    /// Run queued jobs, including any that get queued while doing so, until
+    /// the queue is empty.
+    fn run_jobs(&self, cx: &mut JSContext);
+
+    /// Whether there are no jobs waiting to run.
+    fn is_empty(&self) -> bool;
+
+    /// Create an empty queue to use while this one is saved, for example while
+    /// the debugger runs code in the middle of a job.
+    fn new_interrupt_queue(&self) -> Self
+    where
+        Self: Sized;
+
+    /// Provide the host defined data that is passed back to
+    /// [`JobQueue::enqueue_promise_job`] for jobs queued by the current script.
+    /// The default provides none.
+    fn host_defined_data(&self, _cx: &mut JSContext, mut data: MutableHandleObject) -> bool {
+        data.set(ptr::null_mut());
+        true
+    }
+}
+
+/// A FIFO queue of Promise jobs, run in the order they were queued.
+///
+/// A job that throws does not stop the queue; its exception is reported
+/// with `log` and cleared before the next job runs.
+#[derive(Default)]
+pub struct MicrotaskQueue {
+    jobs: RefCell<VecDeque<RootedTraceableBox<Heap<*mut JSObject>>>>,
+    draining: Cell<bool>,
+}
+
+impl MicrotaskQueue {
+    /// The number of jobs waiting to run.
+    pub fn len(&self) -> usize {
+        self.jobs.borrow().len()
+    }
+
+    /// Drop all pending jobs without running them.
+    pub fn clear(&self) {
+        self.jobs.borrow_mut().clear();
+    }
+}
+
+/// Clears the `draining` flag of a [`MicrotaskQueue`] when dropped.
+struct Draining<'a>(&'a Cell<bool>);
+
+impl Drop for Draining<'_> {
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+impl JobQueue for MicrotaskQueue {
+    fn enqueue_promise_job(
+        &self,
+        _cx: &mut JSContext,
+        _promise: HandleObject,
+        job: HandleObject,
+        _allocation_site: HandleObject,
+        _host_defined_data: HandleObject,
+    ) -> bool {
+        self.jobs
+            .borrow_mut()
+            .push_back(RootedTraceableBox::from_box(Heap::boxed(job.get())));
+        true
+    }
+
+    fn run_jobs(&self, cx: &mut JSContext) {
+        // A job may end up calling back into us; the outer loop will pick up
+        // anything queued in the meantime.
+        if self.draining.replace(true) {
+            return;
+        }
+        // Reset the flag even if a job panics, so the queue can run again.
+        let _draining = Draining(&self.draining);
+        loop {
+            // Don't hold the borrow while the job runs, it may queue more jobs.
+            let job = self.jobs.borrow_mut().pop_front();
+            let Some(job) = job else {
+                break;
+            };
+            let job = NonNull::new(job.get()).expect("Promise jobs are never null");
+            let mut realm = AutoRealm::new(cx, job);
+            let cx = &mut *realm;
+            rooted!(&in(cx) let callee = ObjectValue(job.as_ptr()));
+            rooted!(&in(cx) let mut rval = UndefinedValue());
+            let args = jsapi::HandleValueArray::empty();
+            let ok = unsafe {
+                Call(
+                    cx,
+                    HandleValue::undefined(),
+                    callee.handle(),
+                    &args,
+                    rval.handle_mut(),
+                )
+            };
+            if !ok {
+                warn!("Uncaught exception in Promise job");
+                unsafe { JS_ClearPendingException(cx) };
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.jobs.borrow().is_empty()
+    }
+
+    fn new_interrupt_queue(&self) -> MicrotaskQueue {
+        MicrotaskQueue::default()
+    }
+}
+
+/// Run the jobs in the job queue installed on the current runtime until it is
+/// empty. Does nothing if no queue is installed.
+///
+/// Afterwards, futures awaiting a [`JsPromise`](crate::promise::JsPromise)
+/// that has settled are woken.
+pub fn run_jobs(cx: &mut JSContext) {
+    unsafe {
+        RunJobs(cx);
+    }
+    maybe_resume_unwind();
+    crate::promise::wake_settled_promises();
+}
+
+/// The queues created for `saveJobQueue`, most recent last. Owned by the
+/// `RustJobQueue` and freed through `dropInterruptQueues`.
+struct InterruptQueues<Q: JobQueue> {
+    root: *const Q,
+    saved: Vec<Box<Q>>,
+}
+
+/// A job queue installed on a runtime with `JS::SetJobQueue`.
+pub(crate) struct InstalledJobQueue {
+    raw: *mut jsapi::JobQueue,
+    queue: Box<dyn Any>,
+}
+
+impl InstalledJobQueue {
+    /// Create the glue job queue for `queue` and install it on `cx`.
+    pub(crate) fn install<Q: JobQueue>(cx: &JSContext, queue: Q) -> InstalledJobQueue {
+        let queue = Box::new(queue);
+        let root: *const Q = &*queue;
+        let interrupt_queues = Box::into_raw(Box::new(InterruptQueues::<Q> {
+            root,
+            saved: Vec::new(),
+        }));
+        let traps = JobQueueTraps {
+            getHostDefinedData: Some(get_host_defined_data::<Q>),
+            enqueuePromiseJob: Some(enqueue_promise_job::<Q>),
+            runJobs: Some(run_jobs_trap::<Q>),
+            empty: Some(empty::<Q>),
+            pushNewInterruptQueue: Some(push_new_interrupt_queue::<Q>),
+            popInterruptQueue: Some(pop_interrupt_queue::<Q>),
+            dropInterruptQueues: Some(drop_interrupt_queues::<Q>),
+        };
+        unsafe {
+            let raw = CreateJobQueue(
+                &traps,
+                root as *const c_void,
+                interrupt_queues as *mut c_void,
+            );
+            crate::rust::wrappers2::SetJobQueue(cx, raw);
+            InstalledJobQueue { raw, queue }
+        }
+    }
+
+    /// The queue passed to [`InstalledJobQueue::install`], if it has type `Q`.
+    pub(crate) fn downcast_ref<Q: JobQueue>(&self) -> Option<&Q> {
+        self.queue.downcast_ref()
+    }
+}
+
+impl Drop for InstalledJobQueue {
+    fn drop(&mut self) {
+        unsafe { DeleteJobQueue(self.raw) }
+    }
+}
+
+unsafe extern "C" fn get_host_defined_data<Q: JobQueue>(
+    queue: *const c_void,
+    cx: *mut jsapi::JSContext,
+    data: jsapi::MutableHandleObject,
+) -> bool {
+    let mut result = false;
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        let queue = &*(queue as *const Q);
+        result = queue.host_defined_data(&mut cx, MutableHandleObject::from_raw(data));
+    });
+    result
+}
+
+unsafe extern "C" fn enqueue_promise_job<Q: JobQueue>(
+    queue: *const c_void,
+    cx: *mut jsapi::JSContext,
+    promise: jsapi::HandleObject,
+    job: jsapi::HandleObject,
+    allocation_site: jsapi::HandleObject,
+    host_defined_data: jsapi::HandleObject,
+) -> bool {
+    let mut result = false;
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        let queue = &*(queue as *const Q);
+        result = queue.enqueue_promise_job(
+            &mut cx,
+            Handle::from_raw(promise),
+            Handle::from_raw(job),
+            Handle::from_raw(allocation_site),
+            Handle::from_raw(host_defined_data),
+        );
+    });
+    result
+}
+
+unsafe extern "C" fn run_jobs_trap<Q: JobQueue>(queue: *const c_void, cx: *mut jsapi::JSContext) {
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        let queue = &*(queue as *const Q);
+        queue.run_jobs(&mut cx);
+    });
+}
+
+unsafe extern "C" fn empty<Q: JobQueue>(queue: *const c_void) -> bool {
+    let mut result = true;
+    wrap_panic(&mut || {
+        result = (*(queue as *const Q)).is_empty();
+    });
+    result
+}
+
+unsafe extern "C" fn push_new_interrupt_queue<Q: JobQueue>(
+    interrupt_queues: *mut c_void,
+) -> *const c_void {
+    let mut result = ptr::null();
+    wrap_panic(&mut || {
+        let interrupt_queues = &mut *(interrupt_queues as *mut InterruptQueues<Q>);
+        // Any saved queue can spawn the new one; use the innermost so queues
+        // can carry state forward if they want to.
+        let current = interrupt_queues
+            .saved
+            .last()
+            .map_or(interrupt_queues.root, |queue| &**queue as *const Q);
+        let queue = Box::new((*current).new_interrupt_queue());
+        result = &*queue as *const Q as *const c_void;
+        interrupt_queues.saved.push(queue);
+    });
+    result
+}
+
+unsafe extern "C" fn pop_interrupt_queue<Q: JobQueue>(
+    interrupt_queues: *mut c_void,
+) -> *const c_void {
+    let mut result = ptr::null();
+    wrap_panic(&mut || {
+        let interrupt_queues = &mut *(interrupt_queues as *mut InterruptQueues<Q>);
+        if let Some(queue) = interrupt_queues.saved.pop() {
+            result = &*queue as *const Q as *const c_void;
+        }
+    });
+    result
+}
+
+unsafe extern "C" fn drop_interrupt_queues<Q: JobQueue>(interrupt_queues: *mut c_void) {
+    wrap_panic(&mut || {
+        let _ = Box::from_raw(interrupt_queues as *mut InterruptQueues<Q>);
+    });
+}
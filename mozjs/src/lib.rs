@@ -47,14 +47,26 @@ pub mod jsapi {
 #[macro_use]
 pub mod rust;
 
+pub mod class;
 mod consts;
 pub mod context;
 pub mod conversions;
 pub mod error;
+pub mod function;
 pub mod gc;
+pub mod jobs;
+pub mod memory;
+pub mod modules;
 pub mod panic;
+pub mod promise;
+pub mod proxy;
 pub mod realm;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod stencil_cache;
+pub mod structured_clone;
 pub mod typedarray;
+pub mod watchdog;
 
 pub use crate::consts::*;
 pub use mozjs_sys::glue;
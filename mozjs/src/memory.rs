@@ -0,0 +1,221 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Memory limits and reports.
+//!
+//! The GC heap of a runtime is capped by [`RuntimeBuilder::max_heap_bytes`]
+//! and [`Runtime::set_max_heap_bytes`]. Once an allocation would exceed the
+//! cap, even after a last-ditch GC, the engine throws an out-of-memory
+//! exception. [`Exception::is_out_of_memory`] recognises it, and
+//! [`ExecutionError`] reports it as [`ExecutionError::OutOfMemory`], along
+//! with the name of the realm that was running, as recorded by the
+//! out-of-memory callback every runtime installs.
+//!
+//! The runtime stays usable afterwards: the exception is cleared when it is
+//! taken, and the memory of the script is reclaimed by the next GC once the
+//! script's objects are unreachable.
+//!
+//! [`Runtime::memory_report`] measures where the memory of a runtime goes,
+//! including the usage of each realm, under the name given to it with
+//! [`set_realm_name`]. With the `serde` feature, the report can be
+//! serialized, for example to JSON.
+//!
+//! [`RuntimeBuilder::max_heap_bytes`]: crate::rust::RuntimeBuilder::max_heap_bytes
+//! [`Runtime::set_max_heap_bytes`]: crate::rust::Runtime::set_max_heap_bytes
+//! [`Exception::is_out_of_memory`]: crate::error::Exception::is_out_of_memory
+//! [`ExecutionError`]: crate::error::ExecutionError
+//! [`ExecutionError::OutOfMemory`]: crate::error::ExecutionError::OutOfMemory
+//! [`Runtime::memory_report`]: crate::rust::Runtime::memory_report
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::{mem, ptr};
+
+use crate::context;
+use crate::glue::MemoryReportSizes;
+use crate::jsapi::{DestroyRealmCallback, OutOfMemoryCallback};
+use crate::jsapi::{GCContext, GetCurrentRealmOrNull, JSContext, Realm, ServoSizes};
+use crate::jsapi::{SetDestroyRealmCallback, SetOutOfMemoryCallback};
+use crate::rust::wrappers2::CollectMemoryReport;
+
+thread_local!(static OUT_OF_MEMORY: RefCell<Option<OutOfMemory>> = RefCell::new(None));
+thread_local!(static REALM_NAMES: RefCell<HashMap<*mut Realm, String>> = RefCell::new(HashMap::new()));
+/// The callbacks given to the `RuntimeBuilder` of the runtime on this thread,
+/// which are called after ours.
+thread_local!(static CHAINED_OUT_OF_MEMORY: Cell<(OutOfMemoryCallback, *mut c_void)> = const { Cell::new((None, ptr::null_mut())) });
+thread_local!(static CHAINED_DESTROY_REALM: Cell<DestroyRealmCallback> = const { Cell::new(None) });
+
+/// Install the out-of-memory and destroy-realm callbacks on `cx`, calling
+/// `out_of_memory` and `destroy_realm` after them.
+pub(crate) unsafe fn install_callbacks(
+    cx: *mut JSContext,
+    out_of_memory: (OutOfMemoryCallback, *mut c_void),
+    destroy_realm: DestroyRealmCallback,
+) {
+    CHAINED_OUT_OF_MEMORY.with(|callback| callback.set(out_of_memory));
+    CHAINED_DESTROY_REALM.with(|callback| callback.set(destroy_realm));
+    SetOutOfMemoryCallback(cx, Some(out_of_memory_callback), ptr::null_mut());
+    SetDestroyRealmCallback(cx, Some(destroy_realm_callback));
+}
+
+/// The out-of-memory callback installed on every context, which records the
+/// name of the realm that was running. Realms can be collected and their
+/// addresses reused before the exception is looked at, so the name is
+/// copied now.
+unsafe extern "C" fn out_of_memory_callback(cx: *mut JSContext, _data: *mut c_void) {
+    let realm = GetCurrentRealmOrNull(cx);
+    let realm_name = REALM_NAMES.with(|names| names.borrow().get(&realm).cloned());
+    OUT_OF_MEMORY
+        .with(|out_of_memory| *out_of_memory.borrow_mut() = Some(OutOfMemory { realm_name }));
+    if let (Some(callback), data) = CHAINED_OUT_OF_MEMORY.with(Cell::get) {
+        callback(cx, data);
+    }
+}
+
+/// Take what the last call of the out-of-memory callback recorded.
+pub(crate) fn take_out_of_memory() -> OutOfMemory {
+    OUT_OF_MEMORY
+        .with(|out_of_memory| out_of_memory.borrow_mut().take())
+        .unwrap_or_default()
+}
+
+/// Where the engine ran out of memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutOfMemory {
+    realm_name: Option<String>,
+}
+
+impl OutOfMemory {
+    /// The name given with [`set_realm_name`] to the realm that was entered
+    /// when the engine ran out of memory, or `None` if there was no realm or
+    /// it was not named.
+    pub fn realm_name(&self) -> Option<&str> {
+        self.realm_name.as_deref()
+    }
+}
+
+/// Name `realm` in memory reports, replacing the name given to it before.
+/// The name is forgotten when the realm is destroyed.
+pub fn set_realm_name(realm: *mut Realm, name: impl Into<String>) {
+    REALM_NAMES.with(|names| names.borrow_mut().insert(realm, name.into()));
+}
+
+/// The destroy-realm callback installed on every context, which forgets the
+/// name of the realm.
+unsafe extern "C" fn destroy_realm_callback(gcx: *mut GCContext, realm: *mut Realm) {
+    REALM_NAMES.with(|names| names.borrow_mut().remove(&realm));
+    if let Some(callback) = CHAINED_DESTROY_REALM.with(Cell::get) {
+        callback(gcx, realm);
+    }
+}
+
+/// Sizes in bytes, by where the memory lives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemorySizes {
+    /// Live things in the GC heap.
+    pub gc_heap_used: usize,
+    /// Free space in the GC heap.
+    pub gc_heap_unused: usize,
+    /// Bookkeeping of the GC heap.
+    pub gc_heap_admin: usize,
+    /// GC heap pages returned to the operating system.
+    pub gc_heap_decommitted: usize,
+    /// Memory allocated with `malloc`.
+    pub malloc_heap: usize,
+    /// Memory mapped directly, such as JIT code.
+    pub non_heap: usize,
+}
+
+impl MemorySizes {
+    fn from_servo(sizes: &ServoSizes) -> MemorySizes {
+        MemorySizes {
+            gc_heap_used: sizes.gcHeapUsed,
+            gc_heap_unused: sizes.gcHeapUnused,
+            gc_heap_admin: sizes.gcHeapAdmin,
+            gc_heap_decommitted: sizes.gcHeapDecommitted,
+            malloc_heap: sizes.mallocHeap,
+            non_heap: sizes.nonHeap,
+        }
+    }
+
+    /// The size of the GC heap, without decommitted pages.
+    pub fn gc_heap(&self) -> usize {
+        self.gc_heap_used + self.gc_heap_unused + self.gc_heap_admin
+    }
+
+    /// The memory in use, without decommitted pages.
+    pub fn total(&self) -> usize {
+        self.gc_heap() + self.malloc_heap + self.non_heap
+    }
+}
+
+/// The memory of a runtime, returned by
+/// [`Runtime::memory_report`](crate::rust::Runtime::memory_report).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryReport {
+    /// All the memory of the runtime.
+    pub total: MemorySizes,
+    /// Objects, including their slots and elements.
+    pub objects: MemorySizes,
+    /// Strings.
+    pub strings: MemorySizes,
+    /// Shapes, which describe the layout of objects.
+    pub shapes: MemorySizes,
+    /// Code generated by the JITs.
+    pub jit_code: MemorySizes,
+    /// The memory of each realm, other than memory shared by the realms of
+    /// a zone, such as strings and JIT code.
+    pub realms: Vec<RealmMemory>,
+}
+
+/// The memory of a realm.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RealmMemory {
+    /// The realm, which may be destroyed after the report was made.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub realm: *mut Realm,
+    /// The name given with [`set_realm_name`], if any.
+    pub name: Option<String>,
+    /// The memory used by the realm.
+    pub sizes: MemorySizes,
+}
+
+unsafe extern "C" fn push_realm(data: *mut c_void, realm: *mut Realm, sizes: *const ServoSizes) {
+    let realms = &mut *(data as *mut Vec<RealmMemory>);
+    realms.push(RealmMemory {
+        realm,
+        name: REALM_NAMES.with(|names| names.borrow().get(&realm).cloned()),
+        sizes: MemorySizes::from_servo(&*sizes),
+    });
+}
+
+/// Measure the memory of the runtime of `cx`, as
+/// [`Runtime::memory_report`] does, from code that only has a context.
+/// Returns `None` if the engine ran out of memory while measuring.
+pub fn memory_report(cx: &mut context::JSContext) -> Option<MemoryReport> {
+    let mut realms = Vec::new();
+    unsafe {
+        let mut sizes: MemoryReportSizes = mem::zeroed();
+        if !CollectMemoryReport(
+            cx,
+            &mut sizes,
+            &mut realms as *mut Vec<RealmMemory> as *mut c_void,
+            Some(push_realm),
+        ) {
+            return None;
+        }
+        Some(MemoryReport {
+            total: MemorySizes::from_servo(&sizes.total),
+            objects: MemorySizes::from_servo(&sizes.objects),
+            strings: MemorySizes::from_servo(&sizes.strings),
+            shapes: MemorySizes::from_servo(&sizes.shapes),
+            jit_code: MemorySizes::from_servo(&sizes.jitCode),
+            realms,
+        })
+    }
+}
@@ -0,0 +1,811 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! ES module loading.
+//!
+//! SpiderMonkey leaves finding and compiling imported modules to the
+//! embedding through the `HostResolveImportedModule` hook. This module
+//! implements that hook on top of a [`ModuleLoader`], which turns import
+//! specifiers into URLs and URLs into source text, and keeps a module map so
+//! that every URL is compiled once per realm.
+//!
+//! The module map of a realm is held by a reserved slot of its global, so it
+//! is collected along with the global. The slot is chosen by
+//! [`ModuleLoader::module_map_slot`], by default the last of the
+//! `JSCLASS_GLOBAL_APPLICATION_SLOTS`; embedders importing modules must leave
+//! that slot alone.
+//!
+//! Dynamic `import()` is supported as well: the module is loaded from a
+//! Promise job, so the installed [job queue](crate::jobs) has to be drained
+//! for the import to settle. `import.meta.url` is the URL of the module.
+//!
+//! Besides JavaScript, modules can be JSON documents, imported with
+//! `import data from "./data.json" with { type: "json" }`. The loader decides
+//! the type of each URL and imports asking for another type fail with a
+//! `TypeError`. Embedders can also register [`SyntheticModule`]s, whose exports
+//! are provided from Rust instead of being parsed from source text. These are
+//! JavaScript modules: SpiderMonkey rejects `type` attributes other than
+//! `"json"` itself, so embedders cannot add module types of their own.
+//!
+//! ```ignore
+//! runtime.set_module_loader(FilesystemLoader::new("scripts")?);
+//! let evaluation = evaluate_module(runtime.cx(), global.handle(), "main.js")?;
+//! block_on(runtime.cx(), evaluation)?;
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+
+use crate::context::JSContext;
+use crate::conversions::{jsstr_to_string, ToJSValConvertible};
+use crate::error::{throw_type_error, Exception};
+use crate::glue::JS_GetReservedSlot;
+use crate::jsapi;
+use crate::jsapi::{CallArgs, JSNative, JSObject, JSRuntime, ModuleErrorBehaviour};
+use crate::jsapi::{GetFunctionNativeReserved, SetFunctionNativeReserved};
+use crate::jsapi::{JS_GetFunctionObject, JSPROP_ENUMERATE};
+use crate::jsapi::{JS_SetReservedSlot, ModuleType, Value, JSCLASS_GLOBAL_APPLICATION_SLOTS};
+use crate::jsval::{ObjectValue, UndefinedValue};
+use crate::panic::{maybe_resume_unwind, wrap_panic};
+use crate::promise::JsPromise;
+use crate::realm::AutoRealm;
+use crate::rooted;
+use crate::rust::wrappers2::{
+    AddPromiseReactions, CallOriginalPromiseReject, CallOriginalPromiseResolve, CompileJsonModule1,
+    CompileModule1, CurrentGlobalOrNull, FinishDynamicModuleImport, GetModuleRequestSpecifier,
+    GetModuleRequestType, JS_ClearPendingException, JS_DefineFunction, JS_DefineProperty,
+    JS_GetPendingException, JS_NewPlainObject, MapGet, MapSet, ModuleEvaluate, ModuleLink,
+    NewFunctionWithReserved, NewMapObject, ThrowOnModuleEvaluationFailure,
+};
+use crate::rust::{
+    transform_str_to_source_text, CompileOptionsWrapper, Handle, HandleObject, HandleValue,
+};
+
+/// Finds the source of the modules imported by scripts.
+///
+/// URLs are opaque to the module host: they identify modules in the module
+/// map, are passed back as the referrer of nested imports, and name the
+/// module in error messages and stacks.
+pub trait ModuleLoader: 'static {
+    /// Resolve `specifier` to the URL of a module. `referrer` is the URL of
+    /// the importing module, or `None` for the entry point passed to
+    /// [`evaluate_module`]. Errors are thrown as a `TypeError`.
+    fn resolve(&self, specifier: &str, referrer: Option<&str>) -> Result<String, String>;
+
+    /// Fetch the source text of the module at `url`. Errors are thrown as a
+    /// `TypeError`.
+    fn fetch(&self, url: &str) -> Result<String, String>;
+
+    /// The type of the module at `url`, which imports have to ask for. The
+    /// default treats URLs ending in `.json` as JSON modules and everything
+    /// else as JavaScript.
+    fn module_type(&self, url: &str) -> ModuleType {
+        if url.ends_with(".json") {
+            ModuleType::JSON
+        } else {
+            ModuleType::JavaScript
+        }
+    }
+
+    /// The reserved slot of the globals importing modules that holds their
+    /// module map. It must be a reserved slot of the class of every such
+    /// global, which the embedder does not otherwise use. The default is the
+    /// last of the `JSCLASS_GLOBAL_APPLICATION_SLOTS`.
+    fn module_map_slot(&self) -> u32 {
+        JSCLASS_GLOBAL_APPLICATION_SLOTS - 1
+    }
+
+    /// Add host properties to the `import.meta` object of the module at `url`.
+    /// `import.meta.url` has already been defined. Returns `false` with an
+    /// exception pending on failure.
+    fn import_meta(&self, _cx: &mut JSContext, _url: &str, _meta: HandleObject) -> bool {
+        true
+    }
+}
+
+/// A module whose exports are provided by the embedding, such as functions
+/// implemented in Rust.
+///
+/// Synthetic modules are registered under a specifier with
+/// [`Runtime::register_synthetic_module`](crate::rust::Runtime::register_synthetic_module).
+/// Importing exactly that specifier loads the module without consulting the
+/// [`ModuleLoader`]; its URL is the specifier itself. The exports are
+/// defined once per realm, when the module is evaluated.
+///
+/// Synthetic modules have the JavaScript module type, so importing one
+/// `with { type: "json" }` throws a `TypeError`. There is no way to give them
+/// a type of their own: SpiderMonkey throws for `type` attributes other than
+/// `"json"` before the module host is asked for the module.
+pub trait SyntheticModule: 'static {
+    /// The names of the exports; `"default"` is the default export.
+    fn export_names(&self) -> Vec<String>;
+
+    /// Define every export as a property of `exports`. Returns `false` with an
+    /// exception pending on failure.
+    fn define_exports(&self, cx: &mut JSContext, exports: HandleObject) -> bool;
+}
+
+/// A [`SyntheticModule`] exporting native functions and Rust values.
+///
+/// ```ignore
+/// let module = NativeModule::new()
+///     .function("add", Some(add), 2)
+///     .value("version", 3);
+/// runtime.register_synthetic_module("native:math", module);
+/// ```
+#[derive(Default)]
+pub struct NativeModule {
+    functions: Vec<(CString, JSNative, u32)>,
+    values: Vec<(CString, Box<dyn ToJSValConvertible>)>,
+}
+
+impl NativeModule {
+    /// Create a module without exports.
+    pub fn new() -> NativeModule {
+        NativeModule::default()
+    }
+
+    /// Export `native` as a function named `name` taking `nargs` arguments.
+    ///
+    /// # Panics
+    /// If `name` contains a NUL character.
+    pub fn function(mut self, name: &str, native: JSNative, nargs: u32) -> NativeModule {
+        self.functions.push((export_name(name), native, nargs));
+        self
+    }
+
+    /// Export `value` under `name`. Every realm importing the module gets its
+    /// own conversion of the value.
+    ///
+    /// # Panics
+    /// If `name` contains a NUL character.
+    pub fn value<T: ToJSValConvertible + 'static>(mut self, name: &str, value: T) -> NativeModule {
+        self.values.push((export_name(name), Box::new(value)));
+        self
+    }
+}
+
+fn export_name(name: &str) -> CString {
+    CString::new(name).expect("Export names cannot contain NUL characters")
+}
+
+impl SyntheticModule for NativeModule {
+    fn export_names(&self) -> Vec<String> {
+        let functions = self.functions.iter().map(|(name, ..)| name);
+        let values = self.values.iter().map(|(name, _)| name);
+        functions
+            .chain(values)
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn define_exports(&self, cx: &mut JSContext, exports: HandleObject) -> bool {
+        unsafe {
+            for (name, native, nargs) in &self.functions {
+                let function = JS_DefineFunction(
+                    cx,
+                    exports,
+                    name.as_ptr(),
+                    *native,
+                    *nargs,
+                    JSPROP_ENUMERATE as u32,
+                );
+                if function.is_null() {
+                    return false;
+                }
+            }
+            for (name, value) in &self.values {
+                rooted!(&in(cx) let mut js_value = UndefinedValue());
+                value.to_jsval(cx.raw_cx(), js_value.handle_mut());
+                if !JS_DefineProperty(
+                    cx,
+                    exports,
+                    name.as_ptr(),
+                    js_value.handle(),
+                    JSPROP_ENUMERATE as u32,
+                ) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Loads modules from the files under a root directory.
+///
+/// Modules are identified by `file://` URLs of their canonical paths.
+/// Relative specifiers (`./x.js`, `../x.js`) resolve against the importing
+/// module and absolute ones (`/x.js`) against the root. Bare specifiers are
+/// only accepted for the entry point, and nothing outside the root can be
+/// loaded.
+pub struct FilesystemLoader {
+    root: PathBuf,
+}
+
+impl FilesystemLoader {
+    /// Create a loader serving the files under `root`.
+    pub fn new(root: impl AsRef<Path>) -> io::Result<FilesystemLoader> {
+        Ok(FilesystemLoader {
+            root: root.as_ref().canonicalize()?,
+        })
+    }
+
+    fn url_to_path(url: &str) -> Result<&Path, String> {
+        url.strip_prefix("file://")
+            .map(Path::new)
+            .ok_or_else(|| format!("{} is not a file:// URL", url))
+    }
+}
+
+impl ModuleLoader for FilesystemLoader {
+    fn resolve(&self, specifier: &str, referrer: Option<&str>) -> Result<String, String> {
+        let path = Path::new(specifier);
+        let path = if let Some(relative) = specifier.strip_prefix('/') {
+            self.root.join(relative)
+        } else if matches!(
+            path.components().next(),
+            Some(Component::CurDir | Component::ParentDir)
+        ) || referrer.is_none()
+        {
+            let base = match referrer {
+                Some(referrer) => Self::url_to_path(referrer)?
+                    .parent()
+                    .unwrap_or(&self.root)
+                    .to_owned(),
+                None => self.root.clone(),
+            };
+            base.join(path)
+        } else {
+            return Err(format!(
+                "Bare specifier {:?} must start with \"./\", \"../\" or \"/\"",
+                specifier
+            ));
+        };
+        let path = path
+            .canonicalize()
+            .map_err(|error| format!("Cannot resolve {:?}: {}", specifier, error))?;
+        if !path.starts_with(&self.root) {
+            return Err(format!("{:?} is outside the module root", specifier));
+        }
+        Ok(format!("file://{}", path.display()))
+    }
+
+    fn fetch(&self, url: &str) -> Result<String, String> {
+        let path = Self::url_to_path(url)?;
+        fs::read_to_string(path).map_err(|error| format!("Cannot load {}: {}", url, error))
+    }
+}
+
+/// The module loader and synthetic modules of the runtime on this thread.
+pub(crate) struct ModuleHost {
+    loader: Box<dyn ModuleLoader>,
+    /// The reserved slot of a global that holds the `Map` from URLs to the
+    /// modules compiled in its realm.
+    map_slot: u32,
+    /// Synthetic modules, by specifier.
+    synthetic: RefCell<HashMap<String, Rc<dyn SyntheticModule>>>,
+}
+
+thread_local!(static MODULE_HOST: RefCell<Option<Rc<ModuleHost>>> = const { RefCell::new(None) });
+
+fn module_host() -> Option<Rc<ModuleHost>> {
+    MODULE_HOST.with(|host| host.borrow().clone())
+}
+
+/// Install `loader` and the module hooks on the runtime of this thread.
+/// Returns the module host, which the runtime keeps to register synthetic
+/// modules with.
+pub(crate) fn install<L: ModuleLoader>(rt: *mut JSRuntime, loader: L) -> Rc<ModuleHost> {
+    let host = Rc::new(ModuleHost {
+        map_slot: loader.module_map_slot(),
+        loader: Box::new(loader),
+        synthetic: RefCell::new(HashMap::new()),
+    });
+    MODULE_HOST.with(|slot| *slot.borrow_mut() = Some(host.clone()));
+    unsafe {
+        jsapi::SetModuleResolveHook(rt, Some(resolve_hook));
+        jsapi::SetModuleDynamicImportHook(rt, Some(dynamic_import_hook));
+        jsapi::SetModuleMetadataHook(rt, Some(metadata_hook));
+    }
+    host
+}
+
+/// Drop the module host of this thread; the runtime is about to be destroyed.
+pub(crate) fn uninstall() {
+    let host = MODULE_HOST.with(|slot| slot.borrow_mut().take());
+    drop(host);
+}
+
+fn throw_error_message(cx: &mut JSContext, message: &str) {
+    let message = CString::new(message.replace('\0', "\\0")).unwrap();
+    unsafe { throw_type_error(cx.raw_cx(), &message) };
+}
+
+/// The module map of the current realm, held in the reserved slot `slot` of
+/// its global and created on first use. Returns null with an exception
+/// pending on failure.
+fn module_map(cx: &mut JSContext, slot: u32) -> *mut JSObject {
+    unsafe {
+        let global = CurrentGlobalOrNull(cx);
+        assert!(!global.is_null(), "Modules can only be loaded in a realm");
+        let mut map = UndefinedValue();
+        JS_GetReservedSlot(global, slot, &mut map);
+        if map.is_object() {
+            return map.to_object();
+        }
+        rooted!(&in(cx) let global = global);
+        let map = NewMapObject(cx);
+        if !map.is_null() {
+            JS_SetReservedSlot(global.get(), slot, &ObjectValue(map));
+        }
+        map
+    }
+}
+
+fn module_type_name(module_type: ModuleType) -> &'static str {
+    match module_type {
+        ModuleType::JavaScript => "javascript",
+        ModuleType::JSON => "json",
+        _ => "an unsupported type",
+    }
+}
+
+/// `string` quoted as a JavaScript string literal.
+fn string_literal(string: &str) -> String {
+    let mut literal = String::with_capacity(string.len() + 2);
+    literal.push('"');
+    for c in string.chars() {
+        match c {
+            '"' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            c if c.is_control() || c == '\u{2028}' || c == '\u{2029}' => {
+                literal.push_str(&format!("\\u{{{:x}}}", c as u32));
+            }
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// The source text of a synthetic module, which re-exports the properties
+/// that `populate_import_meta` defines on `import.meta.exports`.
+fn synthetic_module_source(names: &[String]) -> String {
+    let mut source = String::from("const exports = import.meta.exports;\n");
+    for (index, name) in names.iter().enumerate() {
+        let name = string_literal(name);
+        source.push_str(&format!(
+            "const export{index} = exports[{name}];\nexport {{ export{index} as {name} }};\n"
+        ));
+    }
+    source
+}
+
+impl ModuleHost {
+    /// Make `module` importable as `specifier`.
+    pub(crate) fn register_synthetic<M: SyntheticModule>(&self, specifier: &str, module: M) {
+        self.synthetic
+            .borrow_mut()
+            .insert(specifier.to_owned(), Rc::new(module));
+    }
+
+    /// Resolve `specifier`, imported by the module at `referrer`, to a URL.
+    fn resolve(&self, specifier: &str, referrer: Option<&str>) -> Result<String, String> {
+        if self.synthetic.borrow().contains_key(specifier) {
+            return Ok(specifier.to_owned());
+        }
+        self.loader.resolve(specifier, referrer)
+    }
+
+    /// Returns the module at `url` in the current realm, compiling it on first
+    /// use. Throws a `TypeError` if the module does not have type `requested`.
+    /// Returns null with an exception pending on failure.
+    fn fetch_module(&self, cx: &mut JSContext, url: &str, requested: ModuleType) -> *mut JSObject {
+        let synthetic = self.synthetic.borrow().get(url).cloned();
+        let module_type = match synthetic {
+            Some(_) => ModuleType::JavaScript,
+            None => self.loader.module_type(url),
+        };
+        if module_type != requested {
+            throw_error_message(
+                cx,
+                &format!(
+                    "Module {} has type {} but was imported as {}",
+                    url,
+                    module_type_name(module_type),
+                    module_type_name(requested)
+                ),
+            );
+            return ptr::null_mut();
+        }
+
+        rooted!(&in(cx) let map = module_map(cx, self.map_slot));
+        if map.get().is_null() {
+            return ptr::null_mut();
+        }
+        rooted!(&in(cx) let mut key = UndefinedValue());
+        rooted!(&in(cx) let mut cached = UndefinedValue());
+        unsafe {
+            url.to_jsval(cx.raw_cx(), key.handle_mut());
+            if !MapGet(cx, map.handle(), key.handle(), cached.handle_mut()) {
+                return ptr::null_mut();
+            }
+        }
+        if cached.get().is_object() {
+            return cached.get().to_object();
+        }
+
+        let source = match synthetic {
+            Some(synthetic) => Ok(synthetic_module_source(&synthetic.export_names())),
+            None => self.loader.fetch(url),
+        };
+        let source = match source {
+            Ok(source) => source,
+            Err(message) => {
+                throw_error_message(cx, &message);
+                return ptr::null_mut();
+            }
+        };
+        let Ok(filename) = CString::new(url) else {
+            throw_error_message(cx, "Module URLs cannot contain NUL characters");
+            return ptr::null_mut();
+        };
+        let options = CompileOptionsWrapper::new(cx, filename, 1);
+        let mut source = transform_str_to_source_text(&source);
+        rooted!(&in(cx) let module = unsafe {
+            match module_type {
+                ModuleType::JSON => CompileJsonModule1(cx, options.ptr, &mut source),
+                _ => CompileModule1(cx, options.ptr, &mut source),
+            }
+        });
+        if module.get().is_null() {
+            return ptr::null_mut();
+        }
+
+        // The URL is both the key in the module map and the private value.
+        rooted!(&in(cx) let value = ObjectValue(module.get()));
+        unsafe {
+            jsapi::SetModulePrivate(module.get(), &*key);
+            if !MapSet(cx, map.handle(), key.handle(), value.handle()) {
+                return ptr::null_mut();
+            }
+        }
+        module.get()
+    }
+}
+
+/// The URL of the module with private value `private`, or `None` for classic
+/// scripts.
+fn private_to_url(cx: &mut JSContext, private: HandleValue) -> Option<String> {
+    if !private.get().is_string() {
+        return None;
+    }
+    let string = NonNull::new(private.get().to_string())?;
+    Some(unsafe { jsstr_to_string(cx.raw_cx(), string) })
+}
+
+/// The specifier of `request`, or `None` with an exception pending.
+fn request_specifier(cx: &mut JSContext, request: HandleObject) -> Option<String> {
+    let specifier = unsafe { GetModuleRequestSpecifier(cx, request) };
+    let specifier = NonNull::new(specifier)?;
+    Some(unsafe { jsstr_to_string(cx.raw_cx(), specifier) })
+}
+
+fn resolve_imported_module(
+    cx: &mut JSContext,
+    referencing_private: HandleValue,
+    module_request: HandleObject,
+) -> *mut JSObject {
+    let Some(host) = module_host() else {
+        throw_error_message(cx, "No module loader is installed");
+        return ptr::null_mut();
+    };
+    let referrer = private_to_url(cx, referencing_private);
+    let Some(specifier) = request_specifier(cx, module_request) else {
+        return ptr::null_mut();
+    };
+    let requested = unsafe { GetModuleRequestType(cx, module_request) };
+    match host.resolve(&specifier, referrer.as_deref()) {
+        Ok(url) => host.fetch_module(cx, &url, requested),
+        Err(message) => {
+            throw_error_message(cx, &message);
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe extern "C" fn resolve_hook(
+    cx: *mut jsapi::JSContext,
+    referencing_private: jsapi::HandleValue,
+    module_request: jsapi::HandleObject,
+) -> *mut JSObject {
+    let mut result = ptr::null_mut();
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        result = resolve_imported_module(
+            &mut cx,
+            Handle::from_raw(referencing_private),
+            Handle::from_raw(module_request),
+        );
+    });
+    result
+}
+
+/// Reserved slots of the job that finishes a dynamic import.
+const DYNAMIC_IMPORT_PRIVATE_SLOT: usize = 0;
+const DYNAMIC_IMPORT_REQUEST_SLOT: usize = 1;
+const DYNAMIC_IMPORT_PROMISE_SLOT: usize = 2;
+
+/// Start loading the module requested by an `import()` expression. The
+/// module is loaded by a job that runs once the job queue is drained.
+fn start_dynamic_import(
+    cx: &mut JSContext,
+    referencing_private: HandleValue,
+    module_request: HandleObject,
+    promise: HandleObject,
+) -> bool {
+    unsafe {
+        let job = NewFunctionWithReserved(
+            cx,
+            Some(dynamic_import_job),
+            0,
+            0,
+            c"dynamicImport".as_ptr(),
+        );
+        if job.is_null() {
+            return false;
+        }
+        rooted!(&in(cx) let job = JS_GetFunctionObject(job));
+        SetFunctionNativeReserved(
+            job.get(),
+            DYNAMIC_IMPORT_PRIVATE_SLOT,
+            &referencing_private.get(),
+        );
+        SetFunctionNativeReserved(
+            job.get(),
+            DYNAMIC_IMPORT_REQUEST_SLOT,
+            &ObjectValue(module_request.get()),
+        );
+        SetFunctionNativeReserved(
+            job.get(),
+            DYNAMIC_IMPORT_PROMISE_SLOT,
+            &ObjectValue(promise.get()),
+        );
+
+        rooted!(&in(cx) let resolved = CallOriginalPromiseResolve(cx, HandleValue::undefined()));
+        if resolved.get().is_null() {
+            return false;
+        }
+        AddPromiseReactions(cx, resolved.handle(), job.handle(), HandleObject::null())
+    }
+}
+
+/// Load, link and evaluate the module requested by an `import()` expression,
+/// and settle `promise` with its namespace or the error.
+fn finish_dynamic_import(
+    cx: &mut JSContext,
+    referencing_private: HandleValue,
+    module_request: HandleObject,
+    promise: HandleObject,
+) -> bool {
+    unsafe {
+        rooted!(&in(cx) let module = resolve_imported_module(cx, referencing_private, module_request));
+        rooted!(&in(cx) let mut evaluation = UndefinedValue());
+        let evaluated = !module.get().is_null()
+            && ModuleLink(cx, module.handle())
+            && ModuleEvaluate(cx, module.handle(), evaluation.handle_mut());
+
+        let evaluation_promise = if evaluated && evaluation.get().is_object() {
+            evaluation.get().to_object()
+        } else if evaluated {
+            CallOriginalPromiseResolve(cx, HandleValue::undefined())
+        } else {
+            // FinishDynamicModuleImport rejects the import() promise with the
+            // reason of a rejected evaluation promise.
+            rooted!(&in(cx) let mut error = UndefinedValue());
+            if !JS_GetPendingException(cx, error.handle_mut()) {
+                return false;
+            }
+            JS_ClearPendingException(cx);
+            CallOriginalPromiseReject(cx, error.handle())
+        };
+        rooted!(&in(cx) let evaluation_promise = evaluation_promise);
+        if evaluation_promise.get().is_null() {
+            return false;
+        }
+
+        FinishDynamicModuleImport(
+            cx,
+            evaluation_promise.handle(),
+            referencing_private,
+            module_request,
+            promise,
+        )
+    }
+}
+
+unsafe extern "C" fn dynamic_import_hook(
+    cx: *mut jsapi::JSContext,
+    referencing_private: jsapi::HandleValue,
+    module_request: jsapi::HandleObject,
+    promise: jsapi::HandleObject,
+) -> bool {
+    let mut result = false;
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        result = start_dynamic_import(
+            &mut cx,
+            Handle::from_raw(referencing_private),
+            Handle::from_raw(module_request),
+            Handle::from_raw(promise),
+        );
+    });
+    result
+}
+
+unsafe extern "C" fn dynamic_import_job(
+    cx: *mut jsapi::JSContext,
+    argc: u32,
+    vp: *mut Value,
+) -> bool {
+    let mut result = false;
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        let args = CallArgs::from_vp(vp, argc);
+        let callee = args.callee();
+        rooted!(&in(cx) let private = *GetFunctionNativeReserved(callee, DYNAMIC_IMPORT_PRIVATE_SLOT));
+        rooted!(&in(cx) let request =
+            (*GetFunctionNativeReserved(callee, DYNAMIC_IMPORT_REQUEST_SLOT)).to_object());
+        rooted!(&in(cx) let promise =
+            (*GetFunctionNativeReserved(callee, DYNAMIC_IMPORT_PROMISE_SLOT)).to_object());
+        args.rval().set(UndefinedValue());
+        result = finish_dynamic_import(
+            &mut cx,
+            private.handle(),
+            request.handle(),
+            promise.handle(),
+        );
+    });
+    result
+}
+
+/// Fill in the `import.meta` object of the module with private value `private`.
+fn populate_import_meta(cx: &mut JSContext, private: HandleValue, meta: HandleObject) -> bool {
+    let Some(url) = private_to_url(cx, private) else {
+        return true;
+    };
+    rooted!(&in(cx) let mut value = UndefinedValue());
+    unsafe {
+        url.to_jsval(cx.raw_cx(), value.handle_mut());
+        if !JS_DefineProperty(
+            cx,
+            meta,
+            c"url".as_ptr(),
+            value.handle(),
+            JSPROP_ENUMERATE as u32,
+        ) {
+            return false;
+        }
+    }
+    let Some(host) = module_host() else {
+        return true;
+    };
+    let synthetic = host.synthetic.borrow().get(&url).cloned();
+    match synthetic {
+        Some(synthetic) => define_synthetic_exports(cx, &*synthetic, meta),
+        None => host.loader.import_meta(cx, &url, meta),
+    }
+}
+
+/// Define `import.meta.exports` for the generated source of `module`.
+fn define_synthetic_exports(
+    cx: &mut JSContext,
+    module: &dyn SyntheticModule,
+    meta: HandleObject,
+) -> bool {
+    rooted!(&in(cx) let exports = unsafe { JS_NewPlainObject(cx) });
+    if exports.get().is_null() || !module.define_exports(cx, exports.handle()) {
+        return false;
+    }
+    rooted!(&in(cx) let value = ObjectValue(exports.get()));
+    unsafe {
+        JS_DefineProperty(
+            cx,
+            meta,
+            c"exports".as_ptr(),
+            value.handle(),
+            JSPROP_ENUMERATE as u32,
+        )
+    }
+}
+
+unsafe extern "C" fn metadata_hook(
+    cx: *mut jsapi::JSContext,
+    private: jsapi::HandleValue,
+    meta: jsapi::HandleObject,
+) -> bool {
+    let mut result = false;
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        result = populate_import_meta(&mut cx, Handle::from_raw(private), Handle::from_raw(meta));
+    });
+    result
+}
+
+/// Load the module graph rooted at `path` in the realm of `global`, link it
+/// and evaluate it, using the loader installed with
+/// [`Runtime::set_module_loader`](crate::rust::Runtime::set_module_loader).
+///
+/// Returns the promise of the evaluation. It is already settled unless a
+/// module uses top-level await, in which case it settles as the
+/// [job queue](crate::jobs) is drained, for example with
+/// [`block_on`](crate::promise::block_on). Errors thrown before the first
+/// await are returned as `Err`; later ones reject the promise.
+pub fn evaluate_module(
+    cx: &mut JSContext,
+    global: HandleObject,
+    path: &str,
+) -> Result<JsPromise, Exception> {
+    let mut realm = AutoRealm::new_from_handle(cx, global);
+    let cx = &mut *realm;
+    load_and_evaluate(cx, path).map_err(|()| unsafe { Exception::steal(cx.raw_cx()) })
+}
+
+/// Returns `Err` with an exception pending on failure.
+fn load_and_evaluate(cx: &mut JSContext, path: &str) -> Result<JsPromise, ()> {
+    let Some(host) = module_host() else {
+        throw_error_message(cx, "No module loader is installed");
+        return Err(());
+    };
+    let module = match host.resolve(path, None) {
+        Ok(url) => host.fetch_module(cx, &url, ModuleType::JavaScript),
+        Err(message) => {
+            throw_error_message(cx, &message);
+            return Err(());
+        }
+    };
+    rooted!(&in(cx) let module = module);
+    if module.get().is_null() {
+        maybe_resume_unwind();
+        return Err(());
+    }
+
+    unsafe {
+        if !ModuleLink(cx, module.handle()) {
+            maybe_resume_unwind();
+            return Err(());
+        }
+
+        rooted!(&in(cx) let mut rval = UndefinedValue());
+        if !ModuleEvaluate(cx, module.handle(), rval.handle_mut()) {
+            maybe_resume_unwind();
+            return Err(());
+        }
+
+        rooted!(&in(cx) let promise = if rval.get().is_object() {
+            rval.get().to_object()
+        } else {
+            CallOriginalPromiseResolve(cx, HandleValue::undefined())
+        });
+        if promise.get().is_null()
+            || !ThrowOnModuleEvaluationFailure(
+                cx,
+                promise.handle(),
+                ModuleErrorBehaviour::ThrowModuleErrorsSync,
+            )
+        {
+            return Err(());
+        }
+        Ok(JsPromise::from_object(promise.handle()).expect("Module evaluation returns a promise"))
+    }
+}
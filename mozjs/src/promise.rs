@@ -0,0 +1,355 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Bridging JavaScript Promises and Rust futures.
+//!
+//! [`JsPromise`] is a rooted Promise object that can be awaited from Rust, and
+//! [`JsPromise::from_future`] turns a Rust future into a Promise that scripts
+//! can await. Both make progress only while the runtime's job queue (see
+//! [`crate::jobs`]) is being drained, which [`run_until_stalled`] and
+//! [`block_on`] take care of.
+//!
+//! ```ignore
+//! // In a JSNative:
+//! let promise = JsPromise::from_future(cx, async { Ok::<_, ()>(fetch_count().await) })?;
+//! args.rval().set(ObjectValue(promise.handle().get()));
+//! ```
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::mem;
+use std::pin::{pin, Pin};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use log::warn;
+
+use crate::context::JSContext;
+use crate::conversions::ToJSValConvertible;
+use crate::error::Exception;
+use crate::jobs::run_jobs;
+use crate::jsapi::{Heap, JSAutoRealm, JSObject, PromiseState};
+use crate::jsval::{JSVal, UndefinedValue};
+use crate::realm::AutoRealm;
+use crate::rooted;
+use crate::rust::wrappers2::{
+    GetPromiseResolutionSite, GetPromiseState, IsPromiseObject, JS_ClearPendingException,
+    JS_GetPromiseResult, NewPromiseObject, RejectPromise, ResolvePromise,
+};
+use crate::rust::{HandleObject, HandleValue, MutableHandle, RootedTraceableBox, Runtime};
+
+/// A JavaScript Promise, rooted for as long as this value lives.
+///
+/// Awaiting a `JsPromise` yields its fulfillment value, or its rejection
+/// reason as an [`Exception`]. The future is woken after the job queue has
+/// been drained with [`run_jobs`] and the promise has settled.
+pub struct JsPromise {
+    promise: RootedTraceableBox<Heap<*mut JSObject>>,
+}
+
+impl JsPromise {
+    /// Create a new pending promise, settled with [`JsPromise::resolve`] or
+    /// [`JsPromise::reject`]. Returns `None` with an exception pending on failure.
+    pub fn new(cx: &mut JSContext) -> Option<JsPromise> {
+        let promise = unsafe { NewPromiseObject(cx, HandleObject::null()) };
+        if promise.is_null() {
+            return None;
+        }
+        Some(JsPromise {
+            promise: RootedTraceableBox::from_box(Heap::boxed(promise)),
+        })
+    }
+
+    /// Wrap `obj` if it is a Promise object.
+    pub fn from_object(obj: HandleObject) -> Option<JsPromise> {
+        if obj.get().is_null() || !unsafe { IsPromiseObject(obj) } {
+            return None;
+        }
+        Some(JsPromise {
+            promise: RootedTraceableBox::from_box(Heap::boxed(obj.get())),
+        })
+    }
+
+    /// Create a promise that settles with the result of `future`.
+    ///
+    /// The future is polled by [`run_until_stalled`] or [`block_on`] on this
+    /// thread; `Ok` values fulfill the promise and `Err` values reject it.
+    pub fn from_future<F, T, E>(cx: &mut JSContext, future: F) -> Option<JsPromise>
+    where
+        F: Future<Output = Result<T, E>> + 'static,
+        T: ToJSValConvertible + 'static,
+        E: ToJSValConvertible + 'static,
+    {
+        let promise = JsPromise::new(cx)?;
+        let future = async move {
+            match future.await {
+                Ok(value) => Ok(Box::new(value) as Box<dyn ToJSValConvertible>),
+                Err(error) => Err(Box::new(error) as Box<dyn ToJSValConvertible>),
+            }
+        };
+        let task = Task {
+            promise: RootedTraceableBox::from_box(Heap::boxed(promise.promise.get())),
+            future: Box::pin(future),
+            waker: Arc::new(ThreadWaker::new(true)),
+        };
+        TASKS.with(|tasks| tasks.borrow_mut().push(task));
+        Some(promise)
+    }
+
+    /// The promise object.
+    pub fn handle(&self) -> HandleObject<'_> {
+        self.promise.handle()
+    }
+
+    /// Whether the promise is pending, fulfilled or rejected.
+    pub fn state(&self) -> PromiseState {
+        unsafe { GetPromiseState(self.handle()) }
+    }
+
+    /// Resolve the promise with `value`. Returns `false` with an exception
+    /// pending on failure.
+    pub fn resolve(&self, cx: &mut JSContext, value: HandleValue) -> bool {
+        let mut realm = AutoRealm::new(cx, self.object());
+        unsafe { ResolvePromise(&mut realm, self.handle(), value) }
+    }
+
+    /// Reject the promise with `reason`. Returns `false` with an exception
+    /// pending on failure.
+    pub fn reject(&self, cx: &mut JSContext, reason: HandleValue) -> bool {
+        let mut realm = AutoRealm::new(cx, self.object());
+        unsafe { RejectPromise(&mut realm, self.handle(), reason) }
+    }
+
+    fn object(&self) -> NonNull<JSObject> {
+        NonNull::new(self.promise.get()).unwrap()
+    }
+
+    /// The fulfillment value or rejection reason of a settled promise.
+    fn result(&self) -> JSVal {
+        let mut result = UndefinedValue();
+        // Nothing can GC between reading the result and rooting it.
+        unsafe {
+            JS_GetPromiseResult(
+                self.handle(),
+                MutableHandle::from_marked_location(&mut result),
+            );
+        }
+        result
+    }
+
+    /// The rejection reason of a rejected promise, described like a thrown
+    /// exception, with the stack the promise was rejected at.
+    fn rejection(&self) -> Exception {
+        let Some(cx) = Runtime::get() else {
+            return Exception::from_value(self.result());
+        };
+        let cx = cx.as_ptr();
+        unsafe {
+            let _realm = JSAutoRealm::new(cx, self.promise.get());
+            rooted!(in(cx) let reason = self.result());
+            rooted!(in(cx) let stack = GetPromiseResolutionSite(self.handle()));
+            Exception::describe(cx, reason.handle(), stack.handle())
+        }
+    }
+}
+
+impl Future for JsPromise {
+    type Output = Result<RootedTraceableBox<Heap<JSVal>>, Exception>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.state() {
+            PromiseState::Fulfilled => {
+                Poll::Ready(Ok(RootedTraceableBox::from_box(Heap::boxed(self.result()))))
+            }
+            PromiseState::Rejected => Poll::Ready(Err(self.rejection())),
+            PromiseState::Pending => {
+                PROMISE_WAKERS.with(|wakers| {
+                    let mut wakers = wakers.borrow_mut();
+                    let promise = self.promise.get();
+                    match wakers.iter_mut().find(|(p, _)| p.get() == promise) {
+                        Some((_, waker)) => waker.clone_from(cx.waker()),
+                        None => wakers.push((
+                            RootedTraceableBox::from_box(Heap::boxed(promise)),
+                            cx.waker().clone(),
+                        )),
+                    }
+                });
+                Poll::Pending
+            }
+        }
+    }
+}
+
+type Settlement = Result<Box<dyn ToJSValConvertible>, Box<dyn ToJSValConvertible>>;
+
+/// A future created by [`JsPromise::from_future`] and the promise it settles.
+struct Task {
+    promise: RootedTraceableBox<Heap<*mut JSObject>>,
+    future: Pin<Box<dyn Future<Output = Settlement>>>,
+    waker: Arc<ThreadWaker>,
+}
+
+/// Records that a task was woken and unparks the runtime's thread, so
+/// futures can be woken from any thread.
+struct ThreadWaker {
+    woken: AtomicBool,
+    thread: Thread,
+}
+
+impl ThreadWaker {
+    fn new(woken: bool) -> ThreadWaker {
+        ThreadWaker {
+            woken: AtomicBool::new(woken),
+            thread: thread::current(),
+        }
+    }
+
+    fn take_woken(&self) -> bool {
+        self.woken.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+        self.thread.unpark();
+    }
+}
+
+thread_local! {
+    static TASKS: RefCell<Vec<Task>> = const { RefCell::new(Vec::new()) };
+    static PROMISE_WAKERS: RefCell<Vec<(RootedTraceableBox<Heap<*mut JSObject>>, Waker)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Wake the futures of [`JsPromise`]s that are no longer pending.
+pub(crate) fn wake_settled_promises() {
+    let settled: Vec<Waker> = PROMISE_WAKERS.with(|wakers| {
+        let mut wakers = wakers.borrow_mut();
+        let (settled, pending) =
+            mem::take(&mut *wakers)
+                .into_iter()
+                .partition::<Vec<_>, _>(|(promise, _)| unsafe {
+                    GetPromiseState(promise.handle()) != PromiseState::Pending
+                });
+        *wakers = pending;
+        settled.into_iter().map(|(_, waker)| waker).collect()
+    });
+    settled.into_iter().for_each(Waker::wake);
+}
+
+/// Drop the tasks and wakers of this thread, which hold promises; the runtime
+/// is about to be destroyed.
+pub(crate) fn clear() {
+    // Dropping a future may queue another task, so don't hold the borrows.
+    loop {
+        let tasks = TASKS.with(|tasks| mem::take(&mut *tasks.borrow_mut()));
+        let wakers = PROMISE_WAKERS.with(|wakers| mem::take(&mut *wakers.borrow_mut()));
+        if tasks.is_empty() && wakers.is_empty() {
+            break;
+        }
+        drop(tasks);
+        drop(wakers);
+    }
+}
+
+/// Poll the futures behind [`JsPromise::from_future`] that have been woken,
+/// settling the promises of those that complete. Returns whether any future
+/// was polled.
+fn poll_tasks(cx: &mut JSContext) -> bool {
+    // Futures may create more tasks while being polled, so don't hold the borrow.
+    let tasks = TASKS.with(|tasks| mem::take(&mut *tasks.borrow_mut()));
+    let mut polled = false;
+    let mut pending = Vec::with_capacity(tasks.len());
+    for mut task in tasks {
+        if !task.waker.take_woken() {
+            pending.push(task);
+            continue;
+        }
+        polled = true;
+        let waker = Waker::from(task.waker.clone());
+        match task.future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Pending => pending.push(task),
+            Poll::Ready(settlement) => settle(cx, &task.promise, settlement),
+        }
+    }
+    TASKS.with(|tasks| {
+        let mut tasks = tasks.borrow_mut();
+        pending.append(&mut tasks);
+        *tasks = pending;
+    });
+    polled
+}
+
+fn settle(cx: &mut JSContext, promise: &Heap<*mut JSObject>, settlement: Settlement) {
+    let mut realm = AutoRealm::new(cx, NonNull::new(promise.get()).unwrap());
+    let cx = &mut *realm;
+    rooted!(&in(cx) let mut value = UndefinedValue());
+    let ok = unsafe {
+        let promise = HandleObject::from_raw(promise.handle());
+        match settlement {
+            Ok(result) => {
+                result.to_jsval(cx.raw_cx(), value.handle_mut());
+                ResolvePromise(cx, promise, value.handle())
+            }
+            Err(error) => {
+                error.to_jsval(cx.raw_cx(), value.handle_mut());
+                RejectPromise(cx, promise, value.handle())
+            }
+        }
+    };
+    if !ok {
+        warn!("Failed to settle the promise of a Rust future");
+        unsafe { JS_ClearPendingException(cx) };
+    }
+}
+
+/// Run Promise jobs and poll woken Rust futures until neither can make
+/// progress without outside events.
+pub fn run_until_stalled(cx: &mut JSContext) {
+    loop {
+        run_jobs(cx);
+        if !poll_tasks(cx) {
+            break;
+        }
+    }
+}
+
+/// Drive `future` to completion on the runtime's thread, running Promise jobs
+/// and the futures behind [`JsPromise::from_future`] in the meantime.
+///
+/// When nothing can make progress, the thread parks until a waker is invoked,
+/// possibly from another thread. Awaiting a promise that nothing will ever
+/// settle blocks forever.
+pub fn block_on<F: Future>(cx: &mut JSContext, future: F) -> F::Output {
+    let waker = Arc::new(ThreadWaker::new(true));
+    let task_waker = Waker::from(waker.clone());
+    let mut future = pin!(future);
+    loop {
+        if waker.take_woken() {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&task_waker))
+            {
+                return output;
+            }
+        }
+        run_until_stalled(cx);
+        let runnable = waker.woken.load(Ordering::SeqCst)
+            || TASKS.with(|tasks| {
+                tasks
+                    .borrow()
+                    .iter()
+                    .any(|task| task.waker.woken.load(Ordering::SeqCst))
+            });
+        if !runnable {
+            thread::park();
+        }
+    }
+}
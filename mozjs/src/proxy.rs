@@ -0,0 +1,402 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Proxies whose internal methods are implemented in Rust.
+//!
+//! A [`ProxyHandler`] implements the internal methods of its proxies, much
+//! like the handler object of a scripted `Proxy`. [`JsProxyHandler`] builds
+//! the `ProxyTraps` of the handler and creates proxies with it.
+//!
+//! ```ignore
+//! struct Answer;
+//!
+//! impl ProxyHandler for Answer {
+//!     fn get(
+//!         &self,
+//!         _cx: &mut JSContext,
+//!         _proxy: HandleObject,
+//!         _receiver: HandleValue,
+//!         _id: HandleId,
+//!         mut vp: MutableHandleValue,
+//!     ) -> bool {
+//!         vp.set(Int32Value(42));
+//!         true
+//!     }
+//! }
+//!
+//! let handler = JsProxyHandler::new(Answer);
+//! let proxy = handler.new_proxy(cx, HandleValue::undefined(), HandleObject::null());
+//! ```
+//!
+//! The traps follow the conventions of JSAPI: they return `false` with an
+//! exception pending on failure, and report whether an operation succeeded
+//! with an [`ObjectOpResult`]. Panics are caught with
+//! [`wrap_panic`](crate::panic::wrap_panic), and resumed once control returns
+//! to Rust.
+//!
+//! The handler is shared by the proxies created with it, and dropped when the
+//! [`JsProxyHandler`] and the last of these proxies are gone. It is not
+//! traced, so state that refers to GC things belongs in the private value of
+//! each proxy.
+
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::ptr::{self, NonNull};
+
+use crate::context::JSContext;
+use crate::glue::{CreateProxyHandler, DeleteProxyHandler, GetProxyHandlerExtra, ProxyTraps};
+use crate::jsapi;
+use crate::jsapi::PropertyDescriptor;
+use crate::jsapi::{GCContext, JSObject, MutableHandleIdVector, ObjectOpResult};
+use crate::panic::wrap_panic;
+use crate::rust::wrappers2::NewProxyObject;
+use crate::rust::wrappers2::{CallBaseProxyGet, CallBaseProxyHas, CallBaseProxySet};
+use crate::rust::{Handle, HandleId, HandleObject, HandleValue};
+use crate::rust::{MutableHandle, MutableHandleValue};
+
+/// The internal methods of proxies.
+///
+/// The default methods describe an object without own properties, whose
+/// `[[Get]]`, `[[Set]]` and `[[HasProperty]]` are derived from its own
+/// properties and its prototype, like those of ordinary objects.
+pub trait ProxyHandler: 'static {
+    /// `[[GetOwnProperty]]`: set `desc` to the descriptor of the own property
+    /// `id`, or `is_none` if there is none.
+    fn get_own_property_descriptor(
+        &self,
+        cx: &mut JSContext,
+        proxy: HandleObject,
+        id: HandleId,
+        desc: MutableHandle<PropertyDescriptor>,
+        is_none: &mut bool,
+    ) -> bool {
+        let _ = (cx, proxy, id, desc);
+        *is_none = true;
+        true
+    }
+
+    /// `[[DefineOwnProperty]]`. Fails by default, as if every property was
+    /// read-only.
+    fn define_property(
+        &self,
+        cx: &mut JSContext,
+        proxy: HandleObject,
+        id: HandleId,
+        desc: Handle<PropertyDescriptor>,
+        result: &mut ObjectOpResult,
+    ) -> bool {
+        let _ = (cx, proxy, id, desc);
+        result.fail_read_only()
+    }
+
+    /// `[[OwnPropertyKeys]]`: append the keys of the own properties to
+    /// `props`, with [`AppendToIdVector`](crate::rust::wrappers2::AppendToIdVector).
+    fn own_property_keys(
+        &self,
+        cx: &mut JSContext,
+        proxy: HandleObject,
+        props: MutableHandleIdVector,
+    ) -> bool {
+        let _ = (cx, proxy, props);
+        true
+    }
+
+    /// `[[Delete]]`. Succeeds by default, since there is nothing to delete.
+    fn delete(
+        &self,
+        cx: &mut JSContext,
+        proxy: HandleObject,
+        id: HandleId,
+        result: &mut ObjectOpResult,
+    ) -> bool {
+        let _ = (cx, proxy, id);
+        result.succeed()
+    }
+
+    /// `[[PreventExtensions]]`. Fails by default.
+    fn prevent_extensions(
+        &self,
+        cx: &mut JSContext,
+        proxy: HandleObject,
+        result: &mut ObjectOpResult,
+    ) -> bool {
+        let _ = (cx, proxy);
+        result.fail_cant_prevent_extensions()
+    }
+
+    /// `[[IsExtensible]]`. Proxies are extensible by default.
+    fn is_extensible(
+        &self,
+        cx: &mut JSContext,
+        proxy: HandleObject,
+        extensible: &mut bool,
+    ) -> bool {
+        let _ = (cx, proxy);
+        *extensible = true;
+        true
+    }
+
+    /// `[[HasProperty]]`: set `bp` to whether the proxy or its prototype
+    /// chain has the property `id`.
+    fn has(&self, cx: &mut JSContext, proxy: HandleObject, id: HandleId, bp: &mut bool) -> bool {
+        unsafe { CallBaseProxyHas(cx, proxy, id, bp) }
+    }
+
+    /// `[[Get]]`: set `vp` to the value of the property `id`, calling
+    /// getters with `receiver` as `this`.
+    fn get(
+        &self,
+        cx: &mut JSContext,
+        proxy: HandleObject,
+        receiver: HandleValue,
+        id: HandleId,
+        vp: MutableHandleValue,
+    ) -> bool {
+        unsafe { CallBaseProxyGet(cx, proxy, receiver, id, vp) }
+    }
+
+    /// `[[Set]]`: set the property `id` to `v`, calling setters with
+    /// `receiver` as `this`.
+    fn set(
+        &self,
+        cx: &mut JSContext,
+        proxy: HandleObject,
+        id: HandleId,
+        v: HandleValue,
+        receiver: HandleValue,
+        result: &mut ObjectOpResult,
+    ) -> bool {
+        unsafe { CallBaseProxySet(cx, proxy, id, v, receiver, result) }
+    }
+}
+
+/// The handler shared by a [`JsProxyHandler`] and its proxies.
+struct Shared<H> {
+    handler: H,
+    /// The C++ handler forwarding to the traps, whose extra is this value.
+    forwarding: Cell<*const c_void>,
+    /// The number of proxies, plus one while the [`JsProxyHandler`] lives.
+    references: Cell<usize>,
+}
+
+/// Drop a reference to `shared`, freeing it with its C++ handler if it was
+/// the last one.
+unsafe fn release<H>(shared: *const Shared<H>) {
+    let references = (*shared).references.get() - 1;
+    (*shared).references.set(references);
+    if references == 0 {
+        let shared = Box::from_raw(shared as *mut Shared<H>);
+        DeleteProxyHandler(shared.forwarding.get());
+    }
+}
+
+/// Call `f` with the handler of `proxy`, catching panics.
+unsafe fn with_handler<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    f: impl FnOnce(&H, &mut JSContext, HandleObject) -> bool,
+) -> bool {
+    let mut result = false;
+    let mut f = Some(f);
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        let shared = &*(GetProxyHandlerExtra(proxy.get()) as *const Shared<H>);
+        let f = f.take().unwrap();
+        result = f(&shared.handler, &mut cx, Handle::from_raw(proxy));
+    });
+    result
+}
+
+unsafe extern "C" fn get_own_property_descriptor<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    id: jsapi::HandleId,
+    desc: jsapi::MutableHandle<PropertyDescriptor>,
+    is_none: *mut bool,
+) -> bool {
+    with_handler::<H>(cx, proxy, |handler, cx, proxy| {
+        let desc = MutableHandle::from_raw(desc);
+        handler.get_own_property_descriptor(cx, proxy, Handle::from_raw(id), desc, &mut *is_none)
+    })
+}
+
+unsafe extern "C" fn define_property<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    id: jsapi::HandleId,
+    desc: jsapi::Handle<PropertyDescriptor>,
+    result: *mut ObjectOpResult,
+) -> bool {
+    with_handler::<H>(cx, proxy, |handler, cx, proxy| {
+        let id = Handle::from_raw(id);
+        handler.define_property(cx, proxy, id, Handle::from_raw(desc), &mut *result)
+    })
+}
+
+unsafe extern "C" fn own_property_keys<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    props: MutableHandleIdVector,
+) -> bool {
+    with_handler::<H>(cx, proxy, |handler, cx, proxy| {
+        handler.own_property_keys(cx, proxy, props)
+    })
+}
+
+unsafe extern "C" fn delete<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    id: jsapi::HandleId,
+    result: *mut ObjectOpResult,
+) -> bool {
+    with_handler::<H>(cx, proxy, |handler, cx, proxy| {
+        handler.delete(cx, proxy, Handle::from_raw(id), &mut *result)
+    })
+}
+
+/// Only called for proxies with a lazy prototype, which are never created.
+unsafe extern "C" fn get_prototype_if_ordinary(
+    _cx: *mut jsapi::JSContext,
+    _proxy: jsapi::HandleObject,
+    is_ordinary: *mut bool,
+    _proto: jsapi::MutableHandleObject,
+) -> bool {
+    *is_ordinary = false;
+    true
+}
+
+unsafe extern "C" fn prevent_extensions<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    result: *mut ObjectOpResult,
+) -> bool {
+    with_handler::<H>(cx, proxy, |handler, cx, proxy| {
+        handler.prevent_extensions(cx, proxy, &mut *result)
+    })
+}
+
+unsafe extern "C" fn is_extensible<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    extensible: *mut bool,
+) -> bool {
+    with_handler::<H>(cx, proxy, |handler, cx, proxy| {
+        handler.is_extensible(cx, proxy, &mut *extensible)
+    })
+}
+
+unsafe extern "C" fn has<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    id: jsapi::HandleId,
+    bp: *mut bool,
+) -> bool {
+    with_handler::<H>(cx, proxy, |handler, cx, proxy| {
+        handler.has(cx, proxy, Handle::from_raw(id), &mut *bp)
+    })
+}
+
+unsafe extern "C" fn get<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    receiver: jsapi::HandleValue,
+    id: jsapi::HandleId,
+    vp: jsapi::MutableHandleValue,
+) -> bool {
+    with_handler::<H>(cx, proxy, |handler, cx, proxy| {
+        let receiver = Handle::from_raw(receiver);
+        let id = Handle::from_raw(id);
+        handler.get(cx, proxy, receiver, id, MutableHandle::from_raw(vp))
+    })
+}
+
+unsafe extern "C" fn set<H: ProxyHandler>(
+    cx: *mut jsapi::JSContext,
+    proxy: jsapi::HandleObject,
+    id: jsapi::HandleId,
+    v: jsapi::HandleValue,
+    receiver: jsapi::HandleValue,
+    result: *mut ObjectOpResult,
+) -> bool {
+    with_handler::<H>(cx, proxy, |handler, cx, proxy| {
+        let id = Handle::from_raw(id);
+        let v = Handle::from_raw(v);
+        handler.set(cx, proxy, id, v, Handle::from_raw(receiver), &mut *result)
+    })
+}
+
+unsafe extern "C" fn finalize<H>(_gcx: *mut GCContext, proxy: *mut JSObject) {
+    release(GetProxyHandlerExtra(proxy) as *const Shared<H>);
+}
+
+/// A [`ProxyHandler`], and the C++ proxy handler calling it.
+pub struct JsProxyHandler<H: ProxyHandler> {
+    shared: NonNull<Shared<H>>,
+}
+
+impl<H: ProxyHandler> JsProxyHandler<H> {
+    /// Create the C++ proxy handler calling `handler`.
+    pub fn new(handler: H) -> JsProxyHandler<H> {
+        let traps = ProxyTraps {
+            getOwnPropertyDescriptor: Some(get_own_property_descriptor::<H>),
+            defineProperty: Some(define_property::<H>),
+            ownPropertyKeys: Some(own_property_keys::<H>),
+            delete_: Some(delete::<H>),
+            getPrototypeIfOrdinary: Some(get_prototype_if_ordinary),
+            preventExtensions: Some(prevent_extensions::<H>),
+            isExtensible: Some(is_extensible::<H>),
+            has: Some(has::<H>),
+            get: Some(get::<H>),
+            set: Some(set::<H>),
+            finalize: Some(finalize::<H>),
+            ..ProxyTraps::default()
+        };
+        let shared = Box::into_raw(Box::new(Shared {
+            handler,
+            forwarding: Cell::new(ptr::null()),
+            references: Cell::new(1),
+        }));
+        unsafe {
+            let forwarding = CreateProxyHandler(&traps, shared as *const c_void);
+            (*shared).forwarding.set(forwarding);
+            JsProxyHandler {
+                shared: NonNull::new_unchecked(shared),
+            }
+        }
+    }
+
+    /// The handler.
+    pub fn handler(&self) -> &H {
+        unsafe { &self.shared.as_ref().handler }
+    }
+
+    /// The C++ proxy handler, for `NewProxyObject` and `IsProxyHandlerFamily`.
+    pub fn as_ptr(&self) -> *const c_void {
+        unsafe { self.shared.as_ref().forwarding.get() }
+    }
+
+    /// Create a proxy in the current realm with `private` as its private
+    /// value and `proto` as its prototype. Returns null with an exception
+    /// pending on failure.
+    pub fn new_proxy(
+        &self,
+        cx: &mut JSContext,
+        private: HandleValue,
+        proto: HandleObject,
+    ) -> *mut JSObject {
+        let proxy =
+            unsafe { NewProxyObject(cx, self.as_ptr(), private, proto.get(), ptr::null(), false) };
+        if !proxy.is_null() {
+            let shared = unsafe { self.shared.as_ref() };
+            shared.references.set(shared.references.get() + 1);
+        }
+        proxy
+    }
+}
+
+impl<H: ProxyHandler> Drop for JsProxyHandler<H> {
+    fn drop(&mut self) {
+        unsafe { release(self.shared.as_ptr()) }
+    }
+}
@@ -7,15 +7,17 @@
 use std::cell::Cell;
 use std::char;
 use std::default::Default;
-use std::ffi::{CStr, CString};
+use std::ffi::{c_void, CStr, CString};
+use std::fmt;
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::ops::{Deref, DerefMut};
 use std::ptr::{self, NonNull};
+use std::rc::Rc;
 use std::slice;
 use std::str;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 
 use self::wrappers::{
     StackGCVectorStringAtIndex, StackGCVectorStringLength, StackGCVectorValueAtIndex,
@@ -25,6 +27,7 @@ use crate::consts::{JSCLASS_GLOBAL_SLOT_COUNT, JSCLASS_RESERVED_SLOTS_MASK};
 use crate::consts::{JSCLASS_IS_DOMJSCLASS, JSCLASS_IS_GLOBAL};
 use crate::conversions::jsstr_to_string;
 use crate::default_heapsize;
+use crate::error::Exception;
 pub use crate::gc::*;
 use crate::glue::AppendToRootedObjectVector;
 use crate::glue::{CreateRootedIdVector, CreateRootedObjectVector};
@@ -35,7 +38,13 @@ use crate::glue::{DeleteJSAutoStructuredCloneBuffer, NewJSAutoStructuredCloneBuf
 use crate::glue::{
     GetIdVectorAddress, GetObjectVectorAddress, NewCompileOptions, SliceRootedIdVector,
 };
+use crate::jobs::{InstalledJobQueue, JobQueue};
 use crate::jsapi;
+use crate::jsapi::glue::{
+    CompileGlobalScriptToStencilWithFrontendContext, DecodeStencilOffThread, EncodeStencil,
+    GetScriptTranscodingBuildId, InstantiateGlobalStencilWithDefaultOptions,
+    NewFrontendCompileOptions, SetTranscodingBuildId,
+};
 use crate::jsapi::glue::{DeleteRealmOptions, JS_Init, JS_NewRealmOptions};
 use crate::jsapi::js;
 use crate::jsapi::js::frontend::InitialStencilAndDelazifications;
@@ -44,18 +53,22 @@ use crate::jsapi::shadow::BaseShape;
 use crate::jsapi::HandleObjectVector as RawHandleObjectVector;
 use crate::jsapi::HandleValue as RawHandleValue;
 use crate::jsapi::JS_AddExtraGCRootsTracer;
+use crate::jsapi::JS_AddInterruptCallback;
 use crate::jsapi::MutableHandleIdVector as RawMutableHandleIdVector;
 use crate::jsapi::{already_AddRefed, jsid};
 use crate::jsapi::{BuildStackString, CaptureCurrentStack, StackFormat};
-use crate::jsapi::{HandleValueArray, StencilRelease};
+use crate::jsapi::{DestroyFrontendContext, FrontendContext, GetFrontendErrorReport};
+use crate::jsapi::{HadFrontendOverRecursed, NewFrontendContext, SetNativeStackQuota};
+use crate::jsapi::{HandleValueArray, StencilRelease, ThreadStackQuotaForSize};
 use crate::jsapi::{InitSelfHostedCode, IsWindowSlow};
 use crate::jsapi::{JSAutoStructuredCloneBuffer, JSStructuredCloneCallbacks, StructuredCloneScope};
 use crate::jsapi::{JSClass, JSClassOps, JSContext, Realm, JSCLASS_RESERVED_SLOTS_SHIFT};
 use crate::jsapi::{JSErrorReport, JSFunctionSpec, JSGCParamKey};
-use crate::jsapi::{JSObject, JSPropertySpec, JSRuntime};
+use crate::jsapi::{JSObject, JSPropertySpec, JSRuntime, JSScript};
 use crate::jsapi::{JSString, Object, PersistentRootedIdVector};
 use crate::jsapi::{JS_DefineFunctions, JS_DefineProperties, JS_DestroyContext, JS_ShutDown};
 use crate::jsapi::{JS_EnumerateStandardClasses, JS_GlobalObjectTraceHook};
+use crate::jsapi::{JS_GetImplementationVersion, TranscodeResult};
 use crate::jsapi::{JS_MayResolveStandardClass, JS_NewContext, JS_ResolveStandardClass};
 use crate::jsapi::{JS_RequestInterruptCallback, JS_RequestInterruptCallbackCanWait};
 use crate::jsapi::{JS_SetGCParameter, JS_SetNativeStackQuota, JS_WrapObject, JS_WrapValue};
@@ -65,8 +78,11 @@ use crate::jsapi::{SetWarningReporter, SourceText, ToBooleanSlow};
 use crate::jsapi::{ToInt32Slow, ToInt64Slow, ToNumberSlow, ToStringSlow, ToUint16Slow};
 use crate::jsapi::{ToUint32Slow, ToUint64Slow, ToWindowProxyIfWindowSlow};
 use crate::jsval::{JSVal, ObjectValue};
+use crate::memory::MemoryReport;
+use crate::modules::{ModuleHost, ModuleLoader, SyntheticModule};
 use crate::panic::maybe_resume_unwind;
 use crate::realm::AutoRealm;
+use crate::watchdog::interrupt_callback;
 use log::{debug, warn};
 use mozjs_sys::jsapi::JS::SavedFrameResult;
 pub use mozjs_sys::jsgc::{GCMethods, IntoHandle, IntoMutableHandle};
@@ -109,20 +125,6 @@ const SYSTEM_CODE_BUFFER: usize = 10 * 1024;
 // Gecko's value on 64-bit.
 const TRUSTED_SCRIPT_BUFFER: usize = 8 * 12800;
 
-trait ToResult {
-    fn to_result(self) -> Result<(), ()>;
-}
-
-impl ToResult for bool {
-    fn to_result(self) -> Result<(), ()> {
-        if self {
-            Ok(())
-        } else {
-            Err(())
-        }
-    }
-}
-
 // ___________________________________________________________________________
 // friendly Rustic API to runtimes
 
@@ -301,6 +303,11 @@ pub struct Runtime {
     /// This is shared with all [`ThreadSafeJSContext`]s, so
     /// they can detect when it's destroyed on the main thread.
     thread_safe_handle: Arc<RwLock<Option<NonNull<JSContext>>>>,
+    /// The job queue installed with [`Runtime::set_job_queue`], dropped
+    /// before the context is destroyed.
+    job_queue: Option<InstalledJobQueue>,
+    /// The module host installed with [`Runtime::set_module_loader`].
+    module_host: Option<Rc<ModuleHost>>,
 }
 
 impl Runtime {
@@ -319,8 +326,23 @@ impl Runtime {
     }
 
     /// Creates a new `JSContext`.
+    ///
+    /// This uses the default configuration; see [`RuntimeBuilder`] to customize
+    /// the heap, GC parameters or stack quotas.
+    ///
+    /// Every runtime sets the out-of-memory callback (`SetOutOfMemoryCallback`)
+    /// and the destroy-realm callback (`SetDestroyRealmCallback`) of its
+    /// context, which [`crate::memory`] relies on. Setting either directly
+    /// replaces them; pass the embedder's own callbacks to
+    /// [`RuntimeBuilder::out_of_memory_callback`] and
+    /// [`RuntimeBuilder::destroy_realm_callback`] instead.
     pub fn new(engine: JSEngineHandle) -> Runtime {
-        unsafe { Self::create(engine, None) }
+        RuntimeBuilder::new().build(engine)
+    }
+
+    /// Returns a [`RuntimeBuilder`] with the default configuration.
+    pub fn builder() -> RuntimeBuilder {
+        RuntimeBuilder::new()
     }
 
     /// Signal that a new child runtime will be created in the future, and ensure
@@ -344,31 +366,44 @@ impl Runtime {
     /// continue executing after the thread with the parent runtime panics, but they
     /// will be in an invalid and undefined state.
     pub unsafe fn create_with_parent(parent: ParentRuntime) -> Runtime {
-        Self::create(parent.engine.clone(), Some(parent))
+        RuntimeBuilder::new().build_with_parent(parent)
     }
 
-    unsafe fn create(engine: JSEngineHandle, parent: Option<ParentRuntime>) -> Runtime {
+    unsafe fn create(
+        builder: RuntimeBuilder,
+        engine: JSEngineHandle,
+        parent: Option<ParentRuntime>,
+    ) -> Runtime {
         let parent_runtime = parent.as_ref().map_or(ptr::null_mut(), |r| r.parent);
-        let js_context = NonNull::new(JS_NewContext(
-            default_heapsize + (ChunkSize as u32),
-            parent_runtime,
-        ))
-        .unwrap();
+        let js_context =
+            NonNull::new(JS_NewContext(builder.context_bytes, parent_runtime)).unwrap();
 
-        // Unconstrain the runtime's threshold on nominal heap size, to avoid
-        // triggering GC too often if operating continuously near an arbitrary
-        // finite threshold. This leaves the maximum-JS_malloc-bytes threshold
-        // still in effect to cause periodical, and we hope hygienic,
-        // last-ditch GCs from within the GC's allocator.
-        JS_SetGCParameter(js_context.as_ptr(), JSGCParamKey::JSGC_MAX_BYTES, u32::MAX);
+        JS_SetGCParameter(
+            js_context.as_ptr(),
+            JSGCParamKey::JSGC_MAX_BYTES,
+            builder.max_heap_bytes,
+        );
+
+        if let Some(nursery_bytes) = builder.nursery_bytes {
+            JS_SetGCParameter(
+                js_context.as_ptr(),
+                JSGCParamKey::JSGC_MAX_NURSERY_BYTES,
+                nursery_bytes,
+            );
+        }
+
+        for &(key, value) in &builder.gc_parameters {
+            JS_SetGCParameter(js_context.as_ptr(), key, value);
+        }
 
         JS_AddExtraGCRootsTracer(js_context.as_ptr(), Some(trace_traceables), ptr::null_mut());
 
+        let quota = builder.stack_quota;
         JS_SetNativeStackQuota(
             js_context.as_ptr(),
-            STACK_QUOTA,
-            STACK_QUOTA - SYSTEM_CODE_BUFFER,
-            STACK_QUOTA - SYSTEM_CODE_BUFFER - TRUSTED_SCRIPT_BUFFER,
+            quota.system,
+            quota.trusted_script,
+            quota.untrusted_script,
         );
 
         CONTEXT.with(|context| {
@@ -383,7 +418,14 @@ impl Runtime {
 
         InitSelfHostedCode(js_context.as_ptr(), cache, None);
 
-        SetWarningReporter(js_context.as_ptr(), Some(report_warning));
+        SetWarningReporter(js_context.as_ptr(), builder.warning_reporter);
+
+        JS_AddInterruptCallback(js_context.as_ptr(), Some(interrupt_callback));
+        crate::memory::install_callbacks(
+            js_context.as_ptr(),
+            builder.out_of_memory_callback,
+            builder.destroy_realm_callback,
+        );
 
         Runtime {
             engine,
@@ -391,9 +433,88 @@ impl Runtime {
             cx: crate::context::JSContext::from_ptr(js_context),
             outstanding_children: Arc::new(()),
             thread_safe_handle: Arc::new(RwLock::new(Some(js_context))),
+            job_queue: None,
+            module_host: None,
         }
     }
 
+    /// Install `queue` as the queue that receives this runtime's Promise jobs,
+    /// replacing any queue installed before. Jobs still pending in a replaced
+    /// queue are dropped without running.
+    pub fn set_job_queue<Q: JobQueue>(&mut self, queue: Q) {
+        let installed = InstalledJobQueue::install(self.cx_no_gc(), queue);
+        self.job_queue = Some(installed);
+    }
+
+    /// Install `observer` to be told about the collections of this runtime,
+    /// replacing any observer installed before.
+    ///
+    /// The observer takes over the runtime's GC callback (`JS_SetGCCallback`),
+    /// replacing any callback set by the embedder: SpiderMonkey does not
+    /// return the previous one, so it cannot be chained. The slice callback
+    /// set before (`SetGCSliceCallback`) is still called after the
+    /// observer's, and is put back when the runtime is dropped.
+    pub fn set_gc_observer<O: GcObserver>(&mut self, observer: O) {
+        crate::gc::observer::install(unsafe { self.cx.raw_cx() }, observer);
+    }
+
+    /// A [`GcScheduler`] running the collections of this runtime.
+    pub fn gc_scheduler(&mut self) -> GcScheduler<'_> {
+        GcScheduler::new(&mut self.cx)
+    }
+
+    /// Install `loader` to resolve and fetch the modules imported by scripts
+    /// on this runtime. See [`crate::modules`].
+    ///
+    /// The module map of each global importing modules is kept in its
+    /// reserved slot [`ModuleLoader::module_map_slot`], by default the last
+    /// of the `JSCLASS_GLOBAL_APPLICATION_SLOTS`, which the embedder must not
+    /// use for anything else.
+    pub fn set_module_loader<L: ModuleLoader>(&mut self, loader: L) {
+        self.module_host = Some(crate::modules::install(self.rt(), loader));
+    }
+
+    /// Make `module` importable as `specifier` by the modules of this runtime,
+    /// as a JavaScript module. Installing another module loader forgets
+    /// registered modules.
+    ///
+    /// # Panics
+    /// If no loader has been installed with [`Runtime::set_module_loader`].
+    pub fn register_synthetic_module<M: SyntheticModule>(&mut self, specifier: &str, module: M) {
+        self.module_host
+            .as_ref()
+            .expect("set_module_loader must be called before registering modules")
+            .register_synthetic(specifier, module);
+    }
+
+    /// Returns the queue installed with [`Runtime::set_job_queue`], if it has type `Q`.
+    pub fn job_queue<Q: JobQueue>(&self) -> Option<&Q> {
+        self.job_queue
+            .as_ref()
+            .and_then(|queue| queue.downcast_ref())
+    }
+
+    /// The maximum size of the GC heap (`JSGC_MAX_BYTES`).
+    pub fn max_heap_bytes(&self) -> u32 {
+        unsafe { wrappers2::JS_GetGCParameter(self.cx_no_gc(), JSGCParamKey::JSGC_MAX_BYTES) }
+    }
+
+    /// Change the maximum size of the GC heap (`JSGC_MAX_BYTES`). Allocations
+    /// that would exceed it fail with an out-of-memory exception; see
+    /// [`crate::memory`].
+    pub fn set_max_heap_bytes(&mut self, bytes: u32) {
+        unsafe {
+            wrappers2::JS_SetGCParameter(self.cx(), JSGCParamKey::JSGC_MAX_BYTES, bytes);
+        }
+    }
+
+    /// Measure the memory of this runtime, by kind of thing and by realm.
+    /// See [`crate::memory`]. Returns `None` if the engine ran out of memory
+    /// while measuring.
+    pub fn memory_report(&mut self) -> Option<MemoryReport> {
+        crate::memory::memory_report(&mut self.cx)
+    }
+
     /// Returns the `JSRuntime` object.
     pub fn rt(&self) -> *mut JSRuntime {
         unsafe { wrappers2::JS_GetRuntime(self.cx_no_gc()) }
@@ -410,13 +531,168 @@ impl Runtime {
     }
 }
 
+/// Native stack limits, in bytes, for the three kinds of code SpiderMonkey
+/// distinguishes. See `JS_SetNativeStackQuota`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StackQuota {
+    /// The limit for system (chrome) code.
+    pub system: usize,
+    /// The limit for trusted script.
+    pub trusted_script: usize,
+    /// The limit for untrusted (content) script.
+    pub untrusted_script: usize,
+}
+
+impl StackQuota {
+    /// Derive the trusted and untrusted limits from a total stack size, leaving
+    /// the same buffers between the categories that Gecko uses.
+    pub fn from_total(total: usize) -> StackQuota {
+        StackQuota {
+            system: total,
+            trusted_script: total.saturating_sub(SYSTEM_CODE_BUFFER),
+            untrusted_script: total.saturating_sub(SYSTEM_CODE_BUFFER + TRUSTED_SCRIPT_BUFFER),
+        }
+    }
+}
+
+impl Default for StackQuota {
+    fn default() -> StackQuota {
+        StackQuota::from_total(STACK_QUOTA)
+    }
+}
+
+/// Configuration for a new [`Runtime`], applied before any script can run.
+///
+/// ```ignore
+/// let runtime = Runtime::builder()
+///     .max_heap_bytes(64 * 1024 * 1024)
+///     .stack_quota(StackQuota::from_total(256 * 1024))
+///     .build(engine.handle());
+/// ```
+#[derive(Clone, Debug)]
+pub struct RuntimeBuilder {
+    context_bytes: u32,
+    max_heap_bytes: u32,
+    nursery_bytes: Option<u32>,
+    gc_parameters: Vec<(JSGCParamKey, u32)>,
+    stack_quota: StackQuota,
+    warning_reporter: jsapi::WarningReporter,
+    out_of_memory_callback: (jsapi::OutOfMemoryCallback, *mut c_void),
+    destroy_realm_callback: jsapi::DestroyRealmCallback,
+}
+
+impl Default for RuntimeBuilder {
+    fn default() -> RuntimeBuilder {
+        RuntimeBuilder::new()
+    }
+}
+
+impl RuntimeBuilder {
+    /// A builder with the configuration used by [`Runtime::new`].
+    pub fn new() -> RuntimeBuilder {
+        RuntimeBuilder {
+            context_bytes: default_heapsize + (ChunkSize as u32),
+            // Unconstrain the runtime's threshold on nominal heap size, to avoid
+            // triggering GC too often if operating continuously near an arbitrary
+            // finite threshold. This leaves the maximum-JS_malloc-bytes threshold
+            // still in effect to cause periodical, and we hope hygienic,
+            // last-ditch GCs from within the GC's allocator.
+            max_heap_bytes: u32::MAX,
+            nursery_bytes: None,
+            gc_parameters: Vec::new(),
+            stack_quota: StackQuota::default(),
+            warning_reporter: Some(report_warning),
+            out_of_memory_callback: (None, ptr::null_mut()),
+            destroy_realm_callback: None,
+        }
+    }
+
+    /// The heap size passed to `JS_NewContext`.
+    pub fn context_bytes(mut self, bytes: u32) -> RuntimeBuilder {
+        self.context_bytes = bytes;
+        self
+    }
+
+    /// The maximum size of the GC heap (`JSGC_MAX_BYTES`), unlimited by
+    /// default. See [`crate::memory`] for what happens when it is reached.
+    pub fn max_heap_bytes(mut self, bytes: u32) -> RuntimeBuilder {
+        self.max_heap_bytes = bytes;
+        self
+    }
+
+    /// The maximum size of the nursery (`JSGC_MAX_NURSERY_BYTES`). The engine
+    /// default is kept when this is not called.
+    pub fn nursery_bytes(mut self, bytes: u32) -> RuntimeBuilder {
+        self.nursery_bytes = Some(bytes);
+        self
+    }
+
+    /// Set an arbitrary GC parameter. Parameters are applied in order, after
+    /// `max_heap_bytes` and `nursery_bytes`, so they can override either.
+    pub fn gc_parameter(mut self, key: JSGCParamKey, value: u32) -> RuntimeBuilder {
+        self.gc_parameters.push((key, value));
+        self
+    }
+
+    /// The native stack limits for system, trusted and untrusted code.
+    pub fn stack_quota(mut self, quota: StackQuota) -> RuntimeBuilder {
+        self.stack_quota = quota;
+        self
+    }
+
+    /// The reporter that receives warnings; `None` discards them.
+    /// Defaults to [`report_warning`], which logs through the `log` crate.
+    pub fn warning_reporter(mut self, reporter: jsapi::WarningReporter) -> RuntimeBuilder {
+        self.warning_reporter = reporter;
+        self
+    }
+
+    /// A callback called with `data` when the engine runs out of memory,
+    /// after the runtime's own, which records the realm for
+    /// [`crate::memory`].
+    pub fn out_of_memory_callback(
+        mut self,
+        callback: jsapi::OutOfMemoryCallback,
+        data: *mut c_void,
+    ) -> RuntimeBuilder {
+        self.out_of_memory_callback = (callback, data);
+        self
+    }
+
+    /// A callback called when a realm is destroyed, after the runtime's own,
+    /// which forgets the name given with [`crate::memory::set_realm_name`].
+    pub fn destroy_realm_callback(
+        mut self,
+        callback: jsapi::DestroyRealmCallback,
+    ) -> RuntimeBuilder {
+        self.destroy_realm_callback = callback;
+        self
+    }
+
+    /// Creates a new `JSContext` with this configuration.
+    ///
+    /// The out-of-memory and destroy-realm callbacks of the context are set
+    /// as described on [`Runtime::new`].
+    pub fn build(self, engine: JSEngineHandle) -> Runtime {
+        unsafe { Runtime::create(self, engine, None) }
+    }
+
+    /// Creates a new `JSContext` with this configuration and a parent runtime.
+    ///
+    /// Unsafety:
+    /// The same requirements as [`Runtime::create_with_parent`] apply.
+    pub unsafe fn build_with_parent(self, parent: ParentRuntime) -> Runtime {
+        Runtime::create(self, parent.engine.clone(), Some(parent))
+    }
+}
+
 pub fn evaluate_script(
     cx: &mut crate::context::JSContext,
     glob: HandleObject,
     script: &str,
     rval: MutableHandleValue,
     options: CompileOptionsWrapper,
-) -> Result<(), ()> {
+) -> Result<(), Exception> {
     debug!(
         "Evaluating script from {} with content {}",
         options.filename(),
@@ -430,7 +706,7 @@ pub fn evaluate_script(
         if !wrappers2::Evaluate2(&mut realm, options.ptr, &mut source, rval.into()) {
             debug!("...err!");
             maybe_resume_unwind();
-            Err(())
+            Err(Exception::steal(realm.raw_cx()))
         } else {
             // we could return the script result but then we'd have
             // to root it and so forth and, really, who cares?
@@ -447,6 +723,12 @@ impl Drop for Runtime {
             Arc::get_mut(&mut self.outstanding_children).is_some(),
             "This runtime still has live children."
         );
+        crate::modules::uninstall();
+        self.module_host.take();
+        crate::promise::clear();
+        crate::gc::observer::uninstall(unsafe { self.cx.raw_cx() });
+        // Pending jobs are held in `Heap`s, whose barriers need the runtime.
+        self.job_queue.take();
         unsafe {
             JS_DestroyContext(self.cx.raw_cx());
 
@@ -592,12 +874,148 @@ impl Drop for JSAutoStructuredCloneBufferWrapper {
     }
 }
 
+/// Options for [`Stencil::compile`].
+#[derive(Clone, Debug)]
+pub struct StencilOptions {
+    filename: CString,
+    line: u32,
+    stack_size: usize,
+}
+
+impl StencilOptions {
+    /// Options for a script named `filename`, starting at `line`.
+    pub fn new(filename: CString, line: u32) -> StencilOptions {
+        StencilOptions {
+            filename,
+            line,
+            stack_size: STACK_QUOTA,
+        }
+    }
+
+    /// The stack size of the threads compiling with these options; deeply
+    /// nested scripts fail to compile instead of overflowing it. Defaults to
+    /// the stack quota of a [`Runtime`].
+    pub fn stack_size(mut self, bytes: usize) -> StencilOptions {
+        self.stack_size = bytes;
+        self
+    }
+
+    /// The name of the script.
+    pub fn filename(&self) -> &CStr {
+        &self.filename
+    }
+
+    /// The line number the script starts at.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+}
+
+/// An error reported while compiling a [`Stencil`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompileError {
+    pub message: String,
+    pub filename: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl CompileError {
+    unsafe fn from_frontend(
+        fc: *mut FrontendContext,
+        options: *const ReadOnlyCompileOptions,
+        filename: &CStr,
+    ) -> CompileError {
+        let report = if options.is_null() {
+            ptr::null()
+        } else {
+            GetFrontendErrorReport(fc, options)
+        };
+        if report.is_null() {
+            let message = if HadFrontendOverRecursed(fc) {
+                "too much recursion"
+            } else {
+                "out of memory"
+            };
+            return CompileError {
+                message: message.to_owned(),
+                filename: filename.to_string_lossy().into_owned(),
+                line: 0,
+                column: 0,
+            };
+        }
+        let report = &(*report)._base;
+        let filename = if report.filename.data_.is_null() {
+            filename.to_string_lossy().into_owned()
+        } else {
+            CStr::from_ptr(report.filename.data_)
+                .to_string_lossy()
+                .into_owned()
+        };
+        let message = if report.message_.data_.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(report.message_.data_)
+                .to_string_lossy()
+                .into_owned()
+        };
+        CompileError {
+            message,
+            filename,
+            line: report.lineno,
+            column: report.column._base,
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.filename, self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// A `JS::FrontendContext`, destroyed on drop.
+struct OwnedFrontendContext(*mut FrontendContext);
+
+impl OwnedFrontendContext {
+    fn new() -> OwnedFrontendContext {
+        let fc = unsafe { NewFrontendContext() };
+        assert!(!fc.is_null());
+        OwnedFrontendContext(fc)
+    }
+}
+
+impl Drop for OwnedFrontendContext {
+    fn drop(&mut self) {
+        unsafe { DestroyFrontendContext(self.0) }
+    }
+}
+
+/// A compiled script that is not tied to any runtime.
+///
+/// Stencils can be compiled on any thread with [`Stencil::compile`], shared
+/// between threads, and turned into scripts on a runtime's thread with
+/// [`Stencil::instantiate`], as many times as needed.
 pub struct Stencil {
     inner: already_AddRefed<InitialStencilAndDelazifications>,
 }
 
-/*unsafe impl Send for Stencil {}
-unsafe impl Sync for Stencil {}*/
+// SAFETY: `InitialStencilAndDelazifications` is documented as safe to use
+// from multiple threads once initialized, which `Stencil::compile` and
+// `Stencil::decode` do before returning. Its reference count, and that of
+// the `CompilationStencil` it shares, are `mozilla::Atomic`, so `Drop` may
+// release it on any thread. Delazifications are published through atomic
+// pointers, and nothing else is mutated after compilation.
+unsafe impl Send for Stencil {}
+// SAFETY: see `Send`; every operation exposed through `&Stencil` is
+// thread-safe.
+unsafe impl Sync for Stencil {}
 
 impl Drop for Stencil {
     fn drop(&mut self) {
@@ -619,11 +1037,205 @@ impl Deref for Stencil {
 }
 
 impl Stencil {
+    /// Compile `source` as a global script, without a `JSContext`.
+    ///
+    /// This can run on any thread, as long as the [`JSEngine`] is
+    /// initialized.
+    pub fn compile(source: &str, options: &StencilOptions) -> Result<Stencil, CompileError> {
+        unsafe {
+            let fc = OwnedFrontendContext::new();
+            SetNativeStackQuota(fc.0, ThreadStackQuotaForSize(options.stack_size));
+
+            let compile_options =
+                NewFrontendCompileOptions(fc.0, options.filename.as_ptr(), options.line);
+            if compile_options.is_null() {
+                return Err(CompileError::from_frontend(
+                    fc.0,
+                    compile_options,
+                    &options.filename,
+                ));
+            }
+            let stencil = Stencil {
+                inner: CompileGlobalScriptToStencilWithFrontendContext(
+                    fc.0,
+                    compile_options,
+                    source.as_ptr() as *const _,
+                    source.len(),
+                ),
+            };
+            let result = if stencil.is_null() {
+                Err(CompileError::from_frontend(
+                    fc.0,
+                    compile_options,
+                    &options.filename,
+                ))
+            } else {
+                Ok(stencil)
+            };
+            DeleteCompileOptions(compile_options);
+            result
+        }
+    }
+
+    /// Instantiate the stencil as a script in the realm of `global`, ready to
+    /// be run with `JS_ExecuteScript`, and store it in `script`.
+    pub fn instantiate(
+        &self,
+        cx: &mut crate::context::JSContext,
+        global: HandleObject,
+        mut script: MutableHandle<*mut JSScript>,
+    ) -> Result<(), Exception> {
+        assert!(!self.is_null());
+        let mut realm = AutoRealm::new_from_handle(cx, global);
+        script.set(unsafe {
+            InstantiateGlobalStencilWithDefaultOptions(realm.raw_cx(), self.inner.mRawPtr)
+        });
+        if script.get().is_null() {
+            maybe_resume_unwind();
+            Err(unsafe { Exception::steal(realm.raw_cx()) })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Serialize the stencil, tagged with the [`transcoding_build_id`], for
+    /// [`Stencil::decode`]. On [`TranscodeError::Error`] an exception is
+    /// pending on `cx`.
+    pub fn encode(&self, cx: &mut crate::context::JSContext) -> Result<Vec<u8>, TranscodeError> {
+        assert!(!self.is_null());
+        let build_id = transcoding_build_id();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(build_id.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&build_id);
+        bytes.resize(align_transcoding_offset(bytes.len()), 0);
+
+        let result = unsafe {
+            EncodeStencil(
+                cx.raw_cx(),
+                self.inner.mRawPtr,
+                &mut bytes as *mut Vec<u8> as *mut c_void,
+                Some(append_encoded_bytes),
+            )
+        };
+        TranscodeError::check(result)?;
+        Ok(bytes)
+    }
+
+    /// Deserialize a stencil produced by [`Stencil::encode`]. Data encoded by
+    /// another build is rejected with [`TranscodeError::BadBuildId`].
+    ///
+    /// Like [`Stencil::compile`], this can run on any thread.
+    pub fn decode(bytes: &[u8]) -> Result<Stencil, TranscodeError> {
+        let length = bytes
+            .get(..4)
+            .map(|length| u32::from_le_bytes(length.try_into().unwrap()) as usize)
+            .ok_or(TranscodeError::BadDecode)?;
+        let build_id = bytes.get(4..4 + length).ok_or(TranscodeError::BadDecode)?;
+        if build_id != transcoding_build_id() {
+            return Err(TranscodeError::BadBuildId);
+        }
+        let data = bytes
+            .get(align_transcoding_offset(4 + length)..)
+            .ok_or(TranscodeError::BadDecode)?;
+
+        let fc = OwnedFrontendContext::new();
+        let mut result = TranscodeResult::Ok;
+        let stencil = Stencil {
+            inner: unsafe { DecodeStencilOffThread(fc.0, data.as_ptr(), data.len(), &mut result) },
+        };
+        TranscodeError::check(result)?;
+        if stencil.is_null() {
+            return Err(TranscodeError::Error);
+        }
+        Ok(stencil)
+    }
+
     pub fn is_null(&self) -> bool {
         self.inner.mRawPtr.is_null()
     }
 }
 
+unsafe extern "C" fn append_encoded_bytes(data: *mut c_void, bytes: *const u8, length: usize) {
+    let data = &mut *(data as *mut Vec<u8>);
+    data.extend_from_slice(slice::from_raw_parts(bytes, length));
+}
+
+/// Round `offset` up to the alignment SpiderMonkey requires of encoded stencils.
+fn align_transcoding_offset(offset: usize) -> usize {
+    offset.next_multiple_of(4)
+}
+
+/// The build ID that [`Stencil::encode`] tags its output with. It identifies
+/// the SpiderMonkey and mozjs versions, and the pointer size and endianness.
+///
+/// The first call installs the process-wide `JS::BuildIdOp`, replacing any
+/// set by the embedding.
+pub fn transcoding_build_id() -> Vec<u8> {
+    static BUILD_ID: OnceLock<CString> = OnceLock::new();
+    BUILD_ID.get_or_init(|| {
+        let version = unsafe { CStr::from_ptr(JS_GetImplementationVersion()) };
+        let build_id = CString::new(format!(
+            "{}-mozjs-{}",
+            version.to_string_lossy(),
+            env!("CARGO_PKG_VERSION")
+        ))
+        .unwrap();
+        // The string's buffer does not move when it is stored in BUILD_ID.
+        unsafe { SetTranscodingBuildId(build_id.as_ptr()) };
+        build_id
+    });
+
+    unsafe {
+        let length = GetScriptTranscodingBuildId(ptr::null_mut(), 0);
+        assert!(length > 0, "Failed to get the transcoding build ID");
+        let mut build_id = vec![0u8; length];
+        GetScriptTranscodingBuildId(build_id.as_mut_ptr() as *mut _, length);
+        build_id
+    }
+}
+
+/// Why a stencil could not be encoded or decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranscodeError {
+    /// The data was encoded by a different build.
+    BadBuildId,
+    /// The stencil contains asm.js, which cannot be encoded.
+    AsmJSNotSupported,
+    /// The data is corrupt.
+    BadDecode,
+    /// SpiderMonkey failed to transcode the stencil without saying why.
+    Failure,
+    /// Another error, such as running out of memory.
+    Error,
+}
+
+impl TranscodeError {
+    fn check(result: TranscodeResult) -> Result<(), TranscodeError> {
+        match result {
+            TranscodeResult::Ok => Ok(()),
+            TranscodeResult::Failure_BadBuildId => Err(TranscodeError::BadBuildId),
+            TranscodeResult::Failure_AsmJSNotSupported => Err(TranscodeError::AsmJSNotSupported),
+            TranscodeResult::Failure_BadDecode => Err(TranscodeError::BadDecode),
+            TranscodeResult::Failure => Err(TranscodeError::Failure),
+            TranscodeResult::Throw => Err(TranscodeError::Error),
+        }
+    }
+}
+
+impl fmt::Display for TranscodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TranscodeError::BadBuildId => "the stencil was encoded by a different build",
+            TranscodeError::AsmJSNotSupported => "stencils containing asm.js cannot be encoded",
+            TranscodeError::BadDecode => "the encoded stencil is corrupt",
+            TranscodeError::Failure => "the stencil could not be transcoded",
+            TranscodeError::Error => "failed to transcode the stencil",
+        })
+    }
+}
+
+impl std::error::Error for TranscodeError {}
+
 // ___________________________________________________________________________
 // Fast inline converters
 
@@ -803,7 +1415,7 @@ impl Deref for IdVector {
 ///
 /// # Failures
 ///
-/// Returns `Err` on JSAPI failure.
+/// Returns the exception that was thrown on JSAPI failure.
 ///
 /// # Panics
 ///
@@ -817,7 +1429,7 @@ pub unsafe fn define_methods(
     cx: *mut JSContext,
     obj: HandleObject,
     methods: &'static [JSFunctionSpec],
-) -> Result<(), ()> {
+) -> Result<(), Exception> {
     assert!({
         match methods.last() {
             Some(&JSFunctionSpec {
@@ -837,7 +1449,11 @@ pub unsafe fn define_methods(
         }
     });
 
-    JS_DefineFunctions(cx, obj.into(), methods.as_ptr()).to_result()
+    if JS_DefineFunctions(cx, obj.into(), methods.as_ptr()) {
+        Ok(())
+    } else {
+        Err(Exception::steal(cx))
+    }
 }
 
 /// Defines attributes on `obj`. The last entry of `properties` must contain
@@ -845,7 +1461,7 @@ pub unsafe fn define_methods(
 ///
 /// # Failures
 ///
-/// Returns `Err` on JSAPI failure.
+/// Returns the exception that was thrown on JSAPI failure.
 ///
 /// # Panics
 ///
@@ -859,7 +1475,7 @@ pub unsafe fn define_properties(
     cx: *mut JSContext,
     obj: HandleObject,
     properties: &'static [JSPropertySpec],
-) -> Result<(), ()> {
+) -> Result<(), Exception> {
     assert!({
         match properties.last() {
             Some(spec) => spec.is_zeroed(),
@@ -867,7 +1483,11 @@ pub unsafe fn define_properties(
         }
     });
 
-    JS_DefineProperties(cx, obj.into(), properties.as_ptr()).to_result()
+    if JS_DefineProperties(cx, obj.into(), properties.as_ptr()) {
+        Ok(())
+    } else {
+        Err(Exception::steal(cx))
+    }
 }
 
 static SIMPLE_GLOBAL_CLASS_OPS: JSClassOps = JSClassOps {
@@ -0,0 +1,1180 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Conversions between JS values and types implementing serde's
+//! `Serialize` and `Deserialize`.
+//!
+//! [`to_value`] maps Rust data to JS values as follows, and [`from_value`]
+//! accepts the same shapes:
+//!
+//! * numbers, booleans, characters and strings map to the matching
+//!   primitives; 64-bit integers must be within `Number.MAX_SAFE_INTEGER`.
+//! * `None` maps to `null` and `()` to `undefined`; `null` and `undefined`
+//!   both read back as `None`.
+//! * sequences and tuples map to arrays, and byte buffers to `Uint8Array`s.
+//! * structs map to plain objects.
+//! * maps map to plain objects when all their keys serialize to strings, and
+//!   to `Map` objects otherwise.
+//! * enums are externally tagged: unit variants map to their name, and other
+//!   variants to an object with a single property named after the variant.
+//!
+//! Errors carry the path of the property that failed, e.g.
+//! `servers[1].port: invalid type: string "80", expected u16`.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Config { name: String, port: u16 }
+//!
+//! mozjs::serde::to_value(cx, &config, value.handle_mut())?;
+//! let config: Config = mozjs::serde::from_value(cx, value.handle())?;
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ptr::{self, NonNull};
+
+use ::serde::de::value::{SeqDeserializer, StringDeserializer};
+use ::serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Unexpected, Visitor};
+use ::serde::forward_to_deserialize_any;
+use ::serde::ser::{self, Serialize};
+
+use crate::context::JSContext;
+use crate::conversions::{jsstr_to_string, ToJSValConvertible};
+use crate::gc::RootedTraceableBox;
+use crate::jsapi;
+use crate::jsapi::JS_GetFunctionObject;
+use crate::jsapi::{jsid, CallArgs, Heap, JSObject, Value, JSITER_OWNONLY, JSPROP_ENUMERATE};
+use crate::jsval::{BooleanValue, DoubleValue, Int32Value, JSVal, NullValue, ObjectValue};
+use crate::jsval::{UInt32Value, UndefinedValue};
+use crate::panic::{maybe_resume_unwind, wrap_panic};
+use crate::rooted;
+use crate::rust::wrappers2::{
+    GetArrayLength, GetPropertyKeys, IsArrayObject1, IsMapObject, JS_DefineElement,
+    JS_DefinePropertyById2, JS_GetElement, JS_GetPropertyById, JS_NewFunction, JS_NewPlainObject,
+    JS_SetElement, JS_ValueToId, MapForEach, MapSet, NewArrayObject1, NewMapObject,
+};
+use crate::rust::{Handle, HandleObject, HandleValue, IdVector, MutableHandleValue};
+use crate::typedarray::{ArrayBuffer, CreateWith, Uint8Array};
+
+/// The largest integer a JS number represents exactly.
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// Convert `value` to a JS value in the current realm.
+pub fn to_value<T: Serialize + ?Sized>(
+    cx: &mut JSContext,
+    value: &T,
+    mut rval: MutableHandleValue,
+) -> Result<(), Error> {
+    rval.set(value.serialize(Serializer { cx })?);
+    Ok(())
+}
+
+/// Convert the JS value `value` to a `T`.
+pub fn from_value<T: DeserializeOwned>(cx: &mut JSContext, value: HandleValue) -> Result<T, Error> {
+    T::deserialize(Deserializer { cx, value })
+}
+
+/// A failed conversion.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    message: String,
+    /// The path to the failing property, innermost segment first.
+    path: Vec<PathSegment>,
+    exception: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum PathSegment {
+    Property(String),
+    Index(usize),
+}
+
+impl Error {
+    /// A JSAPI call failed, leaving an exception pending.
+    fn exception() -> Error {
+        Error {
+            message: "JavaScript exception".to_owned(),
+            path: vec![],
+            exception: true,
+        }
+    }
+
+    fn at(mut self, segment: PathSegment) -> Error {
+        self.path.push(segment);
+        self
+    }
+
+    /// The reason the conversion failed.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The path to the property that failed to convert, such as
+    /// `servers[1].port`, or an empty string for the value itself.
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in self.path.iter().rev() {
+            match segment {
+                PathSegment::Property(name) if path.is_empty() => path.push_str(name),
+                PathSegment::Property(name) => {
+                    path.push('.');
+                    path.push_str(name);
+                }
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+
+    /// Whether the conversion failed because of a JS exception, such as a
+    /// throwing getter, which is left pending on the context.
+    pub fn is_exception(&self) -> bool {
+        self.exception
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", self.path(), self.message)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Error {
+        Error {
+            message: message.to_string(),
+            path: vec![],
+            exception: false,
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Error {
+        <Error as ser::Error>::custom(message)
+    }
+}
+
+fn check(ok: bool) -> Result<(), Error> {
+    if ok {
+        Ok(())
+    } else {
+        Err(Error::exception())
+    }
+}
+
+fn string_value(cx: &mut JSContext, string: &str) -> JSVal {
+    rooted!(&in(cx) let mut value = UndefinedValue());
+    unsafe { string.to_jsval(cx.raw_cx(), value.handle_mut()) };
+    value.get()
+}
+
+fn js_string(cx: &mut JSContext, value: HandleValue) -> String {
+    unsafe { jsstr_to_string(cx.raw_cx(), NonNull::new(value.to_string()).unwrap()) }
+}
+
+fn safe_integer(value: f64) -> Result<JSVal, Error> {
+    if value.abs() <= MAX_SAFE_INTEGER {
+        Ok(DoubleValue(value))
+    } else {
+        Err(<Error as ser::Error>::custom(format_args!(
+            "{} cannot be represented exactly by a JS number",
+            value
+        )))
+    }
+}
+
+/// Define the enumerable property `name` of `obj` to `value`.
+fn define_property(
+    cx: &mut JSContext,
+    obj: HandleObject,
+    name: HandleValue,
+    value: HandleValue,
+) -> Result<(), Error> {
+    rooted!(&in(cx) let mut id: jsid);
+    unsafe {
+        check(JS_ValueToId(cx, name, id.handle_mut()))?;
+        check(JS_DefinePropertyById2(
+            cx,
+            obj,
+            id.handle(),
+            value,
+            JSPROP_ENUMERATE as u32,
+        ))
+    }
+}
+
+fn get_property(
+    cx: &mut JSContext,
+    obj: HandleObject,
+    name: &str,
+    rval: MutableHandleValue,
+) -> Result<(), Error> {
+    rooted!(&in(cx) let name = string_value(cx, name));
+    rooted!(&in(cx) let mut id: jsid);
+    unsafe {
+        check(JS_ValueToId(cx, name.handle(), id.handle_mut()))?;
+        check(JS_GetPropertyById(cx, obj, id.handle(), rval))
+    }
+}
+
+/// Wrap `value` in an object with a single property named `variant`.
+fn tag_variant(cx: &mut JSContext, variant: &str, value: JSVal) -> Result<JSVal, Error> {
+    rooted!(&in(cx) let value = value);
+    rooted!(&in(cx) let obj = unsafe { JS_NewPlainObject(cx) });
+    check(!obj.get().is_null())?;
+    rooted!(&in(cx) let name = string_value(cx, variant));
+    define_property(cx, obj.handle(), name.handle(), value.handle())?;
+    Ok(ObjectValue(obj.get()))
+}
+
+fn new_array(cx: &mut JSContext) -> Result<RootedTraceableBox<Heap<*mut JSObject>>, Error> {
+    let array = unsafe { NewArrayObject1(cx, 0) };
+    check(!array.is_null())?;
+    Ok(RootedTraceableBox::from_box(Heap::boxed(array)))
+}
+
+fn new_object(cx: &mut JSContext) -> Result<RootedTraceableBox<Heap<*mut JSObject>>, Error> {
+    let obj = unsafe { JS_NewPlainObject(cx) };
+    check(!obj.is_null())?;
+    Ok(RootedTraceableBox::from_box(Heap::boxed(obj)))
+}
+
+/// Serializes into JS values. The returned values are not rooted, so callers
+/// must root them before calling into the engine again.
+struct Serializer<'a> {
+    cx: &'a mut JSContext,
+}
+
+impl<'a> ser::Serializer for Serializer<'a> {
+    type Ok = JSVal;
+    type Error = Error;
+    type SerializeSeq = ArraySerializer<'a>;
+    type SerializeTuple = ArraySerializer<'a>;
+    type SerializeTupleStruct = ArraySerializer<'a>;
+    type SerializeTupleVariant = ArraySerializer<'a>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = ObjectSerializer<'a>;
+    type SerializeStructVariant = ObjectSerializer<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<JSVal, Error> {
+        Ok(BooleanValue(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<JSVal, Error> {
+        Ok(Int32Value(v as i32))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<JSVal, Error> {
+        Ok(Int32Value(v as i32))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<JSVal, Error> {
+        Ok(Int32Value(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<JSVal, Error> {
+        safe_integer(v as f64)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<JSVal, Error> {
+        Ok(Int32Value(v as i32))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<JSVal, Error> {
+        Ok(Int32Value(v as i32))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<JSVal, Error> {
+        Ok(UInt32Value(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<JSVal, Error> {
+        safe_integer(v as f64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<JSVal, Error> {
+        Ok(DoubleValue(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<JSVal, Error> {
+        Ok(DoubleValue(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<JSVal, Error> {
+        Ok(string_value(self.cx, v.encode_utf8(&mut [0; 4])))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<JSVal, Error> {
+        Ok(string_value(self.cx, v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<JSVal, Error> {
+        rooted!(&in(self.cx) let mut array = ptr::null_mut::<JSObject>());
+        unsafe {
+            if Uint8Array::create(self.cx.raw_cx(), CreateWith::Slice(v), array.handle_mut())
+                .is_err()
+            {
+                return Err(Error::exception());
+            }
+        }
+        Ok(ObjectValue(array.get()))
+    }
+
+    fn serialize_none(self) -> Result<JSVal, Error> {
+        Ok(NullValue())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<JSVal, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<JSVal, Error> {
+        Ok(UndefinedValue())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<JSVal, Error> {
+        Ok(UndefinedValue())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<JSVal, Error> {
+        Ok(string_value(self.cx, variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<JSVal, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<JSVal, Error> {
+        let value = value
+            .serialize(Serializer { cx: &mut *self.cx })
+            .map_err(|e| e.at(PathSegment::Property(variant.to_owned())))?;
+        tag_variant(self.cx, variant, value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<ArraySerializer<'a>, Error> {
+        let array = new_array(self.cx)?;
+        Ok(ArraySerializer {
+            cx: self.cx,
+            array,
+            length: 0,
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ArraySerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<ArraySerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ArraySerializer<'a>, Error> {
+        let mut serializer = self.serialize_seq(Some(len))?;
+        serializer.variant = Some(variant);
+        Ok(serializer)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer<'a>, Error> {
+        Ok(MapSerializer {
+            cx: self.cx,
+            entries: RootedTraceableBox::new(vec![]),
+            string_keys: true,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<ObjectSerializer<'a>, Error> {
+        let object = new_object(self.cx)?;
+        Ok(ObjectSerializer {
+            cx: self.cx,
+            object,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ObjectSerializer<'a>, Error> {
+        let mut serializer = self.serialize_struct(name, len)?;
+        serializer.variant = Some(variant);
+        Ok(serializer)
+    }
+}
+
+struct ArraySerializer<'a> {
+    cx: &'a mut JSContext,
+    array: RootedTraceableBox<Heap<*mut JSObject>>,
+    length: u32,
+    variant: Option<&'static str>,
+}
+
+impl ArraySerializer<'_> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let index = self.length;
+        let value = value
+            .serialize(Serializer { cx: &mut *self.cx })
+            .map_err(|e| e.at(PathSegment::Index(index as usize)))?;
+        rooted!(&in(self.cx) let value = value);
+        check(unsafe {
+            JS_DefineElement(
+                self.cx,
+                self.array.handle(),
+                index,
+                value.handle(),
+                JSPROP_ENUMERATE as u32,
+            )
+        })?;
+        self.length += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<JSVal, Error> {
+        let value = ObjectValue(self.array.get());
+        match self.variant {
+            Some(variant) => tag_variant(self.cx, variant, value),
+            None => Ok(value),
+        }
+    }
+}
+
+impl ser::SerializeSeq for ArraySerializer<'_> {
+    type Ok = JSVal;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JSVal, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ArraySerializer<'_> {
+    type Ok = JSVal;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JSVal, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ArraySerializer<'_> {
+    type Ok = JSVal;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JSVal, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ArraySerializer<'_> {
+    type Ok = JSVal;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JSVal, Error> {
+        self.finish()
+    }
+}
+
+/// Collects the entries of a map, to decide between an object and a `Map`
+/// once all the keys are known.
+struct MapSerializer<'a> {
+    cx: &'a mut JSContext,
+    /// Keys and values, alternating.
+    entries: RootedTraceableBox<Vec<Box<Heap<JSVal>>>>,
+    string_keys: bool,
+}
+
+impl ser::SerializeMap for MapSerializer<'_> {
+    type Ok = JSVal;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        let key = key.serialize(Serializer { cx: &mut *self.cx })?;
+        self.string_keys &= key.is_string();
+        self.entries.push(Heap::boxed(key));
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        match value.serialize(Serializer { cx: &mut *self.cx }) {
+            Ok(value) => {
+                self.entries.push(Heap::boxed(value));
+                Ok(())
+            }
+            Err(error) => {
+                let index = self.entries.len() / 2;
+                rooted!(&in(self.cx) let key = self.entries[index * 2].get());
+                let segment = if key.is_string() {
+                    PathSegment::Property(js_string(self.cx, key.handle()))
+                } else {
+                    PathSegment::Index(index)
+                };
+                Err(error.at(segment))
+            }
+        }
+    }
+
+    fn end(self) -> Result<JSVal, Error> {
+        let cx = self.cx;
+        rooted!(&in(cx) let obj = unsafe {
+            if self.string_keys {
+                JS_NewPlainObject(cx)
+            } else {
+                NewMapObject(cx)
+            }
+        });
+        check(!obj.get().is_null())?;
+        for entry in self.entries.chunks(2) {
+            rooted!(&in(cx) let key = entry[0].get());
+            rooted!(&in(cx) let value = entry[1].get());
+            if self.string_keys {
+                define_property(cx, obj.handle(), key.handle(), value.handle())?;
+            } else {
+                check(unsafe { MapSet(cx, obj.handle(), key.handle(), value.handle()) })?;
+            }
+        }
+        Ok(ObjectValue(obj.get()))
+    }
+}
+
+struct ObjectSerializer<'a> {
+    cx: &'a mut JSContext,
+    object: RootedTraceableBox<Heap<*mut JSObject>>,
+    variant: Option<&'static str>,
+}
+
+impl ObjectSerializer<'_> {
+    fn field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        let value = value
+            .serialize(Serializer { cx: &mut *self.cx })
+            .map_err(|e| e.at(PathSegment::Property(key.to_owned())))?;
+        rooted!(&in(self.cx) let value = value);
+        rooted!(&in(self.cx) let name = string_value(self.cx, key));
+        define_property(self.cx, self.object.handle(), name.handle(), value.handle())
+    }
+
+    fn finish(self) -> Result<JSVal, Error> {
+        let value = ObjectValue(self.object.get());
+        match self.variant {
+            Some(variant) => tag_variant(self.cx, variant, value),
+            None => Ok(value),
+        }
+    }
+}
+
+impl ser::SerializeStruct for ObjectSerializer<'_> {
+    type Ok = JSVal;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<JSVal, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for ObjectSerializer<'_> {
+    type Ok = JSVal;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<JSVal, Error> {
+        self.finish()
+    }
+}
+
+/// The error for `value` not having the type `expected` asks for. Strings are
+/// quoted in the message, as serde's own visitors do.
+fn invalid_type(cx: &mut JSContext, value: HandleValue, expected: &dyn de::Expected) -> Error {
+    if value.is_string() {
+        let string = js_string(cx, value);
+        return de::Error::invalid_type(Unexpected::Str(&string), expected);
+    }
+    de::Error::invalid_type(unexpected(&value.get()), expected)
+}
+
+fn unexpected(value: &JSVal) -> Unexpected<'static> {
+    if value.is_undefined() {
+        Unexpected::Other("undefined")
+    } else if value.is_null() {
+        Unexpected::Other("null")
+    } else if value.is_boolean() {
+        Unexpected::Bool(value.to_boolean())
+    } else if value.is_number() {
+        Unexpected::Float(value.to_number())
+    } else if value.is_symbol() {
+        Unexpected::Other("symbol")
+    } else if value.is_bigint() {
+        Unexpected::Other("BigInt")
+    } else {
+        Unexpected::Other("object")
+    }
+}
+
+/// The names of the own enumerable string-keyed properties of `obj`.
+fn property_names(cx: &mut JSContext, obj: HandleObject) -> Result<Vec<String>, Error> {
+    let mut ids = unsafe { IdVector::new(cx.raw_cx()) };
+    check(unsafe { GetPropertyKeys(cx, obj, JSITER_OWNONLY, ids.handle_mut()) })?;
+    let mut names = Vec::with_capacity(ids.len());
+    for id in ids.iter() {
+        if id.is_string() {
+            let string = NonNull::new(id.to_string()).unwrap();
+            names.push(unsafe { jsstr_to_string(cx.raw_cx(), string) });
+        } else if id.is_int() {
+            names.push(id.to_int().to_string());
+        }
+    }
+    Ok(names)
+}
+
+unsafe extern "C" fn push_map_entry(cx: *mut jsapi::JSContext, argc: u32, vp: *mut Value) -> bool {
+    let mut result = false;
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        let args = CallArgs::from_vp(vp, argc);
+        rooted!(&in(cx) let entries = args.thisv().to_object());
+        let mut length = 0;
+        // Map.prototype.forEach passes the value first.
+        result = GetArrayLength(&mut cx, entries.handle(), &mut length)
+            && JS_SetElement(
+                &mut cx,
+                entries.handle(),
+                length,
+                Handle::from_raw(args.get(1)),
+            )
+            && JS_SetElement(
+                &mut cx,
+                entries.handle(),
+                length + 1,
+                Handle::from_raw(args.get(0)),
+            );
+    });
+    result
+}
+
+/// The keys and values of the `Map` `map`, alternating in an array.
+fn map_entries(
+    cx: &mut JSContext,
+    map: HandleObject,
+) -> Result<RootedTraceableBox<Heap<*mut JSObject>>, Error> {
+    let entries = new_array(cx)?;
+    unsafe {
+        let push = JS_NewFunction(cx, Some(push_map_entry), 2, 0, c"push".as_ptr());
+        check(!push.is_null())?;
+        rooted!(&in(cx) let push = ObjectValue(JS_GetFunctionObject(push)));
+        rooted!(&in(cx) let this = ObjectValue(entries.get()));
+        let ok = MapForEach(cx, map, push.handle(), this.handle());
+        maybe_resume_unwind();
+        check(ok)?;
+    }
+    Ok(entries)
+}
+
+struct Deserializer<'a> {
+    cx: &'a mut JSContext,
+    value: HandleValue<'a>,
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let value = self.value.get();
+        if value.is_null_or_undefined() {
+            visitor.visit_unit()
+        } else if value.is_boolean() {
+            visitor.visit_bool(value.to_boolean())
+        } else if value.is_int32() {
+            visitor.visit_i32(value.to_int32())
+        } else if value.is_double() {
+            let number = value.to_double();
+            if number.fract() == 0.0 && number.abs() <= MAX_SAFE_INTEGER {
+                if number < 0.0 {
+                    visitor.visit_i64(number as i64)
+                } else {
+                    visitor.visit_u64(number as u64)
+                }
+            } else {
+                visitor.visit_f64(number)
+            }
+        } else if value.is_string() {
+            visitor.visit_string(js_string(self.cx, self.value))
+        } else if value.is_object() {
+            let cx = self.cx;
+            rooted!(&in(cx) let obj = value.to_object());
+            let (mut is_array, mut is_map) = (false, false);
+            unsafe {
+                check(IsArrayObject1(cx, obj.handle(), &mut is_array))?;
+                if !is_array {
+                    check(IsMapObject(cx, obj.handle(), &mut is_map))?;
+                }
+            }
+            if is_array {
+                let mut length = 0;
+                check(unsafe { GetArrayLength(cx, obj.handle(), &mut length) })?;
+                visitor.visit_seq(ArrayAccess {
+                    cx,
+                    array: obj.handle(),
+                    index: 0,
+                    length,
+                })
+            } else if let Ok(bytes) = Uint8Array::from(obj.get()) {
+                visitor.visit_seq(SeqDeserializer::new(bytes.to_vec().into_iter()))
+            } else if is_map {
+                let entries = map_entries(cx, obj.handle())?;
+                let mut length = 0;
+                check(unsafe { GetArrayLength(cx, entries.handle(), &mut length) })?;
+                visitor.visit_map(MapAccess {
+                    cx,
+                    entries: entries.handle(),
+                    index: 0,
+                    length,
+                    value: RootedTraceableBox::new(Heap::default()),
+                    name: None,
+                })
+            } else {
+                let names = property_names(cx, obj.handle())?;
+                visitor.visit_map(ObjectAccess {
+                    cx,
+                    object: obj.handle(),
+                    names: names.into_iter().map(Cow::Owned).collect(),
+                    index: 0,
+                    skip_undefined: false,
+                    value: RootedTraceableBox::new(Heap::default()),
+                })
+            }
+        } else {
+            Err(invalid_type(self.cx, self.value, &visitor))
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.get().is_null_or_undefined() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let value = self.value.get();
+        if value.is_object() {
+            let obj = value.to_object();
+            if let Ok(bytes) = Uint8Array::from(obj) {
+                return visitor.visit_byte_buf(bytes.to_vec());
+            }
+            if let Ok(bytes) = ArrayBuffer::from(obj) {
+                return visitor.visit_byte_buf(bytes.to_vec());
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let value = self.value.get();
+        if !value.is_object() {
+            return Err(invalid_type(self.cx, self.value, &visitor));
+        }
+        rooted!(&in(self.cx) let obj = value.to_object());
+        visitor.visit_map(ObjectAccess {
+            cx: self.cx,
+            object: obj.handle(),
+            names: fields.iter().map(|field| Cow::Borrowed(*field)).collect(),
+            index: 0,
+            skip_undefined: true,
+            value: RootedTraceableBox::new(Heap::default()),
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let value = self.value.get();
+        if value.is_string() {
+            let variant: StringDeserializer<Error> =
+                js_string(self.cx, self.value).into_deserializer();
+            return visitor.visit_enum(variant);
+        }
+        if !value.is_object() {
+            return Err(invalid_type(self.cx, self.value, &visitor));
+        }
+        let cx = self.cx;
+        rooted!(&in(cx) let obj = value.to_object());
+        let names = property_names(cx, obj.handle())?;
+        let [variant] = &names[..] else {
+            return Err(de::Error::invalid_value(
+                Unexpected::Map,
+                &"an object with a single property",
+            ));
+        };
+        rooted!(&in(cx) let mut content = UndefinedValue());
+        get_property(cx, obj.handle(), variant, content.handle_mut())?;
+        visitor
+            .visit_enum(EnumAccess {
+                cx,
+                variant: variant.clone(),
+                value: content.handle(),
+            })
+            .map_err(|e| e.at(PathSegment::Property(variant.clone())))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit
+        unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+struct ArrayAccess<'a> {
+    cx: &'a mut JSContext,
+    array: HandleObject<'a>,
+    index: u32,
+    length: u32,
+}
+
+impl<'de> de::SeqAccess<'de> for ArrayAccess<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.index == self.length {
+            return Ok(None);
+        }
+        let index = self.index;
+        self.index += 1;
+        rooted!(&in(self.cx) let mut element = UndefinedValue());
+        check(unsafe { JS_GetElement(self.cx, self.array, index, element.handle_mut()) })?;
+        seed.deserialize(Deserializer {
+            cx: &mut *self.cx,
+            value: element.handle(),
+        })
+        .map(Some)
+        .map_err(|e| e.at(PathSegment::Index(index as usize)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.length - self.index) as usize)
+    }
+}
+
+/// Reads the properties `names` of an object.
+struct ObjectAccess<'a> {
+    cx: &'a mut JSContext,
+    object: HandleObject<'a>,
+    names: Vec<Cow<'static, str>>,
+    index: usize,
+    /// Whether to skip properties whose value is `undefined`, so that they
+    /// read as missing struct fields.
+    skip_undefined: bool,
+    /// The value of the property returned by the last `next_key_seed`.
+    value: RootedTraceableBox<Heap<JSVal>>,
+}
+
+impl<'de> de::MapAccess<'de> for ObjectAccess<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        while let Some(name) = self.names.get(self.index) {
+            self.index += 1;
+            rooted!(&in(self.cx) let mut value = UndefinedValue());
+            get_property(self.cx, self.object, name, value.handle_mut())?;
+            if self.skip_undefined && value.get().is_undefined() {
+                continue;
+            }
+            self.value.set(value.get());
+            return seed
+                .deserialize(KeyDeserializer {
+                    key: name.clone().into_owned(),
+                })
+                .map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let name = &self.names[self.index - 1];
+        seed.deserialize(Deserializer {
+            cx: &mut *self.cx,
+            value: self.value.handle(),
+        })
+        .map_err(|e| e.at(PathSegment::Property(name.clone().into_owned())))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.names.len() - self.index)
+    }
+}
+
+/// Reads the entries of a `Map`, collected by [`map_entries`].
+struct MapAccess<'a> {
+    cx: &'a mut JSContext,
+    entries: HandleObject<'a>,
+    index: u32,
+    length: u32,
+    /// The value of the entry returned by the last `next_key_seed`.
+    value: RootedTraceableBox<Heap<JSVal>>,
+    /// The key of that entry, if it is a string.
+    name: Option<String>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.index == self.length {
+            return Ok(None);
+        }
+        let index = self.index;
+        self.index += 2;
+        rooted!(&in(self.cx) let mut key = UndefinedValue());
+        rooted!(&in(self.cx) let mut value = UndefinedValue());
+        unsafe {
+            check(JS_GetElement(
+                self.cx,
+                self.entries,
+                index,
+                key.handle_mut(),
+            ))?;
+            check(JS_GetElement(
+                self.cx,
+                self.entries,
+                index + 1,
+                value.handle_mut(),
+            ))?;
+        }
+        self.value.set(value.get());
+        self.name = key
+            .get()
+            .is_string()
+            .then(|| js_string(self.cx, key.handle()));
+        seed.deserialize(Deserializer {
+            cx: &mut *self.cx,
+            value: key.handle(),
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let segment = match self.name.take() {
+            Some(name) => PathSegment::Property(name),
+            None => PathSegment::Index((self.index / 2 - 1) as usize),
+        };
+        seed.deserialize(Deserializer {
+            cx: &mut *self.cx,
+            value: self.value.handle(),
+        })
+        .map_err(|e| e.at(segment))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(((self.length - self.index) / 2) as usize)
+    }
+}
+
+struct EnumAccess<'a> {
+    cx: &'a mut JSContext,
+    variant: String,
+    value: HandleValue<'a>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for EnumAccess<'a> {
+    type Error = Error;
+    type Variant = Deserializer<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Deserializer<'a>), Error> {
+        let variant = seed.deserialize(KeyDeserializer { key: self.variant })?;
+        Ok((
+            variant,
+            Deserializer {
+                cx: self.cx,
+                value: self.value,
+            },
+        ))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+/// Deserializes a property name, which is always a string in JS, parsing
+/// it for maps with numeric or boolean keys.
+struct KeyDeserializer {
+    key: String,
+}
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.key.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => visitor.visit_string(self.key),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.key)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let variant: StringDeserializer<Error> = self.key.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
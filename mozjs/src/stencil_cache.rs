@@ -0,0 +1,134 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Persistent caches of compiled scripts.
+//!
+//! A [`StencilCache`] stores the bytes produced by [`Stencil::encode`] so
+//! that later runs can skip parsing with [`Stencil::decode`]. Entries are
+//! keyed by a SHA-256 hash of the source, the compile options and the
+//! [transcoding build ID](crate::rust::transcoding_build_id), so entries
+//! written by another build are recompiled rather than used.
+//!
+//! ```ignore
+//! let cache = DirectoryCache::new("cache")?;
+//! let options = StencilOptions::new(c"bundle.js".to_owned(), 1);
+//! let stencil = cache.get_or_compile(cx, &source, &options)?;
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use log::warn;
+use sha2::{Digest, Sha256};
+
+use crate::context::JSContext;
+use crate::rust::wrappers2::JS_ClearPendingException;
+use crate::rust::{transcoding_build_id, CompileError, Stencil, StencilOptions, TranscodeError};
+
+/// Storage for encoded stencils, keyed by [`cache_key`].
+pub trait StencilCache {
+    /// The bytes stored under `key`, if any.
+    fn load(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Store `bytes` under `key`, replacing any previous entry.
+    fn store(&self, key: &str, bytes: &[u8]) -> io::Result<()>;
+
+    /// Decode the cached stencil for `source`, or compile it and cache the
+    /// result. Entries that fail to decode, for example because they were
+    /// written by another build, are replaced.
+    fn get_or_compile(
+        &self,
+        cx: &mut JSContext,
+        source: &str,
+        options: &StencilOptions,
+    ) -> Result<Stencil, CompileError> {
+        let key = cache_key(source, options);
+        if let Some(bytes) = self.load(&key) {
+            match Stencil::decode(&bytes) {
+                Ok(stencil) => return Ok(stencil),
+                Err(error @ (TranscodeError::BadBuildId | TranscodeError::BadDecode)) => {
+                    warn!("Discarding cached stencil {}: {}", key, error)
+                }
+                Err(error) => warn!("Failed to decode cached stencil {}: {}", key, error),
+            }
+        }
+
+        let stencil = Stencil::compile(source, options)?;
+        match stencil.encode(cx) {
+            Ok(bytes) => {
+                if let Err(error) = self.store(&key, &bytes) {
+                    warn!("Failed to cache stencil {}: {}", key, error);
+                }
+            }
+            Err(error) => {
+                warn!("Failed to encode stencil {}: {}", key, error);
+                if error == TranscodeError::Error {
+                    unsafe { JS_ClearPendingException(cx) };
+                }
+            }
+        }
+        Ok(stencil)
+    }
+}
+
+/// The key identifying `source` compiled with `options` by this build: the
+/// hex-encoded SHA-256 hash of the three.
+pub fn cache_key(source: &str, options: &StencilOptions) -> String {
+    let mut hasher = Sha256::new();
+    // Prefix the variable-length fields with their length, so that no two
+    // inputs hash the same bytes.
+    for field in [
+        &transcoding_build_id()[..],
+        options.filename().to_bytes(),
+        source.as_bytes(),
+    ] {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field);
+    }
+    hasher.update(options.line().to_le_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// A [`StencilCache`] keeping one file per entry in a directory.
+pub struct DirectoryCache {
+    dir: PathBuf,
+}
+
+impl DirectoryCache {
+    /// Use `dir` as the cache, creating it if needed.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<DirectoryCache> {
+        let dir = dir.as_ref().to_owned();
+        fs::create_dir_all(&dir)?;
+        Ok(DirectoryCache { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.stencil", key))
+    }
+}
+
+impl StencilCache for DirectoryCache {
+    fn load(&self, key: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(key)).ok()
+    }
+
+    fn store(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        // Write to a temporary file first, so concurrent readers never see a
+        // partial entry. Every call uses its own file, so concurrent writers
+        // of the same entry don't interleave either.
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let path = self.path(key);
+        let write = WRITES.fetch_add(1, Ordering::Relaxed);
+        let temporary = path.with_extension(format!("{}.{}.tmp", process::id(), write));
+        fs::write(&temporary, bytes)?;
+        fs::rename(&temporary, &path)
+    }
+}
@@ -0,0 +1,348 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The structured clone algorithm.
+//!
+//! [`write`] serializes a value into a [`StructuredCloneBuffer`] and [`read`]
+//! creates a copy of it in the current realm, possibly in another runtime.
+//! ArrayBuffers listed in `transfer` are moved rather than copied, and
+//! objects the engine does not know how to clone can be handled by a
+//! [`HostObjects`] implementation under tags of its own.
+//!
+//! ```ignore
+//! let buffer = structured_clone::write(cx, value.handle(), scope, HandleValue::undefined())?;
+//! let bytes = buffer.to_bytes();
+//! // ... later, or elsewhere ...
+//! let buffer = StructuredCloneBuffer::from_bytes(&bytes);
+//! structured_clone::read(cx, &buffer, copy.handle_mut())?;
+//! ```
+
+use std::ffi::c_void;
+use std::ptr::{self, NonNull};
+
+use crate::context::JSContext;
+use crate::error::Exception;
+use crate::glue::{
+    CopyJSStructuredCloneData, GetLengthOfJSStructuredCloneData, WriteBytesToJSStructuredCloneData,
+};
+use crate::jsapi;
+use crate::jsapi::{CloneDataPolicy, JSObject, JSStructuredCloneCallbacks, JSStructuredCloneData};
+use crate::jsapi::{JSStructuredCloneReader, JSStructuredCloneWriter, StructuredCloneScope};
+use crate::jsapi::{JS_ReadBytes, JS_ReadDouble, JS_ReadUint32Pair};
+use crate::jsapi::{
+    JS_WriteBytes, JS_WriteDouble, JS_WriteUint32Pair, JS_STRUCTURED_CLONE_VERSION,
+};
+use crate::panic::{maybe_resume_unwind, wrap_panic};
+use crate::rust::wrappers2::{JS_ReadStructuredClone, JS_WriteStructuredClone};
+use crate::rust::JSAutoStructuredCloneBufferWrapper;
+use crate::rust::{Handle, HandleObject, HandleValue, MutableHandleValue};
+
+/// The smallest tag available to [`HostObjects`]; smaller tags are used by
+/// the engine.
+pub const SCTAG_USER_MIN: u32 = 0xFFFF_8000;
+
+/// Serializes the host objects the engine cannot clone by itself.
+///
+/// Each object is written as a tag pair, whose tag must be at least
+/// [`SCTAG_USER_MIN`], followed by any data the object needs, and is read
+/// back by [`HostObjects::read`] when that tag is encountered.
+pub trait HostObjects {
+    /// Serialize `obj`. Returns `false` with an exception pending if `obj`
+    /// cannot be cloned.
+    fn write(
+        &self,
+        cx: &mut JSContext,
+        writer: &mut StructuredCloneWriter,
+        obj: HandleObject,
+    ) -> bool;
+
+    /// Create the object serialized with `tag` and `data` by
+    /// [`HostObjects::write`]. Returns null with an exception pending on
+    /// failure, including for unknown tags.
+    fn read(
+        &self,
+        cx: &mut JSContext,
+        reader: &mut StructuredCloneReader,
+        tag: u32,
+        data: u32,
+    ) -> *mut JSObject;
+}
+
+/// The output of a structured clone, passed to [`HostObjects::write`].
+pub struct StructuredCloneWriter {
+    raw: *mut JSStructuredCloneWriter,
+}
+
+impl StructuredCloneWriter {
+    /// Write a tag and a 32-bit value.
+    pub fn write_pair(&mut self, tag: u32, data: u32) -> bool {
+        unsafe { JS_WriteUint32Pair(self.raw, tag, data) }
+    }
+
+    /// Write a double.
+    pub fn write_double(&mut self, value: f64) -> bool {
+        unsafe { JS_WriteDouble(self.raw, value) }
+    }
+
+    /// Write raw bytes, to be read back with
+    /// [`StructuredCloneReader::read_bytes`] into a buffer of the same length.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> bool {
+        unsafe { JS_WriteBytes(self.raw, bytes.as_ptr() as *const c_void, bytes.len()) }
+    }
+}
+
+/// The input of a structured clone, passed to [`HostObjects::read`].
+pub struct StructuredCloneReader {
+    raw: *mut JSStructuredCloneReader,
+}
+
+impl StructuredCloneReader {
+    /// Read a tag and a 32-bit value.
+    pub fn read_pair(&mut self) -> Option<(u32, u32)> {
+        let (mut tag, mut data) = (0, 0);
+        unsafe { JS_ReadUint32Pair(self.raw, &mut tag, &mut data) }.then_some((tag, data))
+    }
+
+    /// Read a double.
+    pub fn read_double(&mut self) -> Option<f64> {
+        let mut value = 0.0;
+        unsafe { JS_ReadDouble(self.raw, &mut value) }.then_some(value)
+    }
+
+    /// Fill `bytes` with bytes written by
+    /// [`StructuredCloneWriter::write_bytes`].
+    pub fn read_bytes(&mut self, bytes: &mut [u8]) -> bool {
+        unsafe { JS_ReadBytes(self.raw, bytes.as_mut_ptr() as *mut c_void, bytes.len()) }
+    }
+}
+
+/// A serialized value.
+///
+/// Buffers written with [`StructuredCloneScope::SameProcess`] may point to
+/// memory owned by the buffer, such as the contents of transferred
+/// ArrayBuffers; they can be sent to another runtime of this process but not
+/// persisted. Use [`StructuredCloneScope::DifferentProcess`] for values
+/// passed through [`StructuredCloneBuffer::to_bytes`].
+pub struct StructuredCloneBuffer {
+    buffer: JSAutoStructuredCloneBufferWrapper,
+    scope: StructuredCloneScope,
+}
+
+// The buffer owns its data, and transferred contents are freed with the
+// system allocator, whichever thread drops it.
+unsafe impl Send for StructuredCloneBuffer {}
+
+impl StructuredCloneBuffer {
+    fn new(scope: StructuredCloneScope) -> StructuredCloneBuffer {
+        StructuredCloneBuffer {
+            buffer: unsafe { JSAutoStructuredCloneBufferWrapper::new(scope, ptr::null()) },
+            scope,
+        }
+    }
+
+    fn data(&self) -> *mut JSStructuredCloneData {
+        unsafe { &mut (*self.buffer.as_raw_ptr()).data_ }
+    }
+
+    /// The scope the value was written for.
+    pub fn scope(&self) -> StructuredCloneScope {
+        self.scope
+    }
+
+    /// Copy the serialized value into a byte vector.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        unsafe {
+            let length = GetLengthOfJSStructuredCloneData(self.data());
+            let mut bytes = Vec::with_capacity(length);
+            CopyJSStructuredCloneData(self.data(), bytes.as_mut_ptr());
+            bytes.set_len(length);
+            bytes
+        }
+    }
+
+    /// A buffer holding bytes produced by [`StructuredCloneBuffer::to_bytes`].
+    ///
+    /// The bytes are read with [`StructuredCloneScope::DifferentProcess`],
+    /// which rejects data containing pointers.
+    pub fn from_bytes(bytes: &[u8]) -> StructuredCloneBuffer {
+        let buffer = StructuredCloneBuffer::new(StructuredCloneScope::DifferentProcess);
+        unsafe {
+            assert!(WriteBytesToJSStructuredCloneData(
+                bytes.as_ptr(),
+                bytes.len(),
+                buffer.data(),
+            ));
+        }
+        buffer
+    }
+}
+
+fn clone_data_policy() -> CloneDataPolicy {
+    CloneDataPolicy {
+        allowIntraClusterClonableSharedObjects_: false,
+        allowSharedMemoryObjects_: false,
+    }
+}
+
+static HOST_CALLBACKS: JSStructuredCloneCallbacks = JSStructuredCloneCallbacks {
+    read: Some(read_host_object),
+    write: Some(write_host_object),
+    reportError: None,
+    readTransfer: None,
+    writeTransfer: None,
+    freeTransfer: None,
+    canTransfer: None,
+    sabCloned: None,
+};
+
+/// The callbacks and closure passing `host` to the engine.
+fn host_callbacks(
+    host: &Option<&dyn HostObjects>,
+) -> (*const JSStructuredCloneCallbacks, *mut c_void) {
+    match host {
+        Some(host) => (
+            &HOST_CALLBACKS,
+            host as *const &dyn HostObjects as *mut c_void,
+        ),
+        None => (ptr::null(), ptr::null_mut()),
+    }
+}
+
+/// Serialize `value` for `scope`, in the realm of `cx`. `transfer` is
+/// undefined or an array of ArrayBuffers, which are detached and whose
+/// contents move to the buffer. Returns the exception on failure, including
+/// for values that cannot be cloned.
+pub fn write(
+    cx: &mut JSContext,
+    value: HandleValue,
+    scope: StructuredCloneScope,
+    transfer: HandleValue,
+) -> Result<StructuredCloneBuffer, Exception> {
+    write_impl(cx, value, scope, transfer, None)
+}
+
+/// Like [`write`], passing the objects the engine cannot clone to `host`.
+pub fn write_with_host(
+    cx: &mut JSContext,
+    value: HandleValue,
+    scope: StructuredCloneScope,
+    transfer: HandleValue,
+    host: &dyn HostObjects,
+) -> Result<StructuredCloneBuffer, Exception> {
+    write_impl(cx, value, scope, transfer, Some(host))
+}
+
+fn write_impl(
+    cx: &mut JSContext,
+    value: HandleValue,
+    scope: StructuredCloneScope,
+    transfer: HandleValue,
+    host: Option<&dyn HostObjects>,
+) -> Result<StructuredCloneBuffer, Exception> {
+    let buffer = StructuredCloneBuffer::new(scope);
+    let policy = clone_data_policy();
+    let (callbacks, closure) = host_callbacks(&host);
+    let ok = unsafe {
+        JS_WriteStructuredClone(
+            cx,
+            value,
+            buffer.data(),
+            scope,
+            &policy,
+            callbacks,
+            closure,
+            transfer,
+        )
+    };
+    maybe_resume_unwind();
+    if ok {
+        Ok(buffer)
+    } else {
+        Err(unsafe { Exception::steal(cx.raw_cx()) })
+    }
+}
+
+/// Create a copy of the value in `buffer` in the realm of `cx`. Transferred
+/// ArrayBuffers move to the copy, so buffers that contain some can only be
+/// read once. Returns the exception on failure.
+pub fn read(
+    cx: &mut JSContext,
+    buffer: &StructuredCloneBuffer,
+    rval: MutableHandleValue,
+) -> Result<(), Exception> {
+    read_impl(cx, buffer, None, rval)
+}
+
+/// Like [`read`], creating host objects with `host`.
+pub fn read_with_host(
+    cx: &mut JSContext,
+    buffer: &StructuredCloneBuffer,
+    host: &dyn HostObjects,
+    rval: MutableHandleValue,
+) -> Result<(), Exception> {
+    read_impl(cx, buffer, Some(host), rval)
+}
+
+fn read_impl(
+    cx: &mut JSContext,
+    buffer: &StructuredCloneBuffer,
+    host: Option<&dyn HostObjects>,
+    rval: MutableHandleValue,
+) -> Result<(), Exception> {
+    let policy = clone_data_policy();
+    let (callbacks, closure) = host_callbacks(&host);
+    let ok = unsafe {
+        JS_ReadStructuredClone(
+            cx,
+            buffer.data(),
+            JS_STRUCTURED_CLONE_VERSION,
+            buffer.scope,
+            rval,
+            &policy,
+            callbacks,
+            closure,
+        )
+    };
+    maybe_resume_unwind();
+    if ok {
+        Ok(())
+    } else {
+        Err(unsafe { Exception::steal(cx.raw_cx()) })
+    }
+}
+
+unsafe extern "C" fn write_host_object(
+    cx: *mut jsapi::JSContext,
+    writer: *mut JSStructuredCloneWriter,
+    obj: jsapi::HandleObject,
+    _same_process_scope_required: *mut bool,
+    closure: *mut c_void,
+) -> bool {
+    let mut result = false;
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        let host = *(closure as *const &dyn HostObjects);
+        let mut writer = StructuredCloneWriter { raw: writer };
+        result = host.write(&mut cx, &mut writer, Handle::from_raw(obj));
+    });
+    result
+}
+
+unsafe extern "C" fn read_host_object(
+    cx: *mut jsapi::JSContext,
+    reader: *mut JSStructuredCloneReader,
+    _policy: *const CloneDataPolicy,
+    tag: u32,
+    data: u32,
+    closure: *mut c_void,
+) -> *mut JSObject {
+    let mut result = ptr::null_mut();
+    wrap_panic(&mut || {
+        let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+        let host = *(closure as *const &dyn HostObjects);
+        let mut reader = StructuredCloneReader { raw: reader };
+        result = host.read(&mut cx, &mut reader, tag, data);
+    });
+    result
+}
@@ -0,0 +1,174 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Time budgets for scripts.
+//!
+//! Every [`Runtime`] installs an interrupt callback that terminates the
+//! running script once the budget of the innermost [`evaluate_with_budget`]
+//! has run out. Scripts only run the callback when an interrupt is requested,
+//! which is the job of a [`Watchdog`]: a thread that requests one when the
+//! budget runs out.
+//!
+//! ```ignore
+//! let watchdog = Watchdog::new(&runtime);
+//! match evaluate_with_budget(cx, &watchdog, global, "for (;;) {}", rval, options, budget) {
+//!     Err(ExecutionError::Timeout) => println!("the script was too slow"),
+//!     _ => (),
+//! }
+//! ```
+//!
+//! Termination cannot be caught by the script: neither `catch` nor `finally`
+//! blocks run. Natives that are running when the budget runs out are
+//! not interrupted; the script is terminated when control returns to it.
+
+use std::cell::Cell;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::context::JSContext;
+use crate::error::ExecutionError;
+use crate::jsapi;
+use crate::rust::{evaluate_script, CompileOptionsWrapper, HandleObject, MutableHandleValue};
+use crate::rust::{Runtime, ThreadSafeJSContext};
+
+thread_local!(static DEADLINE: Cell<Option<Instant>> = Cell::new(None));
+
+/// The interrupt callback installed on every context, which terminates the
+/// script once the current deadline has passed.
+pub(crate) unsafe extern "C" fn interrupt_callback(_cx: *mut jsapi::JSContext) -> bool {
+    !DEADLINE.with(|deadline| {
+        deadline
+            .get()
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    })
+}
+
+/// The deadline the watchdog thread waits for.
+struct State {
+    deadline: Option<Instant>,
+    shutdown: bool,
+}
+
+/// A thread that requests an interrupt of a [`Runtime`] when the budget of
+/// [`evaluate_with_budget`] runs out. It can be reused for any number of
+/// scripts, and is stopped when dropped.
+pub struct Watchdog {
+    state: Arc<(Mutex<State>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Start a watchdog for `runtime`.
+    pub fn new(runtime: &Runtime) -> Watchdog {
+        let state = Arc::new((
+            Mutex::new(State {
+                deadline: None,
+                shutdown: false,
+            }),
+            Condvar::new(),
+        ));
+        let cx = runtime.thread_safe_js_context();
+        let thread = thread::Builder::new()
+            .name("mozjs watchdog".to_owned())
+            .spawn({
+                let state = state.clone();
+                move || watch(&state, cx)
+            })
+            .expect("Failed to spawn the watchdog thread");
+        Watchdog {
+            state,
+            thread: Some(thread),
+        }
+    }
+
+    /// Request an interrupt at `deadline`, replacing the previous deadline.
+    fn set_deadline(&self, deadline: Option<Instant>) {
+        let (state, condvar) = &*self.state;
+        state.lock().unwrap().deadline = deadline;
+        condvar.notify_one();
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let (state, condvar) = &*self.state;
+        state.lock().unwrap().shutdown = true;
+        condvar.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watch(state: &(Mutex<State>, Condvar), cx: ThreadSafeJSContext) {
+    let (state, condvar) = state;
+    let mut guard = state.lock().unwrap();
+    while !guard.shutdown {
+        guard = match guard.deadline {
+            None => condvar.wait(guard).unwrap(),
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => {
+                    condvar.wait_timeout(guard, timeout).unwrap().0
+                }
+                _ => {
+                    guard.deadline = None;
+                    cx.request_interrupt_callback();
+                    guard
+                }
+            },
+        };
+    }
+}
+
+/// Restores the enclosing deadline when a budgeted evaluation returns or
+/// unwinds.
+struct Budget<'a> {
+    watchdog: &'a Watchdog,
+    previous: Option<Instant>,
+}
+
+impl Drop for Budget<'_> {
+    fn drop(&mut self) {
+        DEADLINE.with(|deadline| deadline.set(self.previous));
+        self.watchdog.set_deadline(self.previous);
+    }
+}
+
+/// Evaluate `script` like [`evaluate_script`], terminating it if it runs for
+/// longer than `budget`. `watchdog` must belong to the runtime of `cx`.
+///
+/// A budget does not extend the budget of an enclosing evaluation: if that
+/// one runs out first, this script is terminated and the uncatchable
+/// exception is returned.
+pub fn evaluate_with_budget(
+    cx: &mut JSContext,
+    watchdog: &Watchdog,
+    glob: HandleObject,
+    script: &str,
+    rval: MutableHandleValue,
+    options: CompileOptionsWrapper,
+    budget: Duration,
+) -> Result<(), ExecutionError> {
+    let own_deadline = Instant::now().checked_add(budget);
+    let previous = DEADLINE.with(Cell::get);
+    let deadline = match (own_deadline, previous) {
+        (Some(own), Some(previous)) => Some(own.min(previous)),
+        (own, previous) => own.or(previous),
+    };
+    DEADLINE.with(|cell| cell.set(deadline));
+    watchdog.set_deadline(deadline);
+    let _budget = Budget { watchdog, previous };
+
+    match evaluate_script(cx, glob, script, rval, options) {
+        Ok(()) => Ok(()),
+        Err(exception)
+            if exception.is_uncatchable()
+                && own_deadline.is_some_and(|own| Instant::now() >= own) =>
+        {
+            Err(ExecutionError::Timeout)
+        }
+        Err(exception) => Err(exception.into()),
+    }
+}
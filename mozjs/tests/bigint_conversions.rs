@@ -0,0 +1,114 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+
+use mozjs::context::JSContext;
+use mozjs::conversions::{ConversionResult, FromJSValConvertible, JsBigInt, ToJSValConvertible};
+use mozjs::jsapi::{JSObject, OnNewGlobalHookOption};
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{
+    JS_ClearPendingException, JS_IsExceptionPending, JS_NewGlobalObject, JS_SetProperty,
+};
+use mozjs::rust::{Handle, JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+use common::eval;
+
+mod common;
+
+/// Convert `value` to JS and back, checking that JS sees `expected`.
+fn round_trip<T>(cx: &mut JSContext, global: Handle<*mut JSObject>, value: T, expected: &str)
+where
+    T: ToJSValConvertible + FromJSValConvertible<Config = ()> + PartialEq + std::fmt::Debug,
+{
+    rooted!(&in(cx) let mut rval = UndefinedValue());
+    value.safe_to_jsval(cx, rval.handle_mut());
+    assert!(rval.get().is_bigint());
+    assert!(unsafe { JS_SetProperty(cx, global, c"value".as_ptr(), rval.handle()) });
+    eval(
+        cx,
+        global,
+        &format!("value === {}n", expected),
+        rval.handle_mut(),
+    );
+    assert!(rval.get().to_boolean());
+
+    eval(cx, global, &format!("{}n", expected), rval.handle_mut());
+    match T::safe_from_jsval(cx, rval.handle(), ()) {
+        Ok(ConversionResult::Success(converted)) => assert_eq!(converted, value),
+        _ => panic!("conversion of {} failed", expected),
+    }
+}
+
+fn throws<T: FromJSValConvertible<Config = ()>>(
+    cx: &mut JSContext,
+    global: Handle<*mut JSObject>,
+    source: &str,
+) {
+    rooted!(&in(cx) let mut rval = UndefinedValue());
+    eval(cx, global, source, rval.handle_mut());
+    assert!(T::safe_from_jsval(cx, rval.handle(), ()).is_err());
+    unsafe {
+        assert!(JS_IsExceptionPending(cx));
+        JS_ClearPendingException(cx);
+    }
+}
+
+#[test]
+fn bigint_conversions() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        let global = global.handle();
+
+        round_trip(cx, global, JsBigInt(i64::MIN), "-9223372036854775808");
+        round_trip(cx, global, JsBigInt(u64::MAX), "18446744073709551615");
+        round_trip(
+            cx,
+            global,
+            JsBigInt(i128::MIN),
+            "-170141183460469231731687303715884105728",
+        );
+        round_trip(
+            cx,
+            global,
+            JsBigInt(u128::MAX),
+            "340282366920938463463374607431768211455",
+        );
+        #[cfg(feature = "num-bigint")]
+        round_trip(
+            cx,
+            global,
+            JsBigInt(num_bigint::BigInt::from(u128::MAX) * -1000),
+            "-340282366920938463463374607431768211455000",
+        );
+
+        // Values that do not fit throw a RangeError, and Numbers a TypeError.
+        throws::<JsBigInt<i64>>(cx, global, "2n ** 63n");
+        throws::<JsBigInt<u64>>(cx, global, "-1n");
+        throws::<JsBigInt<u128>>(cx, global, "2n ** 128n");
+        throws::<JsBigInt<i64>>(cx, global, "1");
+        #[cfg(feature = "num-bigint")]
+        throws::<JsBigInt<num_bigint::BigUint>>(cx, global, "-1n");
+    }
+}
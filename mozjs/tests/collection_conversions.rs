@@ -0,0 +1,216 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ptr;
+
+use mozjs::conversions::{
+    ConversionBehavior, ConversionResult, FromJSValConvertible, MapObject, SetObject,
+    ToJSValConvertible,
+};
+use mozjs::jsapi::OnNewGlobalHookOption;
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{
+    JS_ClearPendingException, JS_IsExceptionPending, JS_NewGlobalObject, JS_SetProperty,
+};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+use common::eval;
+
+mod common;
+
+fn success<T>(result: Result<ConversionResult<T>, ()>) -> T {
+    match result {
+        Ok(ConversionResult::Success(value)) => value,
+        _ => panic!("conversion failed"),
+    }
+}
+
+#[test]
+fn collection_conversions() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        rooted!(&in(cx) let mut rval = UndefinedValue());
+
+        // Maps with string keys round trip through plain objects.
+        let map = HashMap::from([("one".to_owned(), 1u32), ("two".to_owned(), 2)]);
+        map.safe_to_jsval(cx, rval.handle_mut());
+        assert!(rval.get().is_object());
+        let converted: HashMap<String, u32> = success(FromJSValConvertible::safe_from_jsval(
+            cx,
+            rval.handle(),
+            ConversionBehavior::Default,
+        ));
+        assert_eq!(converted, map);
+
+        // Records only see own enumerable properties, and invoke getters.
+        eval(
+            cx,
+            global.handle(),
+            "Object.defineProperty({ b: 2, get c() { return 3; } }, 'hidden', { value: 4 })",
+            rval.handle_mut(),
+        );
+        let converted: BTreeMap<String, i32> = success(FromJSValConvertible::safe_from_jsval(
+            cx,
+            rval.handle(),
+            ConversionBehavior::Default,
+        ));
+        assert_eq!(
+            converted,
+            BTreeMap::from([("b".to_owned(), 2), ("c".to_owned(), 3)])
+        );
+
+        // Enumerable symbol keys cannot be converted to strings.
+        eval(
+            cx,
+            global.handle(),
+            "({ [Symbol()]: 1 })",
+            rval.handle_mut(),
+        );
+        assert!(HashMap::<String, i32>::safe_from_jsval(
+            cx,
+            rval.handle(),
+            ConversionBehavior::Default
+        )
+        .is_err());
+        assert!(JS_IsExceptionPending(cx));
+        JS_ClearPendingException(cx);
+
+        // Sets round trip through arrays.
+        eval(cx, global.handle(), "[3, 1, 3, 2]", rval.handle_mut());
+        let converted: HashSet<i32> = success(FromJSValConvertible::safe_from_jsval(
+            cx,
+            rval.handle(),
+            ConversionBehavior::Default,
+        ));
+        assert_eq!(converted, HashSet::from([1, 2, 3]));
+        let set = BTreeSet::from([1, 2, 3]);
+        set.safe_to_jsval(cx, rval.handle_mut());
+        let converted: Vec<i32> = success(FromJSValConvertible::safe_from_jsval(
+            cx,
+            rval.handle(),
+            ConversionBehavior::Default,
+        ));
+        assert_eq!(converted, [1, 2, 3]);
+
+        // IndexMap keeps the order of the properties.
+        #[cfg(feature = "indexmap")]
+        {
+            eval(cx, global.handle(), "({ z: 1, a: 2 })", rval.handle_mut());
+            let converted: indexmap::IndexMap<String, i32> =
+                success(FromJSValConvertible::safe_from_jsval(
+                    cx,
+                    rval.handle(),
+                    ConversionBehavior::Default,
+                ));
+            assert_eq!(converted.keys().collect::<Vec<_>>(), ["z", "a"]);
+        }
+
+        // MapObject and SetObject use real Map and Set objects.
+        let map = MapObject(BTreeMap::from([
+            (1, "one".to_owned()),
+            (2, "two".to_owned()),
+        ]));
+        map.safe_to_jsval(cx, rval.handle_mut());
+        assert!(JS_SetProperty(
+            cx,
+            global.handle(),
+            c"map".as_ptr(),
+            rval.handle()
+        ));
+        eval(
+            cx,
+            global.handle(),
+            "map instanceof Map && map.get(2) === 'two'",
+            rval.handle_mut(),
+        );
+        assert!(rval.get().to_boolean());
+
+        eval(
+            cx,
+            global.handle(),
+            "new Map([[1, 'one'], [2, 'two']])",
+            rval.handle_mut(),
+        );
+        let converted: MapObject<HashMap<i32, String>> =
+            success(FromJSValConvertible::safe_from_jsval(
+                cx,
+                rval.handle(),
+                (ConversionBehavior::Default, ()),
+            ));
+        assert_eq!(
+            converted.0,
+            HashMap::from([(1, "one".to_owned()), (2, "two".to_owned())])
+        );
+
+        let set = SetObject(HashSet::from(["a".to_owned()]));
+        set.safe_to_jsval(cx, rval.handle_mut());
+        assert!(JS_SetProperty(
+            cx,
+            global.handle(),
+            c"set".as_ptr(),
+            rval.handle()
+        ));
+        eval(
+            cx,
+            global.handle(),
+            "set instanceof Set && set.has('a')",
+            rval.handle_mut(),
+        );
+        assert!(rval.get().to_boolean());
+
+        eval(
+            cx,
+            global.handle(),
+            "new Set(['a', 'b'])",
+            rval.handle_mut(),
+        );
+        let converted: SetObject<BTreeSet<String>> =
+            success(FromJSValConvertible::safe_from_jsval(cx, rval.handle(), ()));
+        assert_eq!(
+            converted.0,
+            BTreeSet::from(["a".to_owned(), "b".to_owned()])
+        );
+
+        // Other objects are not Maps or Sets.
+        eval(cx, global.handle(), "({})", rval.handle_mut());
+        assert!(matches!(
+            MapObject::<HashMap<String, i32>>::safe_from_jsval(
+                cx,
+                rval.handle(),
+                ((), ConversionBehavior::Default)
+            ),
+            Ok(ConversionResult::Failure(_))
+        ));
+        assert!(matches!(
+            SetObject::<HashSet<i32>>::safe_from_jsval(
+                cx,
+                rval.handle(),
+                ConversionBehavior::Default
+            ),
+            Ok(ConversionResult::Failure(_))
+        ));
+        assert!(!JS_IsExceptionPending(cx));
+    }
+}
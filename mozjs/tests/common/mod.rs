@@ -0,0 +1,33 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Helpers shared by the integration tests. Each test uses some of them.
+
+#![allow(dead_code)]
+
+use mozjs::context::JSContext;
+use mozjs::jsapi::JSObject;
+use mozjs::jsval::UndefinedValue;
+use mozjs::rooted;
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper, Handle, MutableHandleValue};
+
+/// Evaluate `source` in `global`, which must not throw, and return whether
+/// the result is `true`.
+pub fn check(cx: &mut JSContext, global: Handle<*mut JSObject>, source: &str) -> bool {
+    rooted!(&in(cx) let mut rval = UndefinedValue());
+    eval(cx, global, source, rval.handle_mut());
+    rval.get().to_boolean()
+}
+
+/// Evaluate `source` in `global`, which must not throw, storing the result in
+/// `rval`.
+pub fn eval(
+    cx: &mut JSContext,
+    global: Handle<*mut JSObject>,
+    source: &str,
+    rval: MutableHandleValue,
+) {
+    let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+    assert!(evaluate_script(cx, global, source, rval, options).is_ok());
+}
@@ -0,0 +1,125 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+
+use mozjs::conversions::{ConversionResult, FromJSValConvertible, ToJSValConvertible};
+use mozjs::jsapi::OnNewGlobalHookOption;
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_ClearPendingException, JS_IsExceptionPending, JS_NewGlobalObject};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+use common::eval;
+
+mod common;
+
+#[derive(Debug, PartialEq, ToJSValConvertible, FromJSValConvertible)]
+enum Direction {
+    #[js(rename = "up")]
+    Up,
+    #[js(rename = "down")]
+    Down,
+}
+
+#[derive(Debug, PartialEq, ToJSValConvertible, FromJSValConvertible)]
+struct Options {
+    #[js(rename = "maxCount")]
+    max_count: u32,
+    label: Option<String>,
+    #[js(default)]
+    tags: Vec<String>,
+    #[js(clamp)]
+    level: u8,
+    #[js(enforce_range, default)]
+    priority: u8,
+    direction: Direction,
+}
+
+#[test]
+fn derive() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        rooted!(&in(cx) let mut rval = UndefinedValue());
+
+        let options = Options {
+            max_count: 3,
+            label: Some("main".to_owned()),
+            tags: vec!["a".to_owned()],
+            level: 7,
+            priority: 1,
+            direction: Direction::Down,
+        };
+        options.safe_to_jsval(cx, rval.handle_mut());
+        match Options::safe_from_jsval(cx, rval.handle(), ()) {
+            Ok(ConversionResult::Success(converted)) => assert_eq!(converted, options),
+            _ => panic!("round trip failed"),
+        }
+
+        // Missing optional members use their defaults, and clamped members
+        // saturate.
+        eval(
+            cx,
+            global.handle(),
+            "({ maxCount: 3, level: 300, direction: 'up' })",
+            rval.handle_mut(),
+        );
+        match Options::safe_from_jsval(cx, rval.handle(), ()) {
+            Ok(ConversionResult::Success(converted)) => assert_eq!(
+                converted,
+                Options {
+                    max_count: 3,
+                    label: None,
+                    tags: vec![],
+                    level: 255,
+                    priority: 0,
+                    direction: Direction::Up,
+                }
+            ),
+            _ => panic!("conversion failed"),
+        }
+
+        for source in [
+            "5",
+            "({ level: 1, direction: 'up' })",
+            "({ maxCount: 1, level: 1, direction: 'left' })",
+        ] {
+            eval(cx, global.handle(), source, rval.handle_mut());
+            assert!(matches!(
+                Options::safe_from_jsval(cx, rval.handle(), ()),
+                Ok(ConversionResult::Failure(_))
+            ));
+            assert!(!JS_IsExceptionPending(cx));
+        }
+
+        eval(
+            cx,
+            global.handle(),
+            "({ maxCount: 1, level: 1, priority: 300, direction: 'up' })",
+            rval.handle_mut(),
+        );
+        assert!(Options::safe_from_jsval(cx, rval.handle(), ()).is_err());
+        assert!(JS_IsExceptionPending(cx));
+        JS_ClearPendingException(cx);
+    }
+}
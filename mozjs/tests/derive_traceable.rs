@@ -0,0 +1,118 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+
+use mozjs::context::JSContext;
+use mozjs::gc::{Handle, HandleObject, Traceable};
+use mozjs::jsapi::{GCReason, Heap, JSObject, OnNewGlobalHookOption, JSPROP_ENUMERATE};
+use mozjs::jsval::{Int32Value, JSVal, ObjectValue, UndefinedValue};
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{
+    JS_DefineProperty, JS_GetProperty, JS_NewGlobalObject, JS_NewPlainObject, JS_GC,
+};
+use mozjs::rust::{JSEngine, RealmOptions, RootedTraceableBox, Runtime, SIMPLE_GLOBAL_CLASS};
+
+#[derive(Default, Traceable)]
+struct Holder {
+    object: Heap<*mut JSObject>,
+    value: Heap<JSVal>,
+    #[no_trace]
+    generation: u32,
+}
+
+#[derive(Traceable)]
+struct Named<T> {
+    #[no_trace]
+    name: String,
+    item: T,
+}
+
+#[derive(Traceable)]
+enum Slot {
+    #[allow(dead_code)]
+    Empty,
+    Full(Box<Heap<*mut JSObject>>),
+}
+
+#[test]
+fn derive_traceable() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+
+        let named = RootedTraceableBox::new(Named {
+            name: "holder".to_owned(),
+            item: Holder::default(),
+        });
+        named.item.object.set(JS_NewPlainObject(cx));
+        named.item.value.set(ObjectValue(JS_NewPlainObject(cx)));
+        let slot = RootedTraceableBox::new(Slot::Full(Heap::boxed(JS_NewPlainObject(cx))));
+
+        let Slot::Full(full) = &*slot else {
+            unreachable!();
+        };
+
+        define_answer(cx, Handle::from_raw(named.item.object.handle()));
+        define_answer(cx, Handle::from_raw(full.handle()));
+        {
+            rooted!(&in(cx) let value = named.item.value.get().to_object());
+            define_answer(cx, value.handle());
+        }
+
+        // The objects only stay alive, and their heap pointers only get
+        // updated when they move, if the boxes trace their fields.
+        JS_GC(cx, GCReason::API);
+
+        assert_eq!(
+            get_answer(cx, Handle::from_raw(named.item.object.handle())),
+            42
+        );
+        assert_eq!(get_answer(cx, Handle::from_raw(full.handle())), 42);
+        rooted!(&in(cx) let value = named.item.value.get().to_object());
+        assert_eq!(get_answer(cx, value.handle()), 42);
+        assert_eq!(named.name, "holder");
+        assert_eq!(named.item.generation, 0);
+    }
+}
+
+unsafe fn define_answer(cx: &mut JSContext, object: HandleObject) {
+    rooted!(&in(cx) let answer = Int32Value(42));
+    assert!(JS_DefineProperty(
+        cx,
+        object,
+        c"answer".as_ptr(),
+        answer.handle(),
+        JSPROP_ENUMERATE as u32,
+    ));
+}
+
+unsafe fn get_answer(cx: &mut JSContext, object: HandleObject) -> i32 {
+    rooted!(&in(cx) let mut rval = UndefinedValue());
+    assert!(JS_GetProperty(
+        cx,
+        object,
+        c"answer".as_ptr(),
+        rval.handle_mut(),
+    ));
+    rval.get().to_int32()
+}
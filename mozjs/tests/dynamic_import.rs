@@ -0,0 +1,98 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::fs;
+use std::ptr;
+
+use mozjs::jobs::{run_jobs, MicrotaskQueue};
+use mozjs::jsapi::OnNewGlobalHookOption;
+use mozjs::jsval::UndefinedValue;
+use mozjs::modules::{evaluate_module, FilesystemLoader};
+use mozjs::rooted;
+use mozjs::rust::wrappers2::JS_NewGlobalObject;
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+#[test]
+fn dynamic_import() {
+    let root = std::env::temp_dir().join(format!("mozjs-dynamic-import-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(
+        root.join("main.js"),
+        "globalThis.metaUrl = import.meta.url;
+         import('./lib.js').then(m => globalThis.result = m.value);
+         import('./missing.js').catch(e => globalThis.failed = e instanceof TypeError);",
+    )
+    .unwrap();
+    fs::write(
+        root.join("lib.js"),
+        "export const value = import.meta.url.endsWith('/lib.js') ? 42 : 0;",
+    )
+    .unwrap();
+
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    runtime.set_job_queue(MicrotaskQueue::default());
+    runtime.set_module_loader(FilesystemLoader::new(&root).unwrap());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+
+        assert!(evaluate_module(context, global.handle(), "main.js").is_ok());
+        run_jobs(context);
+
+        rooted!(&in(context) let mut rval = UndefinedValue());
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global.handle(),
+            "result === 42 && failed && metaUrl.startsWith('file://') &&
+             metaUrl.endsWith('/main.js')",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        assert!(rval.get().to_boolean());
+
+        // Classic scripts import relative to the loader's root.
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global.handle(),
+            "import('./lib.js').then(m => globalThis.fromScript = m.value)",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        run_jobs(context);
+
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global.handle(),
+            "fromScript",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        assert_eq!(rval.get().to_int32(), 42);
+    }
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,106 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+
+use mozjs::context::JSContext;
+use mozjs::error::Exception;
+use mozjs::jsapi::{JSObject, OnNewGlobalHookOption};
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_IsExceptionPending, JS_NewGlobalObject};
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper, Handle};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+/// Evaluate `source`, which must throw, and return the exception.
+fn throws(cx: &mut JSContext, global: Handle<*mut JSObject>, source: &str) -> Exception {
+    rooted!(&in(cx) let mut rval = UndefinedValue());
+    let options = CompileOptionsWrapper::new(&cx, c"exception.js".to_owned(), 1);
+    let exception = evaluate_script(cx, global, source, rval.handle_mut(), options).unwrap_err();
+    assert!(!unsafe { JS_IsExceptionPending(cx) });
+    exception
+}
+
+#[test]
+fn exception() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        let global = global.handle();
+
+        let exception = throws(
+            cx,
+            global,
+            "function f() {\n  throw new TypeError('bad');\n}\nf();",
+        );
+        assert!(!exception.is_uncatchable());
+        assert!(exception.value().is_object());
+        assert_eq!(exception.name(), Some("TypeError"));
+        assert_eq!(exception.message(), "bad");
+        assert_eq!(exception.filename(), "exception.js");
+        assert_eq!(exception.lineno(), 2);
+        assert!(exception.column() > 0);
+        assert!(exception.stack_object().is_some());
+        let stack = exception.stack().unwrap();
+        assert!(stack.starts_with("f@exception.js:2:"));
+        assert_eq!(stack.lines().count(), 2);
+        let display = exception.to_string();
+        assert!(display.starts_with("exception.js:2:"));
+        assert!(display.ends_with(": TypeError: bad"));
+
+        // Primitives that are not errors are described by their string
+        // conversion, and keep the stack they were thrown with.
+        let exception = throws(cx, global, "throw 42;");
+        assert_eq!(exception.value().to_int32(), 42);
+        assert_eq!(exception.name(), None);
+        assert_eq!(exception.message(), "42");
+        assert_eq!(exception.lineno(), 0);
+        assert!(exception.stack().unwrap().contains("exception.js:1:"));
+        assert_eq!(exception.to_string(), "uncaught exception: 42");
+
+        let exception = throws(cx, global, "throw Symbol('unprintable');");
+        assert!(exception.value().is_symbol());
+        assert_eq!(exception.message(), "Symbol(unprintable)");
+
+        // Objects are described by their class, without calling `toString`.
+        let exception = throws(
+            cx,
+            global,
+            "globalThis.called = false; throw { toString() { called = true; return 'x'; } };",
+        );
+        assert_eq!(exception.message(), "[object Object]");
+        rooted!(&in(cx) let mut called = UndefinedValue());
+        let options = CompileOptionsWrapper::new(&cx, c"exception.js".to_owned(), 1);
+        assert!(evaluate_script(cx, global, "called", called.handle_mut(), options).is_ok());
+        assert!(!called.get().to_boolean());
+
+        // Syntax errors carry notes about related locations.
+        let exception = throws(cx, global, "let a;\nlet a;");
+        assert_eq!(exception.name(), Some("SyntaxError"));
+        assert_eq!(exception.lineno(), 2);
+        let notes = exception.notes();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].filename, "exception.js");
+        assert_eq!(notes[0].lineno, 1);
+        assert!(!notes[0].message.is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::cell::RefCell;
+use std::ptr;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use mozjs::context::RawJSContext;
+use mozjs::gc::{GcObserver, GcStats};
+use mozjs::jsapi::{GCDescription, GCProgress, GCReason, JSFinalizeStatus, OnNewGlobalHookOption};
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_NewGlobalObject, JS_GC};
+use mozjs::rust::SIMPLE_GLOBAL_CLASS;
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper, JSEngine, RealmOptions, Runtime};
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Begin(GCReason),
+    End(GcStats),
+    Slice(GcStats),
+    Minor(GcStats),
+    Finalize(JSFinalizeStatus),
+}
+
+struct Recorder(Rc<RefCell<Vec<Event>>>);
+
+impl GcObserver for Recorder {
+    fn on_gc_begin(&self, reason: GCReason) {
+        self.0.borrow_mut().push(Event::Begin(reason));
+    }
+
+    fn on_gc_end(&self, stats: &GcStats) {
+        self.0.borrow_mut().push(Event::End(*stats));
+    }
+
+    fn on_slice(&self, stats: &GcStats) {
+        self.0.borrow_mut().push(Event::Slice(*stats));
+    }
+
+    fn on_minor_gc(&self, stats: &GcStats) {
+        self.0.borrow_mut().push(Event::Minor(*stats));
+    }
+
+    fn on_finalize(&self, status: JSFinalizeStatus) {
+        self.0.borrow_mut().push(Event::Finalize(status));
+    }
+}
+
+/// The slices seen by a slice callback set by the embedder.
+static EMBEDDER_SLICES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn embedder_slice_callback(
+    _cx: *mut RawJSContext,
+    progress: GCProgress,
+    _desc: *const GCDescription,
+) {
+    if progress == GCProgress::GC_SLICE_END {
+        EMBEDDER_SLICES.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn gc_observer() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    // The observer keeps calling a slice callback that was set before it.
+    unsafe {
+        mozjs::jsapi::SetGCSliceCallback(runtime.cx().raw_cx(), Some(embedder_slice_callback));
+    }
+    let events = Rc::new(RefCell::new(Vec::new()));
+    runtime.set_gc_observer(Recorder(events.clone()));
+    let context = runtime.cx();
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+
+        // Fill the nursery, so that the collection starts with a minor GC.
+        rooted!(&in(cx) let mut rval = UndefinedValue());
+        let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+        let script = "globalThis.objects = []; for (let i = 0; i < 1000; i++) objects.push({ i });";
+        assert!(evaluate_script(cx, global.handle(), script, rval.handle_mut(), options).is_ok());
+
+        events.borrow_mut().clear();
+        JS_GC(cx, GCReason::API);
+    }
+
+    let events = events.borrow();
+    let position = |matches: &dyn Fn(&Event) -> bool| events.iter().position(matches);
+    let begin = position(&|event| *event == Event::Begin(GCReason::API)).unwrap();
+    let end =
+        position(&|event| matches!(event, Event::End(stats) if stats.reason == GCReason::API))
+            .unwrap();
+    let slice =
+        position(&|event| matches!(event, Event::Slice(stats) if stats.reason == GCReason::API))
+            .unwrap();
+    assert!(begin < slice && slice < end);
+    assert!(position(&|event| matches!(event, Event::Minor(_))).is_some());
+    assert!(position(
+        &|event| *event == Event::Finalize(JSFinalizeStatus::JSFINALIZE_COLLECTION_END)
+    )
+    .is_some());
+    let Event::End(stats) = &events[end] else {
+        unreachable!()
+    };
+    assert!(stats.heap_bytes_before > 0 && stats.heap_bytes_after > 0);
+    assert!(EMBEDDER_SLICES.load(Ordering::SeqCst) > 0);
+}
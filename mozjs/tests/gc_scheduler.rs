@@ -0,0 +1,72 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+use std::time::{Duration, Instant};
+
+use mozjs::gc::GcScheduler;
+use mozjs::jsapi::{GCReason, JSGCParamKey, OnNewGlobalHookOption};
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::JS_NewGlobalObject;
+use mozjs::rust::SIMPLE_GLOBAL_CLASS;
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper, JSEngine, RealmOptions, Runtime};
+
+const BUDGET: Duration = Duration::from_millis(1);
+
+#[test]
+fn gc_scheduler() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::builder()
+        .gc_parameter(JSGCParamKey::JSGC_INCREMENTAL_GC_ENABLED, 1)
+        .build(engine.handle());
+    assert!(!runtime.gc_scheduler().is_in_progress());
+    let context = runtime.cx();
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+
+        rooted!(&in(cx) let mut rval = UndefinedValue());
+        let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+        let script =
+            "globalThis.objects = []; for (let i = 0; i < 100000; i++) objects.push({ i });";
+        assert!(evaluate_script(cx, global.handle(), script, rval.handle_mut(), options).is_ok());
+
+        let mut scheduler = GcScheduler::new(cx);
+
+        // Slices run until the collection completes.
+        let mut in_progress = scheduler.start_incremental(GCReason::API, BUDGET);
+        assert_eq!(in_progress, scheduler.is_in_progress());
+        let mut slices = 0;
+        while in_progress {
+            slices += 1;
+            assert!(slices < 100_000, "the collection does not finish");
+            in_progress = scheduler.slice(GCReason::API, BUDGET);
+        }
+        assert!(!scheduler.slice(GCReason::API, BUDGET));
+
+        // A started collection can be finished at once.
+        scheduler.start_incremental(GCReason::API, BUDGET);
+        scheduler.finish(GCReason::API);
+        assert!(!scheduler.is_in_progress());
+
+        // Idle time past the deadline does nothing.
+        assert!(!scheduler.idle(Instant::now()));
+        scheduler.idle(Instant::now() + BUDGET);
+
+        scheduler.collect_full(GCReason::API);
+        assert!(!scheduler.is_in_progress());
+    }
+}
@@ -0,0 +1,96 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+
+use mozjs::jobs::{run_jobs, JobQueue, MicrotaskQueue};
+use mozjs::jsapi::OnNewGlobalHookOption;
+use mozjs::jsval::UndefinedValue;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::JS_NewGlobalObject;
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+#[test]
+fn jobs() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    runtime.set_job_queue(MicrotaskQueue::default());
+    assert!(runtime.job_queue::<MicrotaskQueue>().unwrap().is_empty());
+
+    {
+        let context = runtime.cx();
+        #[cfg(feature = "debugmozjs")]
+        unsafe {
+            mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+        }
+        let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+        let c_option = RealmOptions::default();
+
+        unsafe {
+            rooted!(&in(context) let global = JS_NewGlobalObject(
+                context,
+                &SIMPLE_GLOBAL_CLASS,
+                ptr::null_mut(),
+                h_option,
+                &*c_option,
+            ));
+            rooted!(&in(context) let mut rval = UndefinedValue());
+            let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+            assert!(evaluate_script(
+                context,
+                global.handle(),
+                "var log = [];
+                 Promise.resolve(1).then(v => log.push(v));
+                 (async () => { await null; log.push(2); throw 3; })();
+                 Promise.resolve().then(() => { throw new Error('ignored'); })
+                                  .catch(() => log.push(4));
+                 log.length",
+                rval.handle_mut(),
+                options,
+            )
+            .is_ok());
+            assert_eq!(rval.get().to_int32(), 0);
+
+            run_jobs(context);
+
+            let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+            assert!(evaluate_script(
+                context,
+                global.handle(),
+                "log.join() === '1,2,4'",
+                rval.handle_mut(),
+                options,
+            )
+            .is_ok());
+            assert!(rval.get().to_boolean());
+        }
+    }
+
+    assert!(runtime.job_queue::<MicrotaskQueue>().unwrap().is_empty());
+
+    unsafe {
+        let context = runtime.cx();
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            OnNewGlobalHookOption::FireOnNewGlobalHook,
+            &*RealmOptions::default(),
+        ));
+        rooted!(&in(context) let mut rval = UndefinedValue());
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global.handle(),
+            "Promise.resolve().then(() => {});",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+    }
+
+    // The pending job is dropped along with the runtime, without running.
+    assert_eq!(runtime.job_queue::<MicrotaskQueue>().unwrap().len(), 1);
+}
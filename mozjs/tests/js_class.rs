@@ -0,0 +1,200 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::cell::Cell;
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+
+use mozjs::class::{unwrap, JsClass};
+use mozjs::context::{JSContext, RawJSContext};
+use mozjs::conversions::{ConversionBehavior, ConversionResult, FromJSValConvertible};
+use mozjs::error::throw_type_error;
+use mozjs::gc::Traceable;
+use mozjs::jsapi::{CallArgs, GCReason, Heap, JSFunctionSpec, JSNativeWrapper, JSObject};
+use mozjs::jsapi::{JSPropertySpec_Name, OnNewGlobalHookOption, Value, JSPROP_ENUMERATE};
+use mozjs::jsval::{Int32Value, ObjectValue, UndefinedValue};
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_GetProperty, JS_NewGlobalObject, JS_NewPlainObject};
+use mozjs::rust::wrappers2::{JS_SetProperty, JS_GC};
+use mozjs::rust::{Handle, HandleObject};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+use common::check;
+
+mod common;
+
+#[derive(Traceable)]
+struct Counter {
+    count: Cell<i32>,
+}
+
+unsafe extern "C" fn counter_constructor(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    let args = CallArgs::from_vp(vp, argc);
+    let start = match i32::from_jsval(
+        cx,
+        Handle::from_raw(args.get(0)),
+        ConversionBehavior::Default,
+    ) {
+        Ok(ConversionResult::Success(start)) => start,
+        Ok(ConversionResult::Failure(reason)) => {
+            throw_type_error(cx, &reason);
+            return false;
+        }
+        Err(()) => return false,
+    };
+    let mut cx = JSContext::from_ptr(NonNull::new(cx).unwrap());
+    let counter = Counter {
+        count: Cell::new(start),
+    };
+    let obj = JsClass::new_object_for_constructor(&mut cx, &args, counter);
+    if obj.is_null() {
+        return false;
+    }
+    args.rval().set(ObjectValue(obj));
+    true
+}
+
+unsafe extern "C" fn counter_increment(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    let args = CallArgs::from_vp(vp, argc);
+    let counter = args
+        .thisv()
+        .is_object()
+        .then(|| unwrap::<Counter>(args.thisv().to_object()))
+        .flatten();
+    let Some(counter) = counter else {
+        throw_type_error(cx, c"not a Counter");
+        return false;
+    };
+    counter.count.set(counter.count.get() + 1);
+    args.rval().set(Int32Value(counter.count.get()));
+    true
+}
+
+const COUNTER_METHODS: &[JSFunctionSpec] = &[
+    JSFunctionSpec {
+        name: JSPropertySpec_Name {
+            string_: c"increment".as_ptr(),
+        },
+        call: JSNativeWrapper {
+            op: Some(counter_increment),
+            info: ptr::null(),
+        },
+        nargs: 0,
+        flags: JSPROP_ENUMERATE as u16,
+        selfHostedName: ptr::null(),
+    },
+    JSFunctionSpec::ZERO,
+];
+
+/// Holds an object that is only reachable through it, and sets its flag when
+/// it is dropped.
+#[derive(Traceable)]
+struct Holder {
+    object: Heap<*mut JSObject>,
+    #[no_trace]
+    dropped: Rc<Cell<bool>>,
+}
+
+impl Drop for Holder {
+    fn drop(&mut self) {
+        self.dropped.set(true);
+    }
+}
+
+#[test]
+fn js_class() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        let global = global.handle();
+
+        rooted!(&in(cx) let proto = JsClass::<Counter>::new(c"Counter")
+            .constructor(Some(counter_constructor), 1)
+            .methods(COUNTER_METHODS)
+            .init(cx, global, HandleObject::null()));
+        assert!(!proto.get().is_null());
+        assert!(check(
+            cx,
+            global,
+            "let c = new Counter(5); c instanceof Counter && Counter.length === 1 && c.increment() === 6 && c.increment() === 7",
+        ));
+        assert!(check(
+            cx,
+            global,
+            "(() => { try { Counter.prototype.increment.call({}); } catch (e) { return e instanceof TypeError; } })()",
+        ));
+
+        // Instances created from Rust share the class.
+        rooted!(&in(cx) let counter = JsClass::new_object(cx, proto.handle(), Counter {
+            count: Cell::new(41),
+        }));
+        assert_eq!(unwrap::<Counter>(counter.get()).unwrap().count.get(), 41);
+        assert!(unwrap::<Counter>(proto.get()).is_none());
+        assert!(unwrap::<Holder>(counter.get()).is_none());
+
+        // Classes without a constructor cannot be constructed from JavaScript.
+        rooted!(&in(cx) let holder_proto = JsClass::<Holder>::new(c"Holder")
+            .init(cx, global, HandleObject::null()));
+        assert!(!holder_proto.get().is_null());
+        assert!(check(
+            cx,
+            global,
+            "(() => { try { new Holder(); } catch (e) { return e instanceof TypeError; } })()",
+        ));
+
+        // The value is traced while the instance is alive, and dropped when
+        // it is collected.
+        let dropped = Rc::new(Cell::new(false));
+        rooted!(&in(cx) let mut holder = JsClass::new_object(cx, holder_proto.handle(), Holder {
+            object: Heap::default(),
+            dropped: dropped.clone(),
+        }));
+        rooted!(&in(cx) let mut object = JS_NewPlainObject(cx));
+        rooted!(&in(cx) let mut value = Int32Value(7));
+        assert!(JS_SetProperty(
+            cx,
+            object.handle(),
+            c"marker".as_ptr(),
+            value.handle()
+        ));
+        unwrap::<Holder>(holder.get())
+            .unwrap()
+            .object
+            .set(object.get());
+        object.set(ptr::null_mut());
+        JS_GC(cx, GCReason::API);
+        assert!(!dropped.get());
+        object.set(unwrap::<Holder>(holder.get()).unwrap().object.get());
+        value.set(UndefinedValue());
+        assert!(JS_GetProperty(
+            cx,
+            object.handle(),
+            c"marker".as_ptr(),
+            value.handle_mut()
+        ));
+        assert_eq!(value.get().to_int32(), 7);
+        object.set(ptr::null_mut());
+        holder.set(ptr::null_mut());
+        JS_GC(cx, GCReason::API);
+        assert!(dropped.get());
+    }
+}
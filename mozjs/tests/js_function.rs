@@ -0,0 +1,117 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::ptr;
+use std::rc::Rc;
+
+use mozjs::context::JSContext;
+use mozjs::error::Exception;
+use mozjs::function::JsFunction;
+use mozjs::jsapi::{GCReason, JSExnType, JSObject, OnNewGlobalHookOption};
+use mozjs::jsval::ObjectValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_NewGlobalObject, JS_SetProperty, JS_GC};
+use mozjs::rust::Handle;
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+use common::check;
+
+mod common;
+
+fn define(cx: &mut JSContext, global: Handle<*mut JSObject>, name: &CStr, function: &JsFunction) {
+    rooted!(&in(cx) let value = ObjectValue(function.handle().get()));
+    assert!(unsafe { JS_SetProperty(cx, global, name.as_ptr(), value.handle()) });
+}
+
+struct DropFlag(Rc<Cell<bool>>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+#[test]
+fn js_function() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        let global = global.handle();
+
+        let add = JsFunction::new(cx, c"add", |_cx, (a, b): (i32, i32)| Ok(a + b)).unwrap();
+        define(cx, global, c"add", &add);
+        assert!(check(cx, global, "add.length === 2 && add(2, 3) === 5"));
+        // Missing arguments are undefined.
+        assert!(check(cx, global, "add(2) === 2"));
+
+        let greet = JsFunction::new(cx, c"greet", |_cx, (name,): (String,)| {
+            Ok(format!("Hello, {}!", name))
+        })
+        .unwrap();
+        define(cx, global, c"greet", &greet);
+        assert!(check(cx, global, "greet('world') === 'Hello, world!'"));
+
+        // Conversion failures throw a TypeError.
+        let sum = JsFunction::new(cx, c"sum", |_cx, (values,): (Vec<i32>,)| {
+            Ok(values.iter().sum::<i32>())
+        })
+        .unwrap();
+        define(cx, global, c"sum", &sum);
+        assert!(check(
+            cx,
+            global,
+            "sum([1, 2, 3]) === 6 && (() => { try { sum(5); } catch (e) { return e instanceof TypeError; } })()",
+        ));
+
+        // Returned exceptions are thrown to the caller.
+        let checked = JsFunction::new(cx, c"checked", |cx, (value,): (u32,)| {
+            if value > 10 {
+                Err(Exception::new_error(
+                    cx.raw_cx(),
+                    JSExnType::JSEXN_RANGEERR,
+                    format_args!("{} is too big", value),
+                ))
+            } else {
+                Ok(value)
+            }
+        })
+        .unwrap();
+        define(cx, global, c"checked", &checked);
+        assert!(check(
+            cx,
+            global,
+            "checked(3) === 3 && (() => { try { checked(11); } catch (e) { return e instanceof RangeError && e.message === '11 is too big'; } })()",
+        ));
+
+        // The closure is dropped when the function is collected.
+        let dropped = Rc::new(Cell::new(false));
+        let flag = DropFlag(dropped.clone());
+        let function = JsFunction::new(cx, c"unused", move |_cx, ()| Ok(flag.0.get())).unwrap();
+        JS_GC(cx, GCReason::API);
+        assert!(!dropped.get());
+        drop(function);
+        JS_GC(cx, GCReason::API);
+        assert!(dropped.get());
+    }
+}
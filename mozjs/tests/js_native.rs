@@ -0,0 +1,124 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use mozjs::context::JSContext;
+use mozjs::error::Exception;
+use mozjs::function::js_native;
+use mozjs::jsapi::{JSExnType, JSFunctionSpec, OnNewGlobalHookOption};
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::define_methods;
+use mozjs::rust::wrappers2::JS_NewGlobalObject;
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+use common::check;
+
+mod common;
+
+#[js_native]
+fn repeat(text: String, count: Option<f64>) -> String {
+    text.repeat(count.unwrap_or(1.0) as usize)
+}
+
+#[js_native(name = "byteAt")]
+fn byte_at(#[js(enforce_range)] index: u32, bytes: Vec<u8>) -> Option<u8> {
+    bytes.get(index as usize).copied()
+}
+
+#[js_native]
+fn encode(cx: &mut JSContext, text: String, limit: Option<f64>) -> Result<Vec<u8>, Exception> {
+    match limit {
+        Some(limit) if text.len() as f64 > limit => Err(unsafe {
+            Exception::new_error(
+                cx.raw_cx(),
+                JSExnType::JSEXN_RANGEERR,
+                format_args!("{} is longer than {}", text, limit),
+            )
+        }),
+        _ => Ok(text.into_bytes()),
+    }
+}
+
+#[js_native]
+fn fail() {
+    panic!("fail");
+}
+
+const METHODS: &[JSFunctionSpec] = &[
+    repeat::SPEC,
+    byte_at::SPEC,
+    encode::SPEC,
+    fail::SPEC,
+    JSFunctionSpec::ZERO,
+];
+
+#[test]
+fn js_native() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        let global = global.handle();
+        assert!(define_methods(cx.raw_cx(), global, METHODS).is_ok());
+
+        // Trailing options are optional, and do not count towards `length`.
+        assert!(check(
+            cx,
+            global,
+            "repeat.length === 1 && repeat('ab') === 'ab' && repeat('ab', 3) === 'ababab'",
+        ));
+        assert!(check(
+            cx,
+            global,
+            "(() => { try { repeat(); } catch (e) { return e instanceof TypeError && e.message === 'repeat: At least 1 argument required, but only 0 passed'; } })()",
+        ));
+
+        assert!(check(
+            cx,
+            global,
+            "byteAt.length === 2 && byteAt(1, [4, 5]) === 5 && byteAt(2, [4, 5]) === null",
+        ));
+        assert!(check(
+            cx,
+            global,
+            "(() => { try { byteAt(-1, []); } catch (e) { return e instanceof TypeError; } })()",
+        ));
+        // Conversion failures name the argument.
+        assert!(check(
+            cx,
+            global,
+            "(() => { try { byteAt(0, 5); } catch (e) { return e instanceof TypeError && e.message.startsWith('byteAt: argument 2: '); } })()",
+        ));
+
+        // Returned exceptions are thrown to the caller.
+        assert!(check(
+            cx,
+            global,
+            "encode('hi').join() === '104,105' && (() => { try { encode('hi', 1); } catch (e) { return e instanceof RangeError && e.message === 'hi is longer than 1'; } })()",
+        ));
+
+        // Panics resume unwinding once the script returns.
+        let panic = catch_unwind(AssertUnwindSafe(|| check(cx, global, "fail()")));
+        assert!(panic.is_err());
+    }
+}
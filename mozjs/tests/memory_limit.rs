@@ -0,0 +1,97 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use mozjs::context::{JSContext, RawJSContext};
+use mozjs::error::ExecutionError;
+use mozjs::jsapi::{GCReason, JSObject, OnNewGlobalHookOption};
+use mozjs::jsval::UndefinedValue;
+use mozjs::memory::set_realm_name;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{GetCurrentRealmOrNull, JS_NewGlobalObject, JS_GC};
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper, Handle, JSEngine, RealmOptions};
+use mozjs::rust::{Runtime, SIMPLE_GLOBAL_CLASS};
+
+const MAX_HEAP_BYTES: u32 = 64 * 1024 * 1024;
+
+fn run(
+    cx: &mut JSContext,
+    global: Handle<*mut JSObject>,
+    source: &str,
+) -> Result<(), ExecutionError> {
+    rooted!(&in(cx) let mut rval = UndefinedValue());
+    let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+    evaluate_script(cx, global, source, rval.handle_mut(), options)?;
+    Ok(())
+}
+
+/// How often the embedder's out-of-memory callback was called.
+static OUT_OF_MEMORY_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn count_out_of_memory(_cx: *mut RawJSContext, _data: *mut c_void) {
+    OUT_OF_MEMORY_CALLS.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn memory_limit() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::builder()
+        .max_heap_bytes(MAX_HEAP_BYTES)
+        .out_of_memory_callback(Some(count_out_of_memory), ptr::null_mut())
+        .build(engine.handle());
+    assert_eq!(runtime.max_heap_bytes(), MAX_HEAP_BYTES);
+    let context = runtime.cx();
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        let global = global.handle();
+        set_realm_name(GetCurrentRealmOrNull(cx), "limited");
+
+        let result = run(
+            cx,
+            global,
+            "(() => { const objects = []; for (;;) objects.push({}); })()",
+        );
+        match result {
+            Err(ExecutionError::OutOfMemory(out_of_memory)) => {
+                assert_eq!(out_of_memory.realm_name(), Some("limited"));
+                // The embedder's callback is called after the runtime's.
+                assert!(OUT_OF_MEMORY_CALLS.load(Ordering::SeqCst) > 0);
+            }
+            other => panic!("expected to run out of memory, got {:?}", other),
+        }
+
+        // Once the objects are collected, scripts can allocate again.
+        JS_GC(cx, GCReason::API);
+        assert!(run(
+            cx,
+            global,
+            "(() => { const objects = []; for (let i = 0; i < 1000; i++) objects.push({}); })()",
+        )
+        .is_ok());
+
+        // Other errors are reported as exceptions.
+        match run(cx, global, "throw new Error('oops')") {
+            Err(ExecutionError::Exception(exception)) => {
+                assert!(!exception.is_out_of_memory());
+                assert_eq!(exception.message(), "oops");
+            }
+            other => panic!("expected an exception, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+
+use mozjs::context::JSContext;
+use mozjs::jsapi::{JSObject, OnNewGlobalHookOption, Realm};
+use mozjs::jsval::UndefinedValue;
+use mozjs::memory::{memory_report, set_realm_name, MemoryReport, RealmMemory};
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{GetCurrentRealmOrNull, JS_NewGlobalObject};
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper, Handle, JSEngine, RealmOptions};
+use mozjs::rust::{Runtime, SIMPLE_GLOBAL_CLASS};
+
+/// Name the realm of `global` and keep `count` objects and strings alive in
+/// it. Returns the realm.
+fn populate(
+    cx: &mut JSContext,
+    global: Handle<*mut JSObject>,
+    name: &str,
+    count: usize,
+) -> *mut Realm {
+    let mut realm = AutoRealm::new_from_handle(cx, global);
+    let cx = &mut *realm;
+    let current = unsafe { GetCurrentRealmOrNull(cx) };
+    set_realm_name(current, name);
+
+    rooted!(&in(cx) let mut rval = UndefinedValue());
+    let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+    let script = format!(
+        "globalThis.kept = []; for (let i = 0; i < {count}; i++) kept.push({{ name: 'item ' + i }});"
+    );
+    assert!(evaluate_script(cx, global, &script, rval.handle_mut(), options).is_ok());
+    current
+}
+
+fn realm<'a>(report: &'a MemoryReport, name: &str) -> &'a RealmMemory {
+    report
+        .realms
+        .iter()
+        .find(|realm| realm.name.as_deref() == Some(name))
+        .unwrap()
+}
+
+#[test]
+fn runtime_memory_report() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    let (small, large, report) = unsafe {
+        rooted!(&in(context) let small = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        rooted!(&in(context) let large = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let small = populate(context, small.handle(), "small", 10);
+        let large = populate(context, large.handle(), "large", 100_000);
+        // Measure while both globals are rooted.
+        (small, large, memory_report(context).unwrap())
+    };
+
+    assert!(report.total.gc_heap_used > 0);
+    assert!(report.total.gc_heap() >= report.total.gc_heap_used);
+    assert!(report.total.total() >= report.objects.total() + report.strings.total());
+    assert!(report.objects.gc_heap_used > 0);
+    assert!(report.strings.gc_heap_used > 0);
+    assert!(report.shapes.gc_heap_used > 0);
+
+    // Realms are told apart by name.
+    assert_eq!(realm(&report, "small").realm, small);
+    assert_eq!(realm(&report, "large").realm, large);
+    assert!(realm(&report, "large").sizes.total() > realm(&report, "small").sizes.total());
+
+    // The runtime measures itself the same way.
+    assert!(runtime.memory_report().unwrap().total.gc_heap_used > 0);
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_value(&report).unwrap();
+        assert!(json["total"]["gc_heap_used"].as_u64().unwrap() > 0);
+        let realms = json["realms"].as_array().unwrap();
+        assert!(realms.iter().any(|realm| realm["name"] == "large"
+            && realm["sizes"]["gc_heap_used"].as_u64().unwrap() > 0));
+    }
+}
@@ -0,0 +1,90 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::fs;
+use std::ptr;
+
+use mozjs::jobs::{run_jobs, MicrotaskQueue};
+use mozjs::jsapi::{CallArgs, JSContext, OnNewGlobalHookOption, Value};
+use mozjs::jsval::{Int32Value, UndefinedValue};
+use mozjs::modules::{evaluate_module, FilesystemLoader, NativeModule};
+use mozjs::rooted;
+use mozjs::rust::wrappers2::JS_NewGlobalObject;
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+unsafe extern "C" fn add(_cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool {
+    let args = CallArgs::from_vp(vp, argc);
+    let sum = args.get(0).to_int32() + args.get(1).to_int32();
+    args.rval().set(Int32Value(sum));
+    true
+}
+
+#[test]
+fn module_types() {
+    let root = std::env::temp_dir().join(format!("mozjs-module-types-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(
+        root.join("main.js"),
+        r#"import config from "./config.json" with { type: "json" };
+           import { add, version, "odd name" as odd } from "native:math";
+           globalThis.result = add(config.answer, version) + odd;
+           import("./config.json")
+               .catch(e => globalThis.jsonAsScript = e instanceof TypeError);
+           import("native:math", { with: { type: "json" } })
+               .catch(e => globalThis.nativeAsJson = e instanceof TypeError);
+           import("native:math", { with: { type: "native" } })
+               .catch(e => globalThis.customType = e instanceof TypeError);"#,
+    )
+    .unwrap();
+    fs::write(root.join("config.json"), r#"{ "answer": 40 }"#).unwrap();
+
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    runtime.set_job_queue(MicrotaskQueue::default());
+    runtime.set_module_loader(FilesystemLoader::new(&root).unwrap());
+    runtime.register_synthetic_module(
+        "native:math",
+        NativeModule::new()
+            .function("add", Some(add), 2)
+            .value("version", 1)
+            .value("odd name", 1),
+    );
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+
+        assert!(evaluate_module(context, global.handle(), "main.js").is_ok());
+        run_jobs(context);
+
+        rooted!(&in(context) let mut rval = UndefinedValue());
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global.handle(),
+            "result === 42 && jsonAsScript && nativeAsJson && customType",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        assert!(rval.get().to_boolean());
+    }
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,175 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::fs;
+use std::ptr;
+
+use mozjs::context::JSContext;
+use mozjs::glue::JS_GetReservedSlot;
+use mozjs::jobs::MicrotaskQueue;
+use mozjs::jsapi::{
+    GCReason, OnNewGlobalHookOption, PromiseState, JSCLASS_GLOBAL_APPLICATION_SLOTS,
+};
+use mozjs::jsval::UndefinedValue;
+use mozjs::memory::{memory_report, set_realm_name};
+use mozjs::modules::{evaluate_module, FilesystemLoader, ModuleLoader};
+use mozjs::promise::block_on;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{GetCurrentRealmOrNull, JS_NewGlobalObject, JS_GC};
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+fn realm_alive(cx: &mut JSContext, name: &str) -> bool {
+    let report = memory_report(cx).unwrap();
+    report
+        .realms
+        .iter()
+        .any(|realm| realm.name.as_deref() == Some(name))
+}
+
+/// A [`FilesystemLoader`] keeping the module map in the first application
+/// slot of the global.
+struct FirstSlotLoader(FilesystemLoader);
+
+impl ModuleLoader for FirstSlotLoader {
+    fn resolve(&self, specifier: &str, referrer: Option<&str>) -> Result<String, String> {
+        self.0.resolve(specifier, referrer)
+    }
+
+    fn fetch(&self, url: &str) -> Result<String, String> {
+        self.0.fetch(url)
+    }
+
+    fn module_map_slot(&self) -> u32 {
+        0
+    }
+}
+
+#[test]
+fn modules() {
+    let root = std::env::temp_dir().join(format!("mozjs-modules-{}", std::process::id()));
+    fs::create_dir_all(root.join("lib")).unwrap();
+    fs::write(
+        root.join("main.js"),
+        "import { square } from './lib/math.js';
+         import { counter } from '/lib/counter.js';
+         globalThis.result = square(6) + counter;",
+    )
+    .unwrap();
+    fs::write(
+        root.join("lib/math.js"),
+        "import { counter } from './counter.js';
+         export function square(x) { return x * x; }
+         export const used = counter;",
+    )
+    .unwrap();
+    fs::write(
+        root.join("lib/counter.js"),
+        "globalThis.loads = (globalThis.loads || 0) + 1;
+         export const counter = globalThis.loads * 6;",
+    )
+    .unwrap();
+    fs::write(root.join("escape.js"), "import '../outside.js';").unwrap();
+    fs::write(
+        root.join("await.js"),
+        "globalThis.before = true;
+         await null;
+         globalThis.after = true;",
+    )
+    .unwrap();
+    fs::write(root.join("reject.js"), "await null; throw 7;").unwrap();
+
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    runtime.set_job_queue(MicrotaskQueue::default());
+    runtime.set_module_loader(FirstSlotLoader(FilesystemLoader::new(&root).unwrap()));
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+
+        assert!(evaluate_module(context, global.handle(), "main.js").is_ok());
+
+        // The module map is kept in the slot chosen by the loader.
+        let mut slot = UndefinedValue();
+        JS_GetReservedSlot(global.get(), 0, &mut slot);
+        assert!(slot.is_object());
+        JS_GetReservedSlot(
+            global.get(),
+            JSCLASS_GLOBAL_APPLICATION_SLOTS - 1,
+            &mut slot,
+        );
+        assert!(slot.is_undefined());
+
+        rooted!(&in(context) let mut rval = UndefinedValue());
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global.handle(),
+            "result === 42 && loads === 1",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        assert!(rval.get().to_boolean());
+
+        assert!(evaluate_module(context, global.handle(), "escape.js").is_err());
+        assert!(evaluate_module(context, global.handle(), "missing.js").is_err());
+
+        // Modules using top-level await finish as the job queue is drained.
+        let evaluation = evaluate_module(context, global.handle(), "await.js").unwrap();
+        assert_eq!(evaluation.state(), PromiseState::Pending);
+        assert!(block_on(context, evaluation).is_ok());
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global.handle(),
+            "before && after",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        assert!(rval.get().to_boolean());
+
+        // Errors after the first await reject the evaluation promise.
+        let evaluation = evaluate_module(context, global.handle(), "reject.js").unwrap();
+        let exception = block_on(context, evaluation).unwrap_err();
+        assert_eq!(exception.value().get().to_int32(), 7);
+    }
+
+    // The module map of a realm does not keep its global alive.
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let realm = AutoRealm::new_from_handle(context, global.handle());
+        set_realm_name(GetCurrentRealmOrNull(&realm), "importer");
+        drop(realm);
+        assert!(evaluate_module(context, global.handle(), "main.js").is_ok());
+    }
+    assert!(realm_alive(context, "importer"));
+    unsafe { JS_GC(context, GCReason::API) };
+    assert!(!realm_alive(context, "importer"));
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,139 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr::{self, NonNull};
+
+use mozjs::context::{JSContext, RawJSContext};
+use mozjs::jobs::MicrotaskQueue;
+use mozjs::jsapi::{CallArgs, OnNewGlobalHookOption, PromiseState, Value};
+use mozjs::jsval::{ObjectValue, UndefinedValue};
+use mozjs::promise::{block_on, run_until_stalled, JsPromise};
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_DefineFunction, JS_NewGlobalObject};
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+#[test]
+fn promise() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    runtime.set_job_queue(MicrotaskQueue::default());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let (global_handle, realm) = realm.global_and_reborrow();
+        let context = realm;
+
+        let function = JS_DefineFunction(
+            context,
+            global_handle,
+            c"double".as_ptr(),
+            Some(double),
+            1,
+            0,
+        );
+        assert!(!function.is_null());
+
+        rooted!(&in(context) let mut rval = UndefinedValue());
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global_handle,
+            "(async () => { await null; return 42; })()",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        rooted!(&in(context) let object = rval.get().to_object());
+        let promise = JsPromise::from_object(object.handle()).unwrap();
+        assert_eq!(promise.state(), PromiseState::Pending);
+        let value = block_on(context, promise).unwrap();
+        assert_eq!(value.get().to_int32(), 42);
+
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global_handle,
+            "Promise.reject(7)",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        rooted!(&in(context) let object = rval.get().to_object());
+        let promise = JsPromise::from_object(object.handle()).unwrap();
+        let exception = block_on(context, promise).unwrap_err();
+        assert_eq!(exception.value().get().to_int32(), 7);
+        assert_eq!(exception.message(), "7");
+
+        // Rejection reasons are described like thrown exceptions.
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global_handle,
+            "(async () => { await null; throw new TypeError('bad'); })()",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        rooted!(&in(context) let object = rval.get().to_object());
+        let promise = JsPromise::from_object(object.handle()).unwrap();
+        let exception = block_on(context, promise).unwrap_err();
+        assert_eq!(exception.name(), Some("TypeError"));
+        assert_eq!(exception.message(), "bad");
+        assert!(exception.to_string().starts_with("test:1:"));
+        assert!(exception.stack().is_some());
+
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            context,
+            global_handle,
+            "var result; double(21).then(v => result = v); result",
+            rval.handle_mut(),
+            options,
+        )
+        .is_ok());
+        assert!(rval.get().is_undefined());
+
+        run_until_stalled(context);
+
+        let options = CompileOptionsWrapper::new(&context, c"test".to_owned(), 1);
+        assert!(
+            evaluate_script(context, global_handle, "result", rval.handle_mut(), options,).is_ok()
+        );
+        assert_eq!(rval.get().to_int32(), 42);
+
+        // A task that never completes is dropped along with the runtime.
+        let pending =
+            JsPromise::from_future(context, std::future::pending::<Result<i32, ()>>()).unwrap();
+        assert_eq!(pending.state(), PromiseState::Pending);
+    }
+}
+
+unsafe extern "C" fn double(context: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    let mut context = JSContext::from_ptr(NonNull::new(context).unwrap());
+    let args = CallArgs::from_vp(vp, argc);
+    let n = args.get(0).to_int32();
+
+    let Some(promise) = JsPromise::from_future(&mut context, async move { Ok::<_, ()>(n * 2) })
+    else {
+        return false;
+    };
+    args.rval().set(ObjectValue(promise.handle().get()));
+    true
+}
@@ -0,0 +1,191 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::cell::Cell;
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+
+use mozjs::context::JSContext;
+use mozjs::conversions::jsstr_to_string;
+use mozjs::jsapi::{GCReason, MutableHandleIdVector, OnNewGlobalHookOption};
+use mozjs::jsapi::{PropertyDescriptor, JSPROP_ENUMERATE, JSPROP_READONLY};
+use mozjs::jsid::VoidId;
+use mozjs::jsval::{Int32Value, ObjectValue, StringValue, UndefinedValue};
+use mozjs::proxy::{JsProxyHandler, ProxyHandler};
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{AppendToIdVector, JS_AtomizeAndPinString, JS_NewStringCopyN};
+use mozjs::rust::wrappers2::{JS_NewGlobalObject, JS_SetProperty, JS_GC};
+use mozjs::rust::wrappers2::{SetDataPropertyDescriptor, RUST_JSID_TO_STRING};
+use mozjs::rust::wrappers2::{RUST_INTERNED_STRING_TO_JSID, RUST_JSID_IS_STRING};
+use mozjs::rust::{HandleId, HandleObject};
+use mozjs::rust::{HandleValue, JSEngine, MutableHandle, MutableHandleValue, RealmOptions};
+use mozjs::rust::{Runtime, SIMPLE_GLOBAL_CLASS};
+
+use common::check;
+
+mod common;
+
+fn name_of(cx: &mut JSContext, id: HandleId) -> Option<String> {
+    unsafe {
+        if !RUST_JSID_IS_STRING(id) {
+            return None;
+        }
+        let name = NonNull::new(RUST_JSID_TO_STRING(id)).unwrap();
+        Some(jsstr_to_string(cx.raw_cx(), name))
+    }
+}
+
+/// An object with a single read-only property, `answer`.
+struct Answer {
+    dropped: Rc<Cell<bool>>,
+}
+
+impl Drop for Answer {
+    fn drop(&mut self) {
+        self.dropped.set(true);
+    }
+}
+
+impl ProxyHandler for Answer {
+    fn get_own_property_descriptor(
+        &self,
+        cx: &mut JSContext,
+        _proxy: HandleObject,
+        id: HandleId,
+        desc: MutableHandle<PropertyDescriptor>,
+        is_none: &mut bool,
+    ) -> bool {
+        *is_none = name_of(cx, id).as_deref() != Some("answer");
+        if !*is_none {
+            rooted!(&in(cx) let value = Int32Value(42));
+            let attrs = JSPROP_ENUMERATE | JSPROP_READONLY;
+            unsafe { SetDataPropertyDescriptor(desc, value.handle(), attrs as u32) };
+        }
+        true
+    }
+
+    fn own_property_keys(
+        &self,
+        cx: &mut JSContext,
+        _proxy: HandleObject,
+        props: MutableHandleIdVector,
+    ) -> bool {
+        unsafe {
+            let name = JS_AtomizeAndPinString(cx, c"answer".as_ptr());
+            if name.is_null() {
+                return false;
+            }
+            rooted!(&in(cx) let mut id = VoidId());
+            RUST_INTERNED_STRING_TO_JSID(cx, name, id.handle_mut());
+            AppendToIdVector(props, id.handle())
+        }
+    }
+}
+
+/// An object whose properties are their own names.
+struct Echo;
+
+impl ProxyHandler for Echo {
+    fn get(
+        &self,
+        cx: &mut JSContext,
+        _proxy: HandleObject,
+        _receiver: HandleValue,
+        id: HandleId,
+        mut vp: MutableHandleValue,
+    ) -> bool {
+        let name = name_of(cx, id).unwrap_or_default();
+        let name = unsafe { JS_NewStringCopyN(cx, name.as_ptr() as *const _, name.len()) };
+        if name.is_null() {
+            return false;
+        }
+        vp.set(StringValue(unsafe { &*name }));
+        true
+    }
+}
+
+#[test]
+fn proxy_handler() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        let global = global.handle();
+
+        let dropped = Rc::new(Cell::new(false));
+        let answer = JsProxyHandler::new(Answer {
+            dropped: dropped.clone(),
+        });
+        rooted!(&in(cx) let mut proxy = answer.new_proxy(cx, HandleValue::undefined(), HandleObject::null()));
+        assert!(!proxy.get().is_null());
+        rooted!(&in(cx) let mut value = ObjectValue(proxy.get()));
+        assert!(JS_SetProperty(
+            cx,
+            global,
+            c"answer".as_ptr(),
+            value.handle()
+        ));
+
+        // `[[Get]]` and `[[HasProperty]]` default to the own properties.
+        assert!(check(
+            cx,
+            global,
+            "answer.answer === 42 && 'answer' in answer && !('question' in answer) && answer.question === undefined",
+        ));
+        assert!(check(
+            cx,
+            global,
+            "Object.keys(answer).join() === 'answer' && Object.getPrototypeOf(answer) === null",
+        ));
+        assert!(check(
+            cx,
+            global,
+            "(() => { 'use strict'; try { answer.answer = 1; } catch (e) { return e instanceof TypeError && answer.answer === 42; } })()",
+        ));
+        assert!(check(
+            cx,
+            global,
+            "(() => { 'use strict'; try { Object.preventExtensions(answer); } catch (e) { return e instanceof TypeError && Object.isExtensible(answer); } })()",
+        ));
+
+        rooted!(&in(cx) let echo_proxy = JsProxyHandler::new(Echo)
+            .new_proxy(cx, HandleValue::undefined(), HandleObject::null()));
+        value.set(ObjectValue(echo_proxy.get()));
+        assert!(JS_SetProperty(cx, global, c"echo".as_ptr(), value.handle()));
+        assert!(check(
+            cx,
+            global,
+            "echo.hello === 'hello' && echo[1] === '' && !('hello' in echo)",
+        ));
+
+        // The handler outlives the `JsProxyHandler` until its proxies are
+        // collected.
+        drop(answer);
+        JS_GC(cx, GCReason::API);
+        assert!(!dropped.get());
+        assert!(check(cx, global, "answer.answer === 42"));
+        assert!(check(cx, global, "delete globalThis.answer"));
+        proxy.set(ptr::null_mut());
+        value.set(UndefinedValue());
+        JS_GC(cx, GCReason::API);
+        assert!(dropped.get());
+    }
+}
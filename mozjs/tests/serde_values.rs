@@ -0,0 +1,138 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#![cfg(feature = "serde")]
+
+use std::collections::{BTreeMap, HashMap};
+use std::ptr;
+
+use mozjs::jsapi::OnNewGlobalHookOption;
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_NewGlobalObject, JS_SetProperty};
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+use mozjs::serde::{from_value, to_value};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Mode {
+    Idle,
+    Fixed(u32),
+    Fast { level: u8 },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Server {
+    host: String,
+    port: u16,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    name: String,
+    servers: Vec<Server>,
+    limits: BTreeMap<String, u32>,
+    owners: HashMap<u32, String>,
+    modes: Vec<Mode>,
+    payload: ByteBuf,
+    fallback: Option<Box<Config>>,
+}
+
+#[test]
+fn serde_values() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    let config = Config {
+        name: "primary".to_owned(),
+        servers: vec![Server {
+            host: "localhost".to_owned(),
+            port: 8080,
+        }],
+        limits: BTreeMap::from([("cpu".to_owned(), 2)]),
+        owners: HashMap::from([(7, "ops".to_owned())]),
+        modes: vec![Mode::Idle, Mode::Fixed(3), Mode::Fast { level: 9 }],
+        payload: ByteBuf::from(vec![1, 2, 3]),
+        fallback: None,
+    };
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+
+        rooted!(&in(cx) let mut value = UndefinedValue());
+        to_value(cx, &config, value.handle_mut()).unwrap();
+        assert_eq!(from_value::<Config>(cx, value.handle()).unwrap(), config);
+        assert!(JS_SetProperty(
+            cx,
+            global.handle(),
+            c"config".as_ptr(),
+            value.handle()
+        ));
+
+        let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            cx,
+            global.handle(),
+            "config.servers[0].port === 8080 && config.limits.cpu === 2 && \
+             config.owners instanceof Map && config.owners.get(7) === 'ops' && \
+             config.modes[0] === 'Idle' && config.modes[1].Fixed === 3 && \
+             config.modes[2].Fast.level === 9 && config.payload instanceof Uint8Array && \
+             config.fallback === null",
+            value.handle_mut(),
+            options,
+        )
+        .is_ok());
+        assert!(value.get().to_boolean());
+
+        let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            cx,
+            global.handle(),
+            "({ ...config, fallback: { ...config, servers: [{ host: 'a', port: 1 }, { host: 'b', port: 'http' }] } })",
+            value.handle_mut(),
+            options,
+        )
+        .is_ok());
+        let error = from_value::<Config>(cx, value.handle()).unwrap_err();
+        assert_eq!(error.path(), "fallback.servers[1].port");
+        assert_eq!(
+            error.to_string(),
+            "fallback.servers[1].port: invalid type: string \"http\", expected u16"
+        );
+        assert!(!error.is_exception());
+
+        let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+        assert!(evaluate_script(
+            cx,
+            global.handle(),
+            "[{ host: 'a', port: 1 }, 'b:2']",
+            value.handle_mut(),
+            options,
+        )
+        .is_ok());
+        let error = from_value::<Vec<Server>>(cx, value.handle()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[1]: invalid type: string \"b:2\", expected struct Server"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::ptr;
+use std::thread;
+
+use mozjs::jsapi::{JSScript, OnNewGlobalHookOption};
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_ExecuteScript, JS_NewGlobalObject};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+use mozjs::rust::{Stencil, StencilOptions};
+
+#[test]
+fn stencil() {
+    let engine = JSEngine::init().unwrap();
+
+    let workers: Vec<_> = (0..4)
+        .map(|i| {
+            thread::spawn(move || {
+                let options = StencilOptions::new(c"worker.js".to_owned(), 1);
+                Stencil::compile(&format!("{} * 10", i), &options).unwrap()
+            })
+        })
+        .collect();
+    let stencils: Vec<Stencil> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+
+    let options = StencilOptions::new(c"broken.js".to_owned(), 1);
+    let error = Stencil::compile("let = ;", &options).err().unwrap();
+    assert_eq!(error.filename, "broken.js");
+    assert_eq!(error.line, 1);
+
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+
+        for (i, stencil) in stencils.iter().enumerate() {
+            rooted!(&in(context) let mut script = ptr::null_mut::<JSScript>());
+            assert!(stencil
+                .instantiate(context, global.handle(), script.handle_mut())
+                .is_ok());
+            let mut realm = AutoRealm::new_from_handle(context, global.handle());
+            let cx = &mut *realm;
+            rooted!(&in(cx) let mut rval = UndefinedValue());
+            assert!(JS_ExecuteScript(cx, script.handle(), rval.handle_mut()));
+            assert_eq!(rval.get().to_int32(), i as i32 * 10);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::fs;
+use std::ptr;
+use std::thread;
+
+use mozjs::jsapi::{JSScript, OnNewGlobalHookOption};
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_ExecuteScript, JS_NewGlobalObject};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+use mozjs::rust::{Stencil, StencilOptions, TranscodeError};
+use mozjs::stencil_cache::{cache_key, DirectoryCache, StencilCache};
+
+#[test]
+fn stencil_cache() {
+    let dir = std::env::temp_dir().join(format!("mozjs-stencil-cache-{}", std::process::id()));
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    let source = "function f() { return 21; } f() * 2";
+    let options = StencilOptions::new(c"cached.js".to_owned(), 1);
+    let stencil = Stencil::compile(source, &options).unwrap();
+    let mut bytes = stencil.encode(context).unwrap();
+    let decoded = Stencil::decode(&bytes).unwrap();
+
+    // Entries from another build are rejected.
+    bytes[4] ^= 0xff;
+    assert_eq!(
+        Stencil::decode(&bytes).err(),
+        Some(TranscodeError::BadBuildId)
+    );
+
+    let cache = DirectoryCache::new(&dir).unwrap();
+    let compiled = cache.get_or_compile(context, source, &options).unwrap();
+    let key = cache_key(source, &options);
+    assert_eq!(key.len(), 64);
+    let renamed = StencilOptions::new(c"renamed.js".to_owned(), 1);
+    assert_ne!(key, cache_key(source, &renamed));
+    assert!(dir.join(format!("{}.stencil", key)).exists());
+
+    // Concurrent writers of the same entry don't corrupt it.
+    let entry = stencil.encode(context).unwrap();
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| cache.store(&key, &entry).unwrap());
+        }
+    });
+    assert_eq!(cache.load(&key).unwrap(), entry);
+    let loaded = cache.get_or_compile(context, source, &options).unwrap();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+
+        for stencil in [&decoded, &compiled, &loaded] {
+            rooted!(&in(context) let mut script = ptr::null_mut::<JSScript>());
+            assert!(stencil
+                .instantiate(context, global.handle(), script.handle_mut())
+                .is_ok());
+            let mut realm = AutoRealm::new_from_handle(context, global.handle());
+            let cx = &mut *realm;
+            rooted!(&in(cx) let mut rval = UndefinedValue());
+            assert!(JS_ExecuteScript(cx, script.handle(), rval.handle_mut()));
+            assert_eq!(rval.get().to_int32(), 42);
+        }
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,210 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+
+use mozjs::context::JSContext;
+use mozjs::error::throw_type_error;
+use mozjs::jsapi::JSPROP_ENUMERATE;
+use mozjs::jsapi::{JSClass, JSObject, OnNewGlobalHookOption, StructuredCloneScope};
+use mozjs::jsval::{DoubleValue, ObjectValue, UndefinedValue};
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{
+    JS_DefineProperty, JS_GetProperty, JS_NewGlobalObject, JS_NewObject, JS_SetProperty,
+};
+use mozjs::rust::{evaluate_script, get_object_class, CompileOptionsWrapper, HandleObject};
+use mozjs::rust::{
+    HandleValue, JSEngine, MutableHandleValue, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS,
+};
+use mozjs::structured_clone::{self, HostObjects, StructuredCloneBuffer, SCTAG_USER_MIN};
+use mozjs::structured_clone::{StructuredCloneReader, StructuredCloneWriter};
+
+static POINT_CLASS: JSClass = JSClass {
+    name: c"Point".as_ptr(),
+    flags: 0,
+    cOps: ptr::null(),
+    spec: ptr::null(),
+    ext: ptr::null(),
+    oOps: ptr::null(),
+};
+
+const POINT_TAG: u32 = SCTAG_USER_MIN;
+
+struct Points;
+
+impl HostObjects for Points {
+    fn write(
+        &self,
+        cx: &mut JSContext,
+        writer: &mut StructuredCloneWriter,
+        obj: HandleObject,
+    ) -> bool {
+        unsafe {
+            if !ptr::eq(get_object_class(obj.get()), &POINT_CLASS) {
+                throw_type_error(cx.raw_cx(), c"Not a point");
+                return false;
+            }
+            rooted!(&in(cx) let mut x = UndefinedValue());
+            JS_GetProperty(cx, obj, c"x".as_ptr(), x.handle_mut())
+                && writer.write_pair(POINT_TAG, 0)
+                && writer.write_double(x.get().to_number())
+        }
+    }
+
+    fn read(
+        &self,
+        cx: &mut JSContext,
+        reader: &mut StructuredCloneReader,
+        tag: u32,
+        _data: u32,
+    ) -> *mut JSObject {
+        unsafe {
+            let Some(x) = (tag == POINT_TAG).then(|| reader.read_double()).flatten() else {
+                throw_type_error(cx.raw_cx(), c"Not a point");
+                return ptr::null_mut();
+            };
+            rooted!(&in(cx) let point = JS_NewObject(cx, &POINT_CLASS));
+            rooted!(&in(cx) let x = DoubleValue(x));
+            if !JS_DefineProperty(
+                cx,
+                point.handle(),
+                c"x".as_ptr(),
+                x.handle(),
+                JSPROP_ENUMERATE as u32,
+            ) {
+                return ptr::null_mut();
+            }
+            point.get()
+        }
+    }
+}
+
+#[test]
+fn structured_clone() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+
+        let eval = |cx: &mut JSContext, source: &str, rval: MutableHandleValue| {
+            let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+            assert!(evaluate_script(cx, global.handle(), source, rval, options).is_ok());
+        };
+
+        // Object graphs survive a trip through bytes.
+        rooted!(&in(cx) let mut value = UndefinedValue());
+        eval(
+            cx,
+            "var original = { list: [1, 'two', { three: 3 }], map: new Map([[1, 2]]) }; original",
+            value.handle_mut(),
+        );
+        let buffer = structured_clone::write(
+            cx,
+            value.handle(),
+            StructuredCloneScope::DifferentProcess,
+            HandleValue::undefined(),
+        )
+        .unwrap();
+        let buffer = StructuredCloneBuffer::from_bytes(&buffer.to_bytes());
+        rooted!(&in(cx) let mut copy = UndefinedValue());
+        assert!(structured_clone::read(cx, &buffer, copy.handle_mut()).is_ok());
+        assert!(JS_SetProperty(
+            cx,
+            global.handle(),
+            c"copy".as_ptr(),
+            copy.handle()
+        ));
+
+        // Transferred ArrayBuffers move to the copy.
+        eval(
+            cx,
+            "var buffer = new ArrayBuffer(4); new Uint8Array(buffer)[0] = 7; buffer",
+            value.handle_mut(),
+        );
+        rooted!(&in(cx) let mut transfer = UndefinedValue());
+        eval(cx, "[buffer]", transfer.handle_mut());
+        let buffer = structured_clone::write(
+            cx,
+            value.handle(),
+            StructuredCloneScope::SameProcess,
+            transfer.handle(),
+        )
+        .unwrap();
+        assert!(structured_clone::read(cx, &buffer, copy.handle_mut()).is_ok());
+        assert!(JS_SetProperty(
+            cx,
+            global.handle(),
+            c"moved".as_ptr(),
+            copy.handle()
+        ));
+
+        // Host objects need a HostObjects implementation.
+        rooted!(&in(cx) let point = JS_NewObject(cx, &POINT_CLASS));
+        rooted!(&in(cx) let x = DoubleValue(1.5));
+        assert!(JS_DefineProperty(
+            cx,
+            point.handle(),
+            c"x".as_ptr(),
+            x.handle(),
+            JSPROP_ENUMERATE as u32
+        ));
+        rooted!(&in(cx) let point = ObjectValue(point.get()));
+        assert!(JS_SetProperty(
+            cx,
+            global.handle(),
+            c"point".as_ptr(),
+            point.handle()
+        ));
+        assert!(structured_clone::write(
+            cx,
+            point.handle(),
+            StructuredCloneScope::DifferentProcess,
+            HandleValue::undefined(),
+        )
+        .is_err());
+
+        let buffer = structured_clone::write_with_host(
+            cx,
+            point.handle(),
+            StructuredCloneScope::DifferentProcess,
+            HandleValue::undefined(),
+            &Points,
+        )
+        .unwrap();
+        let buffer = StructuredCloneBuffer::from_bytes(&buffer.to_bytes());
+        assert!(structured_clone::read_with_host(cx, &buffer, &Points, copy.handle_mut()).is_ok());
+        assert!(JS_SetProperty(
+            cx,
+            global.handle(),
+            c"pointCopy".as_ptr(),
+            copy.handle()
+        ));
+
+        eval(
+            cx,
+            "copy !== original && copy.list[2].three === 3 && copy.map.get(1) === 2 && \
+             buffer.byteLength === 0 && new Uint8Array(moved)[0] === 7 && \
+             pointCopy !== point && pointCopy.x === 1.5",
+            value.handle_mut(),
+        );
+        assert!(value.get().to_boolean());
+    }
+}
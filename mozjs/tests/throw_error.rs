@@ -0,0 +1,142 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+
+use mozjs::context::JSContext;
+use mozjs::error::{create_error, throw_error, throw_internal_error, throw_value};
+use mozjs::jsapi::{JSExnType, JSObject, OnNewGlobalHookOption};
+use mozjs::jsval::{Int32Value, UndefinedValue};
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::{JS_ClearPendingException, JS_IsExceptionPending};
+use mozjs::rust::wrappers2::{JS_GetPendingException, JS_NewGlobalObject, JS_SetProperty};
+use mozjs::rust::{evaluate_script, CompileOptionsWrapper, Handle, HandleValue};
+use mozjs::rust::{JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS};
+
+/// Evaluate `source` with `value` bound to the global `value`, and return
+/// whether the result is `true`.
+fn check(
+    cx: &mut JSContext,
+    global: Handle<*mut JSObject>,
+    value: HandleValue,
+    source: &str,
+) -> bool {
+    assert!(unsafe { JS_SetProperty(cx, global, c"value".as_ptr(), value) });
+    rooted!(&in(cx) let mut rval = UndefinedValue());
+    let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+    assert!(evaluate_script(cx, global, source, rval.handle_mut(), options).is_ok());
+    rval.get().to_boolean()
+}
+
+/// Check the pending exception with `source`, and clear it.
+fn check_pending(cx: &mut JSContext, global: Handle<*mut JSObject>, source: &str) -> bool {
+    rooted!(&in(cx) let mut exception = UndefinedValue());
+    unsafe {
+        assert!(JS_IsExceptionPending(cx));
+        assert!(JS_GetPendingException(cx, exception.handle_mut()));
+        JS_ClearPendingException(cx);
+    }
+    check(cx, global, exception.handle(), source)
+}
+
+#[test]
+fn throw_error_types() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let context = runtime.cx();
+    #[cfg(feature = "debugmozjs")]
+    unsafe {
+        mozjs::jsapi::SetGCZeal(context.raw_cx(), 2, 1);
+    }
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        let global = global.handle();
+
+        for (kind, name) in [
+            (JSExnType::JSEXN_ERR, "Error"),
+            (JSExnType::JSEXN_INTERNALERR, "InternalError"),
+            (JSExnType::JSEXN_AGGREGATEERR, "AggregateError"),
+            (JSExnType::JSEXN_EVALERR, "EvalError"),
+            (JSExnType::JSEXN_RANGEERR, "RangeError"),
+            (JSExnType::JSEXN_REFERENCEERR, "ReferenceError"),
+            (JSExnType::JSEXN_SYNTAXERR, "SyntaxError"),
+            (JSExnType::JSEXN_TYPEERR, "TypeError"),
+            (JSExnType::JSEXN_URIERR, "URIError"),
+        ] {
+            throw_error(cx.raw_cx(), kind, format_args!("bad {}", 42));
+            assert!(check_pending(
+                cx,
+                global,
+                &format!(
+                    "value.constructor === {} && value.message === 'bad 42'",
+                    name
+                ),
+            ));
+        }
+
+        throw_error(cx.raw_cx(), JSExnType::JSEXN_AGGREGATEERR, "none");
+        assert!(check_pending(
+            cx,
+            global,
+            "Array.isArray(value.errors) && value.errors.length === 0 \
+             && !Object.keys(value).includes('errors')",
+        ));
+
+        throw_internal_error(cx.raw_cx(), c"internal");
+        assert!(check_pending(
+            cx,
+            global,
+            "value instanceof InternalError && value.message === 'internal'",
+        ));
+
+        throw_value(cx.raw_cx(), &42);
+        assert!(check_pending(cx, global, "value === 42"));
+        throw_value(cx.raw_cx(), "message");
+        assert!(check_pending(cx, global, "value === 'message'"));
+
+        rooted!(&in(cx) let cause = Int32Value(7));
+        rooted!(&in(cx) let mut error = UndefinedValue());
+        assert!(create_error(
+            cx.raw_cx(),
+            JSExnType::JSEXN_TYPEERR,
+            "wrapped",
+            Some(cause.handle()),
+            error.handle_mut(),
+        )
+        .is_ok());
+        assert!(check(
+            cx,
+            global,
+            error.handle(),
+            "value instanceof TypeError && value.message === 'wrapped' && value.cause === 7",
+        ));
+
+        assert!(create_error(
+            cx.raw_cx(),
+            JSExnType::JSEXN_ERR,
+            "no cause",
+            None,
+            error.handle_mut(),
+        )
+        .is_ok());
+        assert!(check(
+            cx,
+            global,
+            error.handle(),
+            "value instanceof Error && !('cause' in value)",
+        ));
+    }
+}
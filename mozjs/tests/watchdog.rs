@@ -0,0 +1,92 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ptr;
+use std::time::{Duration, Instant};
+
+use mozjs::context::JSContext;
+use mozjs::error::ExecutionError;
+use mozjs::jsapi::{JSObject, OnNewGlobalHookOption};
+use mozjs::jsval::UndefinedValue;
+use mozjs::realm::AutoRealm;
+use mozjs::rooted;
+use mozjs::rust::wrappers2::JS_NewGlobalObject;
+use mozjs::rust::SIMPLE_GLOBAL_CLASS;
+use mozjs::rust::{CompileOptionsWrapper, Handle, JSEngine, RealmOptions, Runtime};
+use mozjs::watchdog::{evaluate_with_budget, Watchdog};
+
+const BUDGET: Duration = Duration::from_millis(100);
+
+fn run(
+    cx: &mut JSContext,
+    watchdog: &Watchdog,
+    global: Handle<*mut JSObject>,
+    source: &str,
+) -> Result<i32, ExecutionError> {
+    rooted!(&in(cx) let mut rval = UndefinedValue());
+    let options = CompileOptionsWrapper::new(&cx, c"test".to_owned(), 1);
+    evaluate_with_budget(
+        cx,
+        watchdog,
+        global,
+        source,
+        rval.handle_mut(),
+        options,
+        BUDGET,
+    )?;
+    Ok(rval.get().to_int32())
+}
+
+#[test]
+fn watchdog() {
+    let engine = JSEngine::init().unwrap();
+    let mut runtime = Runtime::new(engine.handle());
+    let watchdog = Watchdog::new(&runtime);
+    let context = runtime.cx();
+    let h_option = OnNewGlobalHookOption::FireOnNewGlobalHook;
+    let c_option = RealmOptions::default();
+
+    unsafe {
+        rooted!(&in(context) let global = JS_NewGlobalObject(
+            context,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            h_option,
+            &*c_option,
+        ));
+        let mut realm = AutoRealm::new_from_handle(context, global.handle());
+        let cx = &mut *realm;
+        let global = global.handle();
+
+        assert_eq!(run(cx, &watchdog, global, "1 + 2").unwrap(), 3);
+
+        let start = Instant::now();
+        let result = run(cx, &watchdog, global, "for (;;) {}");
+        assert!(matches!(result, Err(ExecutionError::Timeout)));
+        assert!(start.elapsed() >= BUDGET);
+
+        // Termination cannot be caught.
+        let result = run(
+            cx,
+            &watchdog,
+            global,
+            "for (;;) { try { for (;;) {} } catch (e) {} finally { continue; } }",
+        );
+        assert!(matches!(result, Err(ExecutionError::Timeout)));
+
+        // Exceptions are still reported as such, and the watchdog can be
+        // reused once a script has been terminated.
+        let result = run(cx, &watchdog, global, "throw new Error('oops')");
+        match result {
+            Err(ExecutionError::Exception(exception)) => {
+                assert_eq!(exception.message(), "oops")
+            }
+            _ => panic!("expected an exception"),
+        }
+        assert_eq!(
+            run(cx, &watchdog, global, "let i = 0; while (i < 1000) i++; i").unwrap(),
+            1000
+        );
+    }
+}